@@ -30,15 +30,16 @@ use super::{
 };
 use crate::adt::interner::Interned;
 use crate::analysis::analyze_dyn_signature;
-use crate::path_to_url;
 use crate::syntax::{get_deref_target, resolve_id_by_path, DerefTarget};
 use crate::{
     lsp_to_typst,
     syntax::{
         construct_module_dependencies, scan_workspace_files, LexicalHierarchy, ModuleDependency,
     },
-    typst_to_lsp, LspPosition, LspRange, PositionEncoding, TypstRange, VersionedDocument,
+    typst_to_lsp, DiagnosticVerbosity, ElementValidator, LspPosition, LspRange,
+    OutOfRootDiagnostics, PositionEncoding, TypstRange, VersionedDocument,
 };
+use crate::{normalize_path, path_to_url};
 
 /// A cache for module-level analysis results of a module.
 ///
@@ -129,6 +130,25 @@ pub struct Analysis {
     pub position_encoding: PositionEncoding,
     /// The position encoding for the workspace.
     pub enable_periscope: bool,
+    /// The maximum number of diagnostics reported for a file.
+    pub max_diagnostics_per_file: Option<usize>,
+    /// Whether to warn about `let` bindings rebinding names in the same scope.
+    pub lint_redefinitions: bool,
+    /// Whether to scan the files ignored by the ignore files, such as
+    /// `.gitignore`, when scanning the workspace.
+    pub scan_ignored_files: bool,
+    /// The stable identities of the diagnostics which are not reported, see
+    /// [`crate::diagnostic_id`].
+    pub suppressed_diagnostics: HashSet<String>,
+    /// How to report the diagnostics in files outside of the root.
+    pub out_of_root_diagnostics: OutOfRootDiagnostics,
+    /// How verbose the messages of the diagnostics are.
+    pub diagnostic_verbosity: DiagnosticVerbosity,
+    /// The number of threads analyzing the files in parallel. The files are
+    /// analyzed serially if it is zero or one.
+    pub analysis_threads: usize,
+    /// The validators checking the elements in successfully compiled documents.
+    pub element_validators: Vec<Arc<dyn ElementValidator>>,
     /// The global caches for analysis.
     pub caches: AnalysisGlobalCaches,
 }
@@ -136,19 +156,62 @@ pub struct Analysis {
 impl Analysis {
     /// Get estimated memory usage of the analysis data.
     pub fn estimated_memory(&self) -> usize {
-        self.caches.modules.capacity() * 32
-            + self
-                .caches
-                .modules
-                .values()
-                .map(|v| {
-                    v.def_use_lexical_hierarchy
-                        .output
-                        .read()
-                        .as_ref()
-                        .map_or(0, |e| e.iter().map(|e| e.estimated_memory()).sum())
-                })
-                .sum::<usize>()
+        self.estimated_memory_by_cache().values().sum()
+    }
+
+    /// Get estimated memory usage of the analysis data, broken down by the
+    /// kind of cache.
+    pub fn estimated_memory_by_cache(&self) -> HashMap<&'static str, usize> {
+        let modules = self.caches.modules.values();
+
+        let lexical_hierarchy = modules
+            .clone()
+            .map(|v| {
+                v.def_use_lexical_hierarchy
+                    .output
+                    .read()
+                    .as_ref()
+                    .map_or(0, |e| e.iter().map(|e| e.estimated_memory()).sum())
+            })
+            .sum::<usize>();
+        let def_use = modules
+            .clone()
+            .map(|v| {
+                v.def_use
+                    .output
+                    .read()
+                    .as_ref()
+                    .map_or(0, |e| e.estimated_memory())
+            })
+            .sum::<usize>();
+        let type_check = modules
+            .clone()
+            .map(|v| {
+                v.type_check
+                    .output
+                    .read()
+                    .as_ref()
+                    .map_or(0, |e| e.estimated_memory())
+            })
+            .sum::<usize>();
+        let signatures = modules
+            .map(|v| {
+                v.signatures.capacity()
+                    * (std::mem::size_of::<usize>() + std::mem::size_of::<Signature>() + 32)
+            })
+            .sum::<usize>()
+            + self.caches.signatures.capacity()
+                * (std::mem::size_of::<u128>()
+                    + std::mem::size_of::<(u64, foundations::Func, Signature)>()
+                    + 32);
+
+        HashMap::from_iter([
+            ("modules", self.caches.modules.capacity() * 32),
+            ("lexicalHierarchy", lexical_hierarchy),
+            ("defUse", def_use),
+            ("typeCheck", type_check),
+            ("signatures", signatures),
+        ])
     }
 
     fn gc(&mut self) {
@@ -213,6 +276,17 @@ impl<Inputs, Output> ComputingNode<Inputs, Output> {
         }
     }
 
+    /// Checks whether the output is computed from the inputs.
+    fn is_computed(&self, inputs: &Inputs) -> bool
+    where
+        Inputs: Hash,
+    {
+        let computed = self.inputs.read();
+        computed
+            .as_ref()
+            .is_some_and(|s| reflexo::hash::hash128(inputs) == reflexo::hash::hash128(s))
+    }
+
     fn compute(
         &self,
         inputs: Inputs,
@@ -419,11 +493,9 @@ pub trait AnalysisResources {
     /// Resolve the real path for a package spec.
     fn resolve(&self, spec: &PackageSpec) -> Result<Arc<Path>, PackageError>;
 
-    /// Get all the files in the workspace.
-    fn iter_dependencies<'a>(
-        &'a self,
-        f: &mut dyn FnMut(&'a ImmutPath, FileResult<&std::time::SystemTime>),
-    );
+    /// Get all the files in the workspace, whose paths are normalized by
+    /// [`normalize_path`].
+    fn iter_dependencies(&self, f: &mut dyn FnMut(&ImmutPath, FileResult<&std::time::SystemTime>));
 
     /// Resolve extra font information.
     fn font_info(&self, _font: Font) -> Option<Arc<DataSource>> {
@@ -496,6 +568,7 @@ impl<'w> AnalysisContext<'w> {
                 scan_workspace_files(
                     &self.analysis.root,
                     PathPreference::Special.ext_matcher(),
+                    !self.analysis.scan_ignored_files,
                     |relative_path| relative_path.to_owned(),
                 )
             })
@@ -569,7 +642,9 @@ impl<'w> AnalysisContext<'w> {
     /// Get the source of a file by file path.
     pub fn source_by_path(&mut self, p: &Path) -> FileResult<Source> {
         // todo: source in packages
-        let relative_path = p.strip_prefix(&self.analysis.root).map_err(|_| {
+        let p = normalize_path(p);
+        let root = normalize_path(&self.analysis.root);
+        let relative_path = p.strip_prefix(&root).map_err(|_| {
             FileError::Other(Some(eco_format!(
                 "not in root, path is {p:?}, root is {:?}",
                 self.analysis.root
@@ -615,6 +690,59 @@ impl<'w> AnalysisContext<'w> {
         self.caches.modules.entry(file_id).or_default()
     }
 
+    /// Maps the sources by a function in parallel, preserving the order.
+    ///
+    /// The world is only accessed on the current thread, so the sources must be
+    /// read ahead, and the function must only depend on the sources. It falls
+    /// back to a serial map if only one thread is configured.
+    pub fn par_map_sources<T: Send>(
+        &self,
+        sources: &[Source],
+        f: impl Fn(&Source) -> T + Sync,
+    ) -> Vec<T> {
+        use rayon::prelude::*;
+
+        let pool = analysis_pool(self.analysis.analysis_threads);
+        match pool.filter(|_| sources.len() > 1) {
+            Some(pool) => pool.install(|| sources.par_iter().map(f).collect()),
+            None => sources.iter().map(f).collect(),
+        }
+    }
+
+    /// Computes the lexical hierarchies for def-use analysis of the files in
+    /// parallel ahead, which are cached for the later analyses of the files.
+    pub fn prefetch_def_use(&mut self, ids: &[TypstFileId]) {
+        let mut sources = Vec::with_capacity(ids.len());
+        for &id in ids {
+            let Ok(source) = self.source_by_id(id) else {
+                continue;
+            };
+            let cache = self.analysis.caches.modules.get(&id);
+            if !cache.is_some_and(|c| c.def_use_lexical_hierarchy.is_computed(&source)) {
+                sources.push(source);
+            }
+        }
+        if sources.len() <= 1 {
+            return;
+        }
+
+        let hierarchies = self.par_map_sources(&sources, |source| {
+            crate::syntax::get_lexical_hierarchy(
+                source.clone(),
+                crate::syntax::LexicalScopeKind::DefUse,
+            )
+        });
+        for (source, hierarchy) in sources.into_iter().zip(hierarchies) {
+            let cache = self.at_module(source.id());
+            let _ = cache
+                .def_use_lexical_hierarchy
+                .compute(source, |_before, _after| {
+                    cache.signatures.clear();
+                    hierarchy
+                });
+        }
+    }
+
     /// Fork a new context for searching in the workspace.
     pub fn fork_for_search<'s>(&'s mut self) -> SearchCtx<'s, 'w> {
         SearchCtx {
@@ -902,6 +1030,41 @@ impl<'w> AnalysisContext<'w> {
     }
 }
 
+/// Gets the thread pool analyzing the files in parallel, which is None if the
+/// files are analyzed serially.
+///
+/// The pool is shared by all analyses, and is only rebuilt when the number of
+/// threads is changed.
+fn analysis_pool(threads: usize) -> Option<Arc<rayon::ThreadPool>> {
+    static POOL: parking_lot::Mutex<Option<(usize, Arc<rayon::ThreadPool>)>> =
+        parking_lot::const_mutex(None);
+
+    if threads <= 1 {
+        return None;
+    }
+
+    let mut pool = POOL.lock();
+    if let Some((_, pool)) = pool.as_ref().filter(|(n, _)| *n == threads) {
+        return Some(pool.clone());
+    }
+
+    let built = rayon::ThreadPoolBuilder::new()
+        .num_threads(threads)
+        .thread_name(|i| format!("tinymist-analysis-{i}"))
+        .build();
+    match built {
+        Ok(built) => {
+            let built = Arc::new(built);
+            *pool = Some((threads, built.clone()));
+            Some(built)
+        }
+        Err(err) => {
+            log::error!("failed to build the analysis thread pool: {err}");
+            None
+        }
+    }
+}
+
 fn ceil_char_boundary(text: &str, mut cursor: usize) -> usize {
     // while is not char boundary, move cursor to right
     while cursor < text.len() && !text.is_char_boundary(cursor) {
@@ -977,12 +1140,12 @@ impl SearchCtx<'_, '_> {
         }
     }
 
-    /// Push the dependents of a file to the worklist.
+    /// Push the dependents of a file to the worklist. The new files are
+    /// analyzed ahead in parallel.
     pub fn push_dependents(&mut self, id: TypstFileId) {
         let deps = self.ctx.module_dependencies().get(&id);
         let dependents = deps.map(|e| e.dependents.clone()).into_iter().flatten();
-        for dep in dependents {
-            self.push(dep);
-        }
+        let pushed = dependents.filter(|&dep| self.push(dep)).collect::<Vec<_>>();
+        self.ctx.prefetch_def_use(&pushed);
     }
 }
@@ -13,8 +13,8 @@ use super::{prelude::*, BibInfo};
 use crate::{
     prelude::*,
     syntax::{
-        find_source_by_expr, get_deref_target, DerefTarget, IdentRef, LexicalKind, LexicalModKind,
-        LexicalVarKind,
+        find_source_by_expr, get_deref_target, DerefTarget, IdentDef, IdentRef, LexicalKind,
+        LexicalModKind, LexicalVarKind,
     },
 };
 
@@ -39,6 +39,27 @@ pub fn find_definition(
     source: Source,
     document: Option<&VersionedDocument>,
     deref_target: DerefTarget<'_>,
+) -> Option<DefinitionLink> {
+    find_definition_(ctx, source, document, deref_target, false)
+}
+
+/// Finds the definition of a symbol, following aliases introduced by imports
+/// (`import "foo.typ": foo as bar`) to the original definition.
+pub fn find_source_definition(
+    ctx: &mut AnalysisContext<'_>,
+    source: Source,
+    document: Option<&VersionedDocument>,
+    deref_target: DerefTarget<'_>,
+) -> Option<DefinitionLink> {
+    find_definition_(ctx, source, document, deref_target, true)
+}
+
+fn find_definition_(
+    ctx: &mut AnalysisContext<'_>,
+    source: Source,
+    document: Option<&VersionedDocument>,
+    deref_target: DerefTarget<'_>,
+    follow_alias: bool,
 ) -> Option<DefinitionLink> {
     let source_id = source.id();
 
@@ -160,6 +181,12 @@ pub fn find_definition(
         });
     };
 
+    let (def_fid, def) = if follow_alias {
+        resolve_alias(ctx, def_fid, def.clone())
+    } else {
+        (def_fid, def.clone())
+    };
+
     match def.kind {
         LexicalKind::Var(LexicalVarKind::BibKey)
         | LexicalKind::Heading(..)
@@ -209,6 +236,38 @@ pub fn find_definition(
     }
 }
 
+/// The maximum depth of alias chains to follow, which prevents cycles.
+const MAX_ALIAS_DEPTH: usize = 32;
+
+/// Follows alias imports, possibly re-exported by other modules, to the
+/// original definition.
+fn resolve_alias(
+    ctx: &mut AnalysisContext<'_>,
+    fid: TypstFileId,
+    def: IdentDef,
+) -> (TypstFileId, IdentDef) {
+    let mut res = (fid, def);
+    for _ in 0..MAX_ALIAS_DEPTH {
+        let LexicalKind::Mod(LexicalModKind::Alias { target }) = &res.1.kind else {
+            break;
+        };
+
+        let next = (|| {
+            let source = ctx.source_by_id(res.0).ok()?;
+            let def_use = ctx.def_use(source)?;
+            let (fid, def) = def_use.get_def_by_id(def_use.get_ref(target)?)?;
+            Some((fid, def.clone()))
+        })();
+
+        match next {
+            Some(next) => res = next,
+            None => break,
+        }
+    }
+
+    res
+}
+
 fn find_bib_definition(bib_elem: Arc<BibInfo>, key: &str) -> Option<DefinitionLink> {
     let entry = bib_elem.entries.get(key);
     log::debug!("find_bib_definition: {key} => {entry:?}");
@@ -337,7 +396,7 @@ fn resolve_callee_(
         let node = source.find(callee.span())?;
         let cursor = node.offset();
         let deref_target = get_deref_target(node, cursor)?;
-        let def = find_definition(ctx, source.clone(), None, deref_target)?;
+        let def = find_source_definition(ctx, source.clone(), None, deref_target)?;
         match def.kind {
             LexicalKind::Var(LexicalVarKind::Function) => match def.value {
                 Some(Value::Func(f)) => Some(f),
@@ -470,3 +529,43 @@ fn value_to_def(
         }
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    fn find_at_marker(contents: &str, follow_alias: bool) -> Option<(String, String)> {
+        run_with_ctx(contents, |ctx, path| {
+            let source = ctx.source_by_path(&path).unwrap();
+            let deref_target = ctx.deref_syntax_at(&source, find_test_position(&source), 1)?;
+            let lnk = find_definition_(ctx, source.clone(), None, deref_target, follow_alias)?;
+            let (fid, _) = lnk.def_at?;
+            Some((
+                lnk.name,
+                fid.vpath().as_rootless_path().display().to_string(),
+            ))
+        })
+    }
+
+    #[test]
+    fn test_alias_chain() {
+        let contents = r#"// path: base.typ
+#let f() = 1;
+-----
+// path: reexport.typ
+#import "base.typ": f as g
+-----
+#import "reexport.typ": g as h
+#(/* position after */ h);"#;
+
+        let (name, path) = find_at_marker(contents, true).unwrap();
+        assert_eq!(name, "f");
+        assert_eq!(path, "base.typ");
+
+        // The alias itself is still the definition for renaming.
+        let (name, path) = find_at_marker(contents, false).unwrap();
+        assert_eq!(name, "h");
+        assert_eq!(path, "s2.typ");
+    }
+}
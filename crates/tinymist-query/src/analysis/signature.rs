@@ -21,7 +21,7 @@ use crate::syntax::{get_def_target, get_deref_target, DefTarget};
 use crate::ty::SigTy;
 use crate::AnalysisContext;
 
-use super::{find_definition, DefinitionLink, LexicalKind, LexicalVarKind, Ty};
+use super::{find_source_definition, DefinitionLink, LexicalKind, LexicalVarKind, Ty};
 
 // pub fn analyze_signature
 
@@ -298,7 +298,7 @@ fn resolve_callee_v2(
     let node = source.find(callee.span())?;
     let cursor = node.offset();
     let deref_target = get_deref_target(node, cursor)?;
-    let def = find_definition(ctx, source.clone(), None, deref_target)?;
+    let def = find_source_definition(ctx, source.clone(), None, deref_target)?;
     if let LexicalKind::Var(LexicalVarKind::Function) = def.kind {
         if let Some(Value::Func(f)) = def.value {
             return Some(TryResolveCalleeResult::Runtime(f));
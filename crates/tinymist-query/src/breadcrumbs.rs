@@ -0,0 +1,180 @@
+use std::{cmp::Reverse, ops::Range};
+
+use lsp_types::SymbolKind;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    prelude::*,
+    syntax::{
+        get_lexical_hierarchy, LexicalHierarchy, LexicalInfo, LexicalKind, LexicalScopeKind,
+        LexicalVarKind,
+    },
+    SyntaxRequest,
+};
+
+/// A named construct enclosing a position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Breadcrumb {
+    /// The name of the construct, such as the text of a heading.
+    pub name: String,
+    /// The kind of the construct, which is the same as the kind of its
+    /// document symbol.
+    pub kind: SymbolKind,
+    /// The range of the construct, such as a section of a heading or a `let`
+    /// binding of a function.
+    pub range: LspRange,
+    /// The range of the name of the construct.
+    pub selection_range: LspRange,
+}
+
+/// A request to get the chain of named constructs enclosing a position, the
+/// outermost first, to show a breadcrumb trail.
+///
+/// Unlike selection ranges, only the headings, the `let` bindings of functions
+/// and the labeled elements are included, which are the document symbols. A
+/// heading encloses the content until the next heading of the same or a
+/// higher level in the same markup.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct BreadcrumbsRequest {
+    /// The path of the document to get the breadcrumbs.
+    pub path: PathBuf,
+    /// The position in the document.
+    pub position: LspPosition,
+}
+
+impl SyntaxRequest for BreadcrumbsRequest {
+    type Response = Vec<Breadcrumb>;
+
+    fn request(
+        self,
+        source: &Source,
+        position_encoding: PositionEncoding,
+    ) -> Option<Self::Response> {
+        let offset = lsp_to_typst::position(self.position, position_encoding, source)?;
+        let symbols = get_lexical_hierarchy(source.clone(), LexicalScopeKind::Symbol)?;
+        let root = LinkedNode::new(source.root());
+
+        let mut crumbs = vec![];
+        collect_crumbs(&symbols, &root, offset, &mut crumbs);
+        crumbs.sort_by_key(|(extent, _)| (extent.start, Reverse(extent.end)));
+
+        let crumbs = crumbs.into_iter().filter_map(|(extent, info)| {
+            Some(Breadcrumb {
+                name: info.name.clone(),
+                kind: info.kind.clone().try_into().ok()?,
+                range: typst_to_lsp::range(extent, source, position_encoding),
+                selection_range: typst_to_lsp::range(info.range.clone(), source, position_encoding),
+            })
+        });
+        Some(crumbs.collect())
+    }
+}
+
+/// Collects the symbols enclosing the offset along with their extents.
+///
+/// The children of every symbol are visited, since the symbols in a block may
+/// be nested under a heading outside the block.
+fn collect_crumbs<'a>(
+    symbols: &'a [LexicalHierarchy],
+    root: &LinkedNode,
+    offset: usize,
+    crumbs: &mut Vec<(Range<usize>, &'a LexicalInfo)>,
+) {
+    let len = root.len();
+    for symbol in symbols {
+        if let Some(extent) = symbol_extent(&symbol.info, root) {
+            if extent.contains(&offset) || (offset == extent.end && offset == len) {
+                crumbs.push((extent, &symbol.info));
+            }
+        }
+
+        if let Some(children) = &symbol.children {
+            collect_crumbs(children, root, offset, crumbs);
+        }
+    }
+}
+
+/// Gets the range of the construct named by a symbol, or None if the symbol
+/// doesn't name a construct shown in breadcrumbs.
+fn symbol_extent(info: &LexicalInfo, root: &LinkedNode) -> Option<Range<usize>> {
+    let leaf = root.leaf_at(info.range.start + 1)?;
+    match info.kind {
+        LexicalKind::Heading(..) => section_extent(&ancestor(leaf, SyntaxKind::Heading)?),
+        LexicalKind::Var(LexicalVarKind::Function) => {
+            Some(ancestor(leaf, SyntaxKind::LetBinding)?.range())
+        }
+        LexicalKind::Var(LexicalVarKind::Label) => labeled_extent(&leaf),
+        _ => None,
+    }
+}
+
+/// Gets the range of a section, which ends before the next heading of the same
+/// or a higher level in the same markup.
+fn section_extent(heading: &LinkedNode) -> Option<Range<usize>> {
+    let depth = heading.cast::<ast::Heading>()?.depth();
+    let markup = heading.parent()?;
+    let end = markup
+        .children()
+        .skip_while(|child| child.offset() <= heading.offset())
+        .find(|child| {
+            let next = child.cast::<ast::Heading>();
+            next.is_some_and(|next| next.depth() <= depth)
+        })
+        .map_or(markup.range().end, |child| child.offset());
+
+    Some(heading.offset()..end)
+}
+
+/// Gets the range of an element labeled by a label, such as a figure or a
+/// content block. The labels attached to text are skipped.
+fn labeled_extent(label: &LinkedNode) -> Option<Range<usize>> {
+    let labeled = label.prev_sibling()?;
+    if matches!(
+        labeled.kind(),
+        SyntaxKind::Text
+            | SyntaxKind::SmartQuote
+            | SyntaxKind::Escape
+            | SyntaxKind::Shorthand
+            | SyntaxKind::Linebreak
+    ) {
+        return None;
+    }
+
+    Some(labeled.offset()..label.range().end)
+}
+
+/// Finds the closest ancestor of a node, including itself, of a kind.
+fn ancestor(node: LinkedNode, kind: SyntaxKind) -> Option<LinkedNode> {
+    let mut node = Some(node);
+    while let Some(n) = node {
+        if n.kind() == kind {
+            return Some(n);
+        }
+        node = n.parent().cloned();
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    #[test]
+    fn test() {
+        snapshot_testing("breadcrumbs", &|ctx, path| {
+            let source = ctx.source_by_path(&path).unwrap();
+
+            let request = BreadcrumbsRequest {
+                path: path.clone(),
+                position: find_test_position_after(&source),
+            };
+
+            let result = request.request(&source, PositionEncoding::Utf16);
+            assert_snapshot!(JsonRepr::new_redacted(result, &REDACT_LOC));
+        });
+    }
+}
@@ -0,0 +1,65 @@
+use comemo::Track;
+use typst::{
+    foundations::NativeElement,
+    model::{BibliographyElem, RefElem},
+};
+
+use crate::{diagnostics::diagnostic_at_span, prelude::*, DiagnosticsMap};
+
+/// A request to check that all references in the compiled document resolve to
+/// a target.
+///
+/// Unlike the analysis on the source, this checks the final document, so it
+/// catches references broken by conditional content. It requires a
+/// successfully compiled document.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct CheckReferencesRequest {
+    /// The path of the document to check references.
+    pub path: PathBuf,
+}
+
+impl StatefulRequest for CheckReferencesRequest {
+    type Response = DiagnosticsMap;
+
+    fn request(
+        self,
+        ctx: &mut AnalysisContext,
+        doc: Option<VersionedDocument>,
+    ) -> Option<Self::Response> {
+        let doc = doc?;
+        let introspector = &doc.document.introspector;
+
+        let bib_keys = BibliographyElem::keys(introspector.track());
+
+        let mut diagnostics = DiagnosticsMap::new();
+        for elem in introspector.query(&RefElem::elem().select()).iter() {
+            let Some(elem) = elem.to_packed::<RefElem>() else {
+                continue;
+            };
+            let target = elem.target();
+            if introspector.query_label(target).is_ok()
+                || bib_keys.iter().any(|(key, _)| key == target.as_str())
+            {
+                continue;
+            }
+
+            let diagnostic = LspDiagnostic {
+                severity: Some(LspSeverity::WARNING),
+                message: format!(
+                    "reference to `{}` is not resolved in the document",
+                    target.as_str()
+                ),
+                source: Some("typst".to_owned()),
+                ..Default::default()
+            };
+            let Some((uri, diagnostic)) = diagnostic_at_span(ctx, elem.span(), diagnostic) else {
+                continue;
+            };
+            diagnostics.entry(uri).or_default().push(diagnostic);
+        }
+
+        Some(diagnostics)
+    }
+}
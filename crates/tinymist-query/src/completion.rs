@@ -1,14 +1,17 @@
+use std::collections::BTreeMap;
+
 use lsp_types::{
-    Command, CompletionItemLabelDetails, CompletionList, CompletionTextEdit, InsertTextFormat,
-    TextEdit,
+    Command, CompletionItemLabelDetails, CompletionList, CompletionTextEdit, Documentation,
+    InsertTextFormat, TextEdit,
 };
 use once_cell::sync::Lazy;
 use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
 
 use crate::{
     analysis::{BuiltinTy, InsTy, Ty},
     prelude::*,
-    syntax::DerefTarget,
+    syntax::{interpret_mode_at_cursor, DerefTarget, InterpretMode},
     upstream::{autocomplete, complete_path, CompletionContext},
     StatefulRequest,
 };
@@ -17,6 +20,49 @@ pub(crate) type LspCompletion = lsp_types::CompletionItem;
 pub(crate) type LspCompletionKind = lsp_types::CompletionItemKind;
 pub(crate) type TypstCompletionKind = crate::upstream::CompletionKind;
 
+/// The details to compute for the completions of a completion request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum CompletionDocs {
+    /// Computes the details of all completions.
+    #[default]
+    All,
+    /// Leaves out the details of all completions, which are computed by the
+    /// `completionItem/resolve` request from the [`CompletionResolveData`]
+    /// attached to each completion.
+    Lazy,
+    /// Computes the detail and the documentation of the completions with the
+    /// label only, and drops the other completions.
+    Resolve(EcoString),
+}
+
+impl CompletionDocs {
+    /// Whether to compute the detail of the completion with the label.
+    pub(crate) fn wants(&self, label: &str) -> bool {
+        match self {
+            CompletionDocs::All => true,
+            CompletionDocs::Lazy => false,
+            CompletionDocs::Resolve(resolved) => resolved == label,
+        }
+    }
+
+    /// Whether to compute the documentation of the completion with the label.
+    pub(crate) fn resolves(&self, label: &str) -> bool {
+        matches!(self, CompletionDocs::Resolve(resolved) if resolved == label)
+    }
+}
+
+/// The data attached to a completion left out by [`CompletionDocs::Lazy`],
+/// from which the `completionItem/resolve` request completes it again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompletionResolveData {
+    /// The path of the document where the completion is computed.
+    pub path: PathBuf,
+    /// The position in the document where the completion is computed.
+    pub position: LspPosition,
+    /// The label of the completion.
+    pub label: String,
+}
+
 /// The [`textDocument/completion`] request is sent from the client to the
 /// server to compute completion items at a given cursor position.
 ///
@@ -45,6 +91,10 @@ pub struct CompletionRequest {
     pub position: LspPosition,
     /// Whether the completion is triggered explicitly.
     pub explicit: bool,
+    /// The user-defined snippets, mapping labels to snippet bodies.
+    pub snippets: Arc<BTreeMap<String, String>>,
+    /// The details to compute for the completions.
+    pub docs: CompletionDocs,
 }
 
 impl StatefulRequest for CompletionRequest {
@@ -128,11 +178,28 @@ impl StatefulRequest for CompletionRequest {
             None => {}
         }
 
+        // Gate completion sources by the kind of syntax at the cursor.
+        let root = LinkedNode::new(source.root());
+        let leaf = root.leaf_at(cursor);
+        let mut gate_string = false;
+        match interpret_mode_at_cursor(leaf.as_ref(), cursor) {
+            // Nothing to complete in comments.
+            InterpretMode::Comment => completion_result = Some(vec![]),
+            // Paths and values of import sources and arguments are completed
+            // by typst, and strings elsewhere have nothing to complete.
+            InterpretMode::String | InterpretMode::Raw => {
+                gate_string = !leaf.as_ref().is_some_and(is_source_or_arg);
+            }
+            _ => {}
+        }
+
         let mut completion_items_rest = None;
         let is_incomplete = false;
 
         let mut items = completion_result.or_else(|| {
             let mut cc_ctx = CompletionContext::new(ctx, doc, &source, cursor, explicit)?;
+            cc_ctx.user_snippets = self.snippets.clone();
+            cc_ctx.docs = self.docs.clone();
 
             // Exclude it self from auto completion
             // e.g. `#let x = (1.);`
@@ -211,11 +278,26 @@ impl StatefulRequest for CompletionRequest {
                 let lsp_snippet = to_lsp_snippet(typst_snippet);
                 let text_edit = CompletionTextEdit::Edit(TextEdit::new(replace_range, lsp_snippet));
 
+                // Ranks snippets lower so that they don't shadow symbol completions.
+                let sort_text = match (&typst_completion.sort_text, &typst_completion.kind) {
+                    (Some(sort_text), _) => Some(sort_text.to_string()),
+                    (None, TypstCompletionKind::Syntax) => {
+                        Some(format!("~{}", typst_completion.label))
+                    }
+                    (None, _) => None,
+                };
+
                 LspCompletion {
                     label: typst_completion.label.to_string(),
                     kind: Some(completion_kind(typst_completion.kind.clone())),
                     detail: typst_completion.detail.as_ref().map(String::from),
-                    sort_text: typst_completion.sort_text.as_ref().map(String::from),
+                    documentation: typst_completion.docs.as_ref().map(|docs| {
+                        Documentation::MarkupContent(MarkupContent {
+                            kind: MarkupKind::Markdown,
+                            value: docs.to_string(),
+                        })
+                    }),
+                    sort_text,
                     label_details: typst_completion.label_detail.as_ref().map(|e| {
                         CompletionItemLabelDetails {
                             detail: None,
@@ -233,12 +315,35 @@ impl StatefulRequest for CompletionRequest {
             });
 
             Some(completions.collect_vec())
-        })?;
+        });
+        // Only the completions of typst are kept in other strings, and an
+        // empty list is returned if typst completes nothing.
+        if gate_string && items.as_ref().map_or(true, Vec::is_empty) {
+            items = Some(vec![]);
+        }
+        let mut items = items?;
 
         if let Some(items_rest) = completion_items_rest.as_mut() {
             items.append(items_rest);
         }
 
+        match &self.docs {
+            CompletionDocs::All => {}
+            CompletionDocs::Lazy => {
+                for item in items.iter_mut() {
+                    let data = CompletionResolveData {
+                        path: self.path.clone(),
+                        position: self.position,
+                        label: item.label.clone(),
+                    };
+                    item.detail = None;
+                    item.documentation = None;
+                    item.data = serde_json::to_value(data).ok();
+                }
+            }
+            CompletionDocs::Resolve(label) => items.retain(|item| item.label == label.as_str()),
+        }
+
         // To response completions in fine-grained manner, we need to mark result as
         // incomplete. This follows what rust-analyzer does.
         // https://github.com/rust-lang/rust-analyzer/blob/f5a9250147f6569d8d89334dc9cca79c0322729f/crates/rust-analyzer/src/handlers/request.rs#L940C55-L940C75
@@ -297,6 +402,22 @@ fn is_arg_like_context(mut matching: &LinkedNode) -> bool {
     false
 }
 
+/// Whether the string at the cursor is the source of an import or an include,
+/// or an argument of a call.
+fn is_source_or_arg(leaf: &LinkedNode) -> bool {
+    use SyntaxKind::*;
+
+    let mut node = leaf.clone();
+    while let Some(parent) = node.parent() {
+        match parent.kind() {
+            ModuleImport | ModuleInclude | Args => return true,
+            Named => node = parent.clone(),
+            _ => return false,
+        }
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
@@ -367,6 +488,8 @@ mod tests {
                     path: path.clone(),
                     position: ctx.to_lsp_pos(s, &source),
                     explicit: false,
+                    snippets: Default::default(),
+                    docs: CompletionDocs::All,
                 };
                 results.push(request.request(ctx, None).map(|resp| {
                     // CompletionResponse::Array(items)
@@ -388,4 +511,42 @@ mod tests {
             })
         });
     }
+
+    #[test]
+    fn test_lazy_docs() {
+        let complete = |docs: CompletionDocs| {
+            run_with_ctx("#tex", |ctx, path| {
+                let source = ctx.source_by_path(&path).unwrap();
+                let request = CompletionRequest {
+                    path,
+                    position: ctx.to_lsp_pos(source.text().len(), &source),
+                    explicit: false,
+                    snippets: Default::default(),
+                    docs,
+                };
+                match request.request(ctx, None).unwrap() {
+                    CompletionResponse::List(list) => list.items,
+                    CompletionResponse::Array(items) => items,
+                }
+            })
+        };
+
+        let all = complete(CompletionDocs::All);
+        let lazy = complete(CompletionDocs::Lazy);
+        assert!(lazy.iter().all(|item| item.detail.is_none()));
+        assert!(lazy.iter().all(|item| item.documentation.is_none()));
+
+        let text = lazy.iter().find(|item| item.label == "text").unwrap();
+        let data = text.data.clone().unwrap();
+        let data: CompletionResolveData = serde_json::from_value(data).unwrap();
+        assert_eq!(data.label, "text");
+
+        let resolved = complete(CompletionDocs::Resolve(data.label.into()));
+        assert!(resolved.iter().all(|item| item.label == "text"));
+        let resolved = &resolved[0];
+        let text = all.iter().find(|item| item.label == "text").unwrap();
+        assert_eq!(resolved.detail, text.detail);
+        assert!(resolved.detail.is_some());
+        assert!(resolved.documentation.is_some());
+    }
 }
@@ -1,14 +1,115 @@
-use crate::prelude::*;
+use lsp_types::{DiagnosticTag, NumberOrString};
+use reflexo::hash::hash128;
+use serde::{Deserialize, Serialize};
+use typst::model::Document as TypstDocument;
+
+use crate::{
+    normalize_path,
+    prelude::*,
+    syntax::{find_import_cycle, find_redefinitions},
+    validate_elements,
+};
 
 /// Stores diagnostics for files.
 pub type DiagnosticsMap = HashMap<Url, Vec<LspDiagnostic>>;
 
+/// The warnings of typst about code having no effect, which are rendered as
+/// unnecessary code by the clients.
+const UNNECESSARY_WARNINGS: &[&str] = &[
+    "unnecessary import rename to same name",
+    "no text within stars",
+    "no text within underscores",
+];
+
+/// The explanations appended to the diagnostics for newcomers, keyed by the
+/// prefixes of the messages of typst.
+const DIAGNOSTIC_EXPLANATIONS: &[(&str, &str)] = &[
+    (
+        "unexpected closing brace",
+        "A `}` is found without a matching `{` before it. Check whether a `{` is removed by \
+         mistake. A literal `}` in markup must be escaped as `\\}`.",
+    ),
+    (
+        "unexpected closing bracket",
+        "A `]` is found without a matching `[` before it. Check whether a `[` is removed by \
+         mistake. A literal `]` in markup must be escaped as `\\]`.",
+    ),
+    (
+        "unexpected closing paren",
+        "A `)` is found without a matching `(` before it. Check whether a `(` is removed by \
+         mistake.",
+    ),
+    (
+        "unclosed delimiter",
+        "A `{`, `[` or `(` is opened but never closed. Add the missing closing delimiter, \
+         usually at the end of the block, the content or the arguments.",
+    ),
+    (
+        "unknown variable",
+        "The name is not defined at this place. Check its spelling, define it with `let` \
+         before using it, or import it from the module defining it. In markup, a `#` followed \
+         by a name is read as a variable, which is escaped as `\\#` to write it literally.",
+    ),
+    (
+        "expected expression",
+        "A value, such as a number, a string or a call, is missing here. In markup, a `#` must \
+         be followed by an expression, such as `#name` or `#func()`.",
+    ),
+    (
+        "expected semicolon or line break",
+        "Two expressions are written on the same line in code. Separate them with a `;` or put \
+         them on different lines.",
+    ),
+    (
+        "file not found",
+        "The path is resolved relative to the file referring to it, or to the root if it \
+         starts with `/`. Files outside of the root can't be read.",
+    ),
+    (
+        "maximum function call depth exceeded",
+        "A function calls itself, directly or through other functions, too many times. Check \
+         whether the recursion has a condition to stop.",
+    ),
+];
+
+/// How verbose the messages of the diagnostics are.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiagnosticVerbosity {
+    /// Reports the messages of typst as they are.
+    #[default]
+    Terse,
+    /// Appends an explanation of the common causes to some messages, which
+    /// is helpful for newcomers.
+    Explained,
+}
+
+/// How to report the diagnostics in files outside of the root, which are
+/// reached through symlinks under the root.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OutOfRootDiagnostics {
+    /// Reports the diagnostics at the real paths of the files, flagged by
+    /// `outOfRoot` in their data.
+    #[default]
+    Report,
+    /// Drops the diagnostics.
+    Suppress,
+}
+
 /// Converts a list of Typst diagnostics to LSP diagnostics.
+///
+/// The elements of the successfully compiled document, if given, are checked
+/// by [`Analysis::element_validators`], whose issues are reported along with
+/// the converted diagnostics.
+///
+/// [`Analysis::element_validators`]: crate::analysis::Analysis::element_validators
 pub fn convert_diagnostics<'a>(
-    ctx: &AnalysisContext,
+    ctx: &mut AnalysisContext,
     errors: impl IntoIterator<Item = &'a TypstDiagnostic>,
+    doc: Option<&TypstDocument>,
 ) -> DiagnosticsMap {
-    errors
+    let mut diagnostics = errors
         .into_iter()
         .flat_map(|error| {
             convert_diagnostic(ctx, error)
@@ -18,7 +119,273 @@ pub fn convert_diagnostics<'a>(
         })
         .collect::<Vec<_>>()
         .into_iter()
-        .into_group_map()
+        .into_group_map();
+
+    relocate_out_of_root(ctx, &mut diagnostics);
+
+    if ctx.analysis.lint_redefinitions {
+        lint_redefinitions(ctx, &mut diagnostics);
+    }
+
+    if let Some(doc) = doc.filter(|_| !ctx.analysis.element_validators.is_empty()) {
+        for (uri, issues) in validate_elements(ctx, doc) {
+            diagnostics.entry(uri).or_default().extend(issues);
+        }
+    }
+
+    let suppressed = &ctx.analysis.suppressed_diagnostics;
+    if !suppressed.is_empty() {
+        for diagnostics in diagnostics.values_mut() {
+            diagnostics.retain(|d| diagnostic_id(d).map_or(true, |id| !suppressed.contains(id)));
+        }
+    }
+
+    if let Some(max) = ctx.analysis.max_diagnostics_per_file {
+        for diagnostics in diagnostics.values_mut() {
+            truncate_diagnostics(diagnostics, max);
+        }
+    }
+
+    diagnostics
+}
+
+/// Moves the diagnostics in files outside of the root to the real paths of the
+/// files, or drops them, according to [`Analysis::out_of_root_diagnostics`].
+///
+/// A file reached through a symlink is identified by the path of the symlink,
+/// while the editor opens it by its real path, so the diagnostics would be
+/// lost otherwise.
+///
+/// [`Analysis::out_of_root_diagnostics`]: crate::analysis::Analysis::out_of_root_diagnostics
+fn relocate_out_of_root(ctx: &AnalysisContext, diagnostics: &mut DiagnosticsMap) {
+    let Ok(root) = ctx.analysis.root.canonicalize() else {
+        return;
+    };
+
+    let uris = diagnostics.keys().cloned().collect::<Vec<_>>();
+    for uri in uris {
+        let Ok(path) = uri.to_file_path() else {
+            continue;
+        };
+        let Some(real_path) = out_of_root_path(&root, &path) else {
+            continue;
+        };
+
+        let mut moved = diagnostics.remove(&uri).unwrap_or_default();
+        let real_uri = match ctx.analysis.out_of_root_diagnostics {
+            OutOfRootDiagnostics::Report => path_to_url(&real_path).ok(),
+            OutOfRootDiagnostics::Suppress => None,
+        };
+        let Some(real_uri) = real_uri else {
+            continue;
+        };
+
+        for diagnostic in &mut moved {
+            if let Some(JsonValue::Object(data)) = &mut diagnostic.data {
+                data.insert("outOfRoot".to_owned(), JsonValue::Bool(true));
+            }
+        }
+        diagnostics.entry(real_uri).or_default().extend(moved);
+    }
+}
+
+/// Gets the real path of a file if it is outside of the canonicalized root,
+/// which happens if the path goes through a symlink pointing outside.
+fn out_of_root_path(root: &Path, path: &Path) -> Option<PathBuf> {
+    let real_path = path.canonicalize().ok()?;
+    (!real_path.starts_with(root)).then_some(real_path)
+}
+
+/// Checks the imports reachable from the main file for a cycle, to report the
+/// whole cycle along with the errors of a failed compilation, which only point
+/// at the import closing the cycle.
+///
+/// Only the imports in the files loaded by the compilation are followed, so
+/// the imports which are never evaluated are not reported. An error naming the
+/// files in the cycle is reported at every import in the cycle, so it can be
+/// broken at any of them.
+pub fn import_cycle_diagnostics(
+    world: &dyn World,
+    main: TypstFileId,
+    loaded: impl Fn(TypstFileId) -> bool,
+) -> EcoVec<TypstDiagnostic> {
+    let source = |id| loaded(id).then(|| world.source(id).ok()).flatten();
+    let Some(cycle) = find_import_cycle(main, source) else {
+        return EcoVec::new();
+    };
+
+    let name = |id: TypstFileId| {
+        let path = id.vpath().as_rootless_path().display();
+        match id.package() {
+            Some(spec) => format!("{spec}/{path}"),
+            None => path.to_string(),
+        }
+    };
+    let files = cycle.iter().map(|edge| name(edge.file));
+    let path = files.chain(iter::once(name(cycle[0].file))).join(" -> ");
+
+    cycle
+        .iter()
+        .map(|edge| {
+            TypstDiagnostic::error(edge.span, format!("cyclic import: {path}"))
+                .with_hint("remove one of the imports in the cycle to break it")
+        })
+        .collect()
+}
+
+/// Reports the redefined bindings in the source files under the root as
+/// warnings, with the previous definitions as related information. A previous
+/// definition never used before the redefinition is also reported as a hint
+/// tagged as unnecessary, which is faded by the clients.
+fn lint_redefinitions(ctx: &AnalysisContext, diagnostics: &mut DiagnosticsMap) {
+    let root = normalize_path(&ctx.analysis.root);
+    let mut ids = vec![];
+    ctx.resources.iter_dependencies(&mut |path, _| {
+        if path.extension().is_some_and(|ext| ext == "typ") {
+            let vpath = VirtualPath::within_root(path, &root);
+            ids.extend(vpath.map(|vpath| TypstFileId::new(None, vpath)));
+        }
+    });
+
+    for id in ids {
+        let (Ok(source), Ok(uri)) = (ctx.world().source(id), ctx.uri_for_id(id)) else {
+            continue;
+        };
+
+        for redefinition in find_redefinitions(&source) {
+            let range = source.range(redefinition.span);
+            let prev = source.range(redefinition.prev);
+            let (Some(range), Some(prev)) = (range, prev) else {
+                continue;
+            };
+
+            let prev_location = LspLocation {
+                uri: uri.clone(),
+                range: ctx.to_lsp_range(prev.clone(), &source),
+            };
+            let mut diagnostic = LspDiagnostic {
+                range: ctx.to_lsp_range(range.clone(), &source),
+                severity: Some(LspSeverity::WARNING),
+                message: format!("`{}` is already defined in this scope", redefinition.name),
+                source: Some("typst".to_owned()),
+                related_information: Some(vec![DiagnosticRelatedInformation {
+                    location: prev_location.clone(),
+                    message: "previously defined here".to_owned(),
+                }]),
+                ..Default::default()
+            };
+            diagnostic.data = Some(stable_data(Some(id), &source.text()[range], &diagnostic));
+            diagnostics.entry(uri.clone()).or_default().push(diagnostic);
+
+            if !redefinition.prev_used {
+                let mut unused = LspDiagnostic {
+                    range: prev_location.range,
+                    severity: Some(LspSeverity::HINT),
+                    message: format!(
+                        "`{}` is never used before it is redefined",
+                        redefinition.name
+                    ),
+                    source: Some("typst".to_owned()),
+                    tags: Some(vec![DiagnosticTag::UNNECESSARY]),
+                    ..Default::default()
+                };
+                unused.data = Some(stable_data(Some(id), &source.text()[prev], &unused));
+                diagnostics.entry(uri.clone()).or_default().push(unused);
+            }
+        }
+    }
+}
+
+/// Places a diagnostic at the source of a span, along with its stable identity,
+/// returning the diagnostic and the URI of the source. Returns None if the span
+/// is detached or its source is not available.
+pub(crate) fn diagnostic_at_span(
+    ctx: &mut AnalysisContext,
+    span: TypstSpan,
+    mut diagnostic: LspDiagnostic,
+) -> Option<(Url, LspDiagnostic)> {
+    let id = span.id()?;
+    let source = ctx.source_by_id(id).ok()?;
+    let range = source.range(span)?;
+    diagnostic.range = ctx.to_lsp_range(range.clone(), &source);
+    diagnostic.data = Some(stable_data(Some(id), &source.text()[range], &diagnostic));
+    Some((ctx.uri_for_id(id).ok()?, diagnostic))
+}
+
+/// Truncates the diagnostics of a file to at most `max` ones, keeping errors
+/// over warnings, and appends a diagnostic telling how many are suppressed.
+fn truncate_diagnostics(diagnostics: &mut Vec<LspDiagnostic>, max: usize) {
+    if diagnostics.len() <= max {
+        return;
+    }
+
+    let is_error = |d: &LspDiagnostic| d.severity == Some(LspSeverity::ERROR);
+    let errors = diagnostics.iter().positions(is_error);
+    let others = diagnostics.iter().positions(|d| !is_error(d));
+
+    let mut kept = vec![false; diagnostics.len()];
+    for idx in errors.chain(others).take(max) {
+        kept[idx] = true;
+    }
+
+    let suppressed = diagnostics.len() - max;
+    let mut kept = kept.into_iter();
+    diagnostics.retain(|_| kept.next().unwrap_or_default());
+    diagnostics.push(LspDiagnostic {
+        range: LspRange::default(),
+        severity: Some(LspSeverity::INFORMATION),
+        message: format!("{suppressed} more diagnostics suppressed"),
+        source: Some("typst".to_owned()),
+        ..Default::default()
+    });
+}
+
+/// Gets the stable identity of a diagnostic, which is the same across
+/// compilations as long as the diagnosed code and the message are unchanged.
+///
+/// The identity is carried in the `data` field of the diagnostic, so clients
+/// can suppress a diagnostic by [`Analysis::suppressed_diagnostics`].
+///
+/// [`Analysis::suppressed_diagnostics`]: crate::analysis::Analysis::suppressed_diagnostics
+pub fn diagnostic_id(diagnostic: &LspDiagnostic) -> Option<&str> {
+    diagnostic.data.as_ref()?.get("id")?.as_str()
+}
+
+/// Gets the category of a diagnostic, which groups the diagnostics of the same
+/// kind across files, e.g. all the unknown variables.
+///
+/// It is the code of the diagnostic if there is one, otherwise it is the first
+/// line of the message with the specific parts removed, i.e. the names quoted
+/// in backticks and the details after a colon.
+pub fn diagnostic_category(diagnostic: &LspDiagnostic) -> String {
+    if let Some(code) = &diagnostic.code {
+        return match code {
+            NumberOrString::Number(code) => code.to_string(),
+            NumberOrString::String(code) => code.clone(),
+        };
+    }
+
+    let message = diagnostic.message.lines().next().unwrap_or_default();
+    let message = message.split(':').next().unwrap_or_default();
+    let mut category = String::new();
+    for (idx, part) in message.split('`').enumerate() {
+        // The odd parts are quoted in backticks.
+        category.push_str(if idx % 2 == 1 { "`_`" } else { part });
+    }
+    category.trim().to_owned()
+}
+
+/// Computes the data of a diagnostic carrying its stable identity, which is
+/// hashed from the file, the diagnosed text with normalized whitespace, the code
+/// and the message, but not the position, so that it survives edits elsewhere.
+fn stable_data(file: Option<TypstFileId>, text: &str, diagnostic: &LspDiagnostic) -> JsonValue {
+    let file = file.map(|id| {
+        let package = id.package().map(ToString::to_string);
+        (package, id.vpath().as_rootless_path().to_owned())
+    });
+    let text = text.split_whitespace().join(" ");
+    let hash = hash128(&(file, text, &diagnostic.code, &diagnostic.message));
+    serde_json::json!({ "id": format!("{hash:032x}") })
 }
 
 fn convert_diagnostic(
@@ -27,10 +394,16 @@ fn convert_diagnostic(
 ) -> anyhow::Result<(Url, LspDiagnostic)> {
     let uri;
     let lsp_range;
+    let mut file = None;
+    let mut text = String::new();
     if let Some((id, span)) = diagnostic_span_id(typst_diagnostic) {
         uri = ctx.uri_for_id(id)?;
         let source = ctx.world().source(id)?;
         lsp_range = diagnostic_range(&source, span, ctx.position_encoding());
+        file = Some(id);
+        if let Some(node) = source.find(span) {
+            text = source.text()[node.range()].to_owned();
+        }
     } else {
         uri = path_to_url(&ctx.analysis.root)?;
         lsp_range = LspRange::default();
@@ -45,14 +418,24 @@ fn convert_diagnostic(
     let tracepoints =
         diagnostic_related_information(ctx, typst_diagnostic, ctx.position_encoding())?;
 
-    let diagnostic = LspDiagnostic {
+    let mut diagnostic = LspDiagnostic {
         range: lsp_range,
         severity: Some(lsp_severity),
         message: lsp_message,
         source: Some("typst".to_owned()),
         related_information: Some(tracepoints),
+        tags: diagnostic_tags(typst_message),
         ..Default::default()
     };
+    diagnostic.data = Some(stable_data(file, &text, &diagnostic));
+
+    // The explanation is appended after computing the stable identity, which
+    // is kept regardless of the verbosity.
+    if ctx.analysis.diagnostic_verbosity == DiagnosticVerbosity::Explained {
+        if let Some(explanation) = diagnostic_explanation(typst_message) {
+            diagnostic.message = explained_message(&diagnostic.message, explanation);
+        }
+    }
 
     Ok((uri, diagnostic))
 }
@@ -133,9 +516,146 @@ fn diagnostic_severity(typst_severity: TypstSeverity) -> LspSeverity {
     }
 }
 
+/// Tags the diagnostics about unnecessary or deprecated code, so that the
+/// clients can render the code faded or struck through.
+fn diagnostic_tags(typst_message: &str) -> Option<Vec<DiagnosticTag>> {
+    if UNNECESSARY_WARNINGS.contains(&typst_message) {
+        Some(vec![DiagnosticTag::UNNECESSARY])
+    } else if typst_message.contains("deprecated") {
+        Some(vec![DiagnosticTag::DEPRECATED])
+    } else {
+        None
+    }
+}
+
+/// Gets the explanation of a message of typst, if there is one.
+fn diagnostic_explanation(typst_message: &str) -> Option<&'static str> {
+    DIAGNOSTIC_EXPLANATIONS
+        .iter()
+        .find(|(pattern, _)| typst_message.starts_with(pattern))
+        .map(|(_, explanation)| *explanation)
+}
+
+/// Appends an explanation to a message, delimited from the words of the
+/// compiler.
+fn explained_message(message: &str, explanation: &str) -> String {
+    format!("{message}\n\n[tinymist explains] {explanation}")
+}
+
 fn diagnostic_hints(typst_hints: &[EcoString]) -> Format<impl Iterator<Item = EcoString> + '_> {
     iter::repeat(EcoString::from("\n\nHint: "))
         .take(typst_hints.len())
         .interleave(typst_hints.iter().cloned())
         .format("")
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_out_of_root_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("root");
+        let outside = dir.path().join("outside");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(&outside).unwrap();
+        std::fs::write(root.join("main.typ"), "#import \"lib/lib.typ\"").unwrap();
+        std::fs::write(outside.join("lib.typ"), "#let x = 1").unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("lib")).unwrap();
+
+        let root = root.canonicalize().unwrap();
+        let inside = out_of_root_path(&root, &root.join("main.typ"));
+        let linked = out_of_root_path(&root, &root.join("lib/lib.typ"));
+        let real = outside.join("lib.typ").canonicalize().unwrap();
+
+        assert_eq!(inside, None);
+        assert_eq!(linked, Some(real));
+    }
+
+    #[test]
+    fn test_diagnostic_explanation() {
+        let explanation = diagnostic_explanation("unknown variable: foo").unwrap();
+        assert!(explanation.starts_with("The name is not defined"));
+        assert!(diagnostic_explanation("unexpected closing brace").is_some());
+        assert!(diagnostic_explanation("expected content, found integer").is_none());
+
+        let message = explained_message("unknown variable: foo\n\nHint: hint", "Why.");
+        assert_eq!(
+            message,
+            "unknown variable: foo\n\nHint: hint\n\n[tinymist explains] Why."
+        );
+    }
+
+    fn diag(message: &str, severity: LspSeverity) -> LspDiagnostic {
+        LspDiagnostic {
+            message: message.to_owned(),
+            severity: Some(severity),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_truncate_diagnostics() {
+        let mut diagnostics = vec![
+            diag("w1", LspSeverity::WARNING),
+            diag("e1", LspSeverity::ERROR),
+            diag("w2", LspSeverity::WARNING),
+            diag("e2", LspSeverity::ERROR),
+            diag("e3", LspSeverity::ERROR),
+        ];
+        truncate_diagnostics(&mut diagnostics, 3);
+
+        let messages = diagnostics.iter().map(|d| d.message.as_str());
+        assert_eq!(
+            messages.collect::<Vec<_>>(),
+            ["e1", "e2", "e3", "2 more diagnostics suppressed"]
+        );
+
+        let mut diagnostics = vec![diag("w1", LspSeverity::WARNING)];
+        truncate_diagnostics(&mut diagnostics, 3);
+        assert_eq!(diagnostics.len(), 1);
+    }
+
+    #[test]
+    fn test_stable_diagnostic_id() {
+        let id = |text: &str, message: &str| {
+            let mut diagnostic = diag(message, LspSeverity::WARNING);
+            diagnostic.data = Some(stable_data(None, text, &diagnostic));
+            diagnostic_id(&diagnostic).unwrap().to_owned()
+        };
+
+        assert_eq!(id("#let x = 1", "w"), id("#let  x =\n 1", "w"));
+        assert_ne!(id("#let x = 1", "w"), id("#let x = 2", "w"));
+        assert_ne!(id("#let x = 1", "w"), id("#let x = 1", "e"));
+    }
+
+    #[test]
+    fn test_diagnostic_tags() {
+        assert_eq!(
+            diagnostic_tags("no text within stars"),
+            Some(vec![DiagnosticTag::UNNECESSARY])
+        );
+        assert_eq!(
+            diagnostic_tags("`state.display` is deprecated"),
+            Some(vec![DiagnosticTag::DEPRECATED])
+        );
+        assert_eq!(diagnostic_tags("unknown variable: foo"), None);
+    }
+
+    #[test]
+    fn test_diagnostic_category() {
+        let category = |message: &str| diagnostic_category(&diag(message, LspSeverity::ERROR));
+
+        assert_eq!(category("unknown variable: foo"), "unknown variable");
+        assert_eq!(
+            category("`x` is already defined in this scope"),
+            "`_` is already defined in this scope"
+        );
+        assert_eq!(
+            category("expected expression\n\nHint: try this"),
+            "expected expression"
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use reflexo::debug_loc::DataSource;
+use serde::{Deserialize, Serialize};
+use typst::text::{FontStretch, FontStyle, FontWeight};
+
+use crate::{document_metrics::font_uses, AnalysisContext, StatefulRequest, VersionedDocument};
+
+/// A font used by the compiled document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentFont {
+    /// The family of the font used by Typst.
+    pub family: String,
+    /// The style of the font.
+    pub style: FontStyle,
+    /// The weight of the font.
+    pub weight: FontWeight,
+    /// The stretch of the font.
+    pub stretch: FontStretch,
+    /// The PostScript name of the font.
+    pub postscript_name: Option<String>,
+    /// The index of the font in the source.
+    pub index: u32,
+    /// The number of glyphs rendered with the font.
+    pub glyphs: u32,
+    /// The source of the font, such as the file it is loaded from. It is None
+    /// if the source is unknown.
+    pub source: Option<DataSource>,
+}
+
+/// A request to list the distinct fonts used by the compiled document, unlike
+/// the fonts available to the compiler. It requires a successfully compiled
+/// document.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct DocumentFontsRequest {
+    /// The path of the document to list fonts.
+    pub path: PathBuf,
+}
+
+impl StatefulRequest for DocumentFontsRequest {
+    type Response = Vec<DocumentFont>;
+
+    fn request(
+        self,
+        ctx: &mut AnalysisContext,
+        doc: Option<VersionedDocument>,
+    ) -> Option<Self::Response> {
+        use ttf_parser::name_id::POST_SCRIPT_NAME;
+
+        let doc = doc?;
+        let mut fonts = font_uses(&doc.document)?
+            .into_iter()
+            .map(|(font, glyphs)| {
+                let info = font.info();
+                DocumentFont {
+                    family: info.family.clone(),
+                    style: info.variant.style,
+                    weight: info.variant.weight,
+                    stretch: info.variant.stretch,
+                    postscript_name: font.find_name(POST_SCRIPT_NAME),
+                    index: font.index(),
+                    glyphs,
+                    source: ctx.resources.font_info(font.clone()).map(|s| (*s).clone()),
+                }
+            })
+            .collect::<Vec<_>>();
+        fonts.sort_by(|a, b| {
+            let key = |f: &DocumentFont| (f.family.clone(), f.weight, f.style, f.stretch);
+            key(a).cmp(&key(b))
+        });
+
+        Some(fonts)
+    }
+}
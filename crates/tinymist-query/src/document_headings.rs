@@ -0,0 +1,87 @@
+use std::num::NonZeroUsize;
+
+use serde::{Deserialize, Serialize};
+use typst::{
+    foundations::{NativeElement, Selector},
+    model::HeadingElem,
+    syntax::Span,
+};
+
+use crate::prelude::*;
+
+/// Information about a heading in the document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentHeading {
+    /// The level of the heading, starting from 1.
+    pub level: NonZeroUsize,
+    /// The text of the heading in the source.
+    pub text: String,
+    /// The range of the heading in the source.
+    pub range: LspRange,
+    /// The page the heading is rendered on, starting from 1.
+    /// It is None if there is no successfully compiled document or the heading
+    /// is not rendered.
+    pub page: Option<NonZeroUsize>,
+}
+
+/// A request to list the headings of a document, with their levels and pages.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct DocumentHeadingsRequest {
+    /// The path of the document to list headings.
+    pub path: PathBuf,
+}
+
+impl StatefulRequest for DocumentHeadingsRequest {
+    type Response = Vec<DocumentHeading>;
+
+    fn request(
+        self,
+        ctx: &mut AnalysisContext,
+        doc: Option<VersionedDocument>,
+    ) -> Option<Self::Response> {
+        let source = ctx.source_by_path(&self.path).ok()?;
+
+        // Correlates the rendered headings with their source by span.
+        let pages: HashMap<Span, NonZeroUsize> = doc
+            .map(|doc| {
+                let introspector = &doc.document.introspector;
+                let selector = Selector::Elem(HeadingElem::elem(), None);
+                introspector
+                    .query(&selector)
+                    .iter()
+                    .filter_map(|elem| Some((elem.span(), introspector.page(elem.location()?))))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut headings = vec![];
+        let root = LinkedNode::new(source.root());
+        collect_headings(ctx, &source, &pages, root, &mut headings);
+        Some(headings)
+    }
+}
+
+fn collect_headings(
+    ctx: &AnalysisContext,
+    source: &Source,
+    pages: &HashMap<Span, NonZeroUsize>,
+    node: LinkedNode,
+    headings: &mut Vec<DocumentHeading>,
+) {
+    if let Some(heading) = node.cast::<ast::Heading>() {
+        let text = heading.body().to_untyped().clone().into_text();
+        headings.push(DocumentHeading {
+            level: heading.depth(),
+            text: text.trim().to_owned(),
+            range: ctx.to_lsp_range(node.range(), source),
+            page: pages.get(&node.span()).copied(),
+        });
+    }
+
+    for child in node.children() {
+        collect_headings(ctx, source, pages, child, headings);
+    }
+}
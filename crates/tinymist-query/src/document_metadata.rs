@@ -0,0 +1,100 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use typst::foundations::Smart;
+
+use crate::{AnalysisContext, StatefulRequest, VersionedDocument};
+
+/// The metadata of the compiled document, as written into exported PDFs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentMetadata {
+    /// The title of the document.
+    pub title: Option<String>,
+    /// The authors of the document, which are joined by commas in PDFs.
+    pub author: Vec<String>,
+    /// The keywords of the document, which are joined by commas in PDFs.
+    pub keywords: Vec<String>,
+    /// The creation date of the document, formatted as `2024-01-31` or
+    /// `2024-01-31 12:00:00`. It is None if the date is `none`, `auto` or has
+    /// no year, in which case no date is written into PDFs.
+    pub date: Option<String>,
+}
+
+/// A request to get the metadata set by `set document(..)` in the compiled
+/// document, which is resolved by evaluating the document instead of parsing
+/// the source. It requires a successfully compiled document.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct DocumentMetadataRequest {
+    /// The path of the document to get the metadata.
+    pub path: PathBuf,
+}
+
+impl StatefulRequest for DocumentMetadataRequest {
+    type Response = DocumentMetadata;
+
+    fn request(
+        self,
+        _ctx: &mut AnalysisContext,
+        doc: Option<VersionedDocument>,
+    ) -> Option<Self::Response> {
+        let doc = doc?;
+        let doc = &doc.document;
+
+        // The PDF exporter is not given a timestamp, so an `auto` date is omitted.
+        let date = match doc.date {
+            Smart::Custom(Some(date)) if date.year().is_some() => {
+                date.display(Smart::Auto).ok().map(Into::into)
+            }
+            _ => None,
+        };
+
+        Some(DocumentMetadata {
+            title: doc.title.as_ref().map(ToString::to_string),
+            author: doc.author.iter().map(ToString::to_string).collect(),
+            keywords: doc.keywords.iter().map(ToString::to_string).collect(),
+            date,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use typst::{foundations::Datetime, model::Document};
+
+    use super::*;
+    use crate::tests::*;
+
+    #[test]
+    fn test_document_metadata() {
+        let metadata = |date| {
+            let doc = Document {
+                title: Some("Report".into()),
+                author: vec!["Alice".into(), "Bob".into()],
+                date,
+                ..Document::default()
+            };
+            let doc = VersionedDocument {
+                version: 0,
+                document: Arc::new(doc),
+            };
+            run_with_ctx("", |ctx, path| {
+                DocumentMetadataRequest { path }.request(ctx, Some(doc))
+            })
+        };
+
+        let date = Datetime::from_ymd(2024, 1, 31);
+        let res = metadata(Smart::Custom(date)).unwrap();
+        assert_eq!(res.title.as_deref(), Some("Report"));
+        assert_eq!(res.author, ["Alice", "Bob"]);
+        assert!(res.keywords.is_empty());
+        assert_eq!(res.date.as_deref(), Some("2024-01-31"));
+
+        assert_eq!(metadata(Smart::Auto).unwrap().date, None);
+        assert_eq!(metadata(Smart::Custom(None)).unwrap().date, None);
+    }
+}
@@ -100,11 +100,9 @@ impl StatefulRequest for DocumentMetricsRequest {
             ctx,
             span_info: Default::default(),
             span_info2: Default::default(),
-            font_info: Default::default(),
+            font_info: font_uses(&doc)?,
         };
 
-        worker.work(&doc)?;
-
         let font_info = worker.compute()?;
         let span_info = SpanInfo {
             sources: worker.span_info2,
@@ -123,38 +121,37 @@ struct DocumentMetricsWorker<'a, 'w> {
     font_info: HashMap<Font, u32>,
 }
 
-impl<'a, 'w> DocumentMetricsWorker<'a, 'w> {
-    fn work(&mut self, doc: &Document) -> Option<()> {
-        for page in &doc.pages {
-            self.work_frame(&page.frame)?;
-        }
-
-        Some(())
+/// Counts the glyphs of each font used by the document. It returns None if the
+/// count overflows.
+pub(crate) fn font_uses(doc: &Document) -> Option<HashMap<Font, u32>> {
+    let mut uses = HashMap::new();
+    for page in &doc.pages {
+        frame_font_uses(&page.frame, &mut uses)?;
     }
 
-    fn work_frame(&mut self, frame: &Frame) -> Option<()> {
-        for (_, elem) in frame.items() {
-            self.work_elem(elem)?;
-        }
-
-        Some(())
-    }
+    Some(uses)
+}
 
-    fn work_elem(&mut self, elem: &FrameItem) -> Option<()> {
+fn frame_font_uses(frame: &Frame, uses: &mut HashMap<Font, u32>) -> Option<()> {
+    for (_, elem) in frame.items() {
         match elem {
-            FrameItem::Text(text) => self.work_text(text),
-            FrameItem::Group(frame) => self.work_frame(&frame.frame),
-            FrameItem::Shape(..) | FrameItem::Image(..) | FrameItem::Meta(..) => Some(()),
+            FrameItem::Text(text) => text_font_uses(text, uses)?,
+            FrameItem::Group(frame) => frame_font_uses(&frame.frame, uses)?,
+            FrameItem::Shape(..) | FrameItem::Image(..) | FrameItem::Meta(..) => {}
         }
     }
 
-    fn work_text(&mut self, text: &TextItem) -> Option<()> {
-        let use_cnt = self.font_info.entry(text.font.clone()).or_default();
-        *use_cnt = use_cnt.checked_add(text.glyphs.len() as u32)?;
+    Some(())
+}
 
-        Some(())
-    }
+fn text_font_uses(text: &TextItem, uses: &mut HashMap<Font, u32>) -> Option<()> {
+    let use_cnt = uses.entry(text.font.clone()).or_default();
+    *use_cnt = use_cnt.checked_add(text.glyphs.len() as u32)?;
 
+    Some(())
+}
+
+impl<'a, 'w> DocumentMetricsWorker<'a, 'w> {
     fn internal_source(&mut self, source: Arc<DataSource>) -> u32 {
         if let Some(&id) = self.span_info.get(source.as_ref()) {
             return id;
@@ -0,0 +1,145 @@
+use std::num::NonZeroUsize;
+
+use serde::{Deserialize, Serialize};
+use typst::{
+    foundations::{NativeElement, Packed, StyleChain},
+    layout::Abs,
+    model::{Document as TypstDocument, HeadingElem},
+};
+
+use crate::prelude::*;
+
+/// An item of the outline embedded in the exported PDF, which is known as a
+/// bookmark.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentOutlineItem {
+    /// The title of the bookmark, which is the plain text of the heading.
+    pub title: String,
+    /// The level of the heading, starting from 1. It may differ from the depth
+    /// of the item in the outline, since the outline skips the headings which
+    /// are not bookmarked.
+    pub level: NonZeroUsize,
+    /// The page the bookmark jumps to, starting from 1.
+    pub page: NonZeroUsize,
+    /// The position the bookmark jumps to in the page in pt, in the form of
+    /// `[x, y]`.
+    pub position: [f64; 2],
+    /// The range of the heading in the source. It is None if the heading is
+    /// not in a source file of the workspace.
+    pub range: Option<LspRange>,
+    /// The nested bookmarks.
+    pub children: Vec<DocumentOutlineItem>,
+}
+
+/// A request to get the outline of the compiled document as it will be
+/// embedded in the exported PDF.
+///
+/// The headings are nested in the same way as the PDF exporter does, which
+/// honors the `bookmarked` and `outlined` settings of the headings. It requires
+/// a successfully compiled document.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct DocumentOutlineRequest {
+    /// The path of the document to get the outline.
+    pub path: PathBuf,
+}
+
+impl StatefulRequest for DocumentOutlineRequest {
+    type Response = Vec<DocumentOutlineItem>;
+
+    fn request(
+        self,
+        ctx: &mut AnalysisContext,
+        doc: Option<VersionedDocument>,
+    ) -> Option<Self::Response> {
+        let doc = doc?;
+        let introspector = &doc.document.introspector;
+
+        // Mirrors the construction of the outline in `typst-pdf`.
+        let mut tree: Vec<HeadingNode> = vec![];
+        let mut last_skipped_level = None;
+        let elements = introspector.query(&HeadingElem::elem().select());
+        for elem in elements.iter() {
+            let Some(heading) = elem.to_packed::<HeadingElem>() else {
+                continue;
+            };
+            let leaf = HeadingNode::leaf(heading);
+
+            if leaf.bookmarked {
+                // Descends through the latest bookmarked heading of each level,
+                // but never deeper than the topmost skipped ancestor.
+                let mut children = &mut tree;
+                while children.last().is_some_and(|last| {
+                    last_skipped_level.map_or(true, |l| last.level < l) && last.level < leaf.level
+                }) {
+                    children = &mut children.last_mut().unwrap().children;
+                }
+
+                last_skipped_level = None;
+                children.push(leaf);
+            } else if last_skipped_level.map_or(true, |l| leaf.level < l) {
+                last_skipped_level = Some(leaf.level);
+            }
+        }
+
+        Some(
+            tree.into_iter()
+                .filter_map(|node| node.into_item(ctx, &doc.document))
+                .collect(),
+        )
+    }
+}
+
+/// A heading in the outline.
+struct HeadingNode<'a> {
+    element: &'a Packed<HeadingElem>,
+    level: NonZeroUsize,
+    bookmarked: bool,
+    children: Vec<HeadingNode<'a>>,
+}
+
+impl<'a> HeadingNode<'a> {
+    fn leaf(element: &'a Packed<HeadingElem>) -> Self {
+        HeadingNode {
+            level: element.resolve_level(StyleChain::default()),
+            // 'bookmarked' set to 'auto' falls back to the value of 'outlined'.
+            bookmarked: element
+                .bookmarked(StyleChain::default())
+                .unwrap_or_else(|| element.outlined(StyleChain::default())),
+            element,
+            children: Vec::new(),
+        }
+    }
+
+    fn into_item(
+        self,
+        ctx: &mut AnalysisContext,
+        doc: &TypstDocument,
+    ) -> Option<DocumentOutlineItem> {
+        let pos = doc.introspector.position(self.element.location()?);
+        // The exporter jumps to a bit above the heading.
+        let y = (pos.point.y - Abs::pt(10.0)).max(Abs::zero());
+
+        let span = self.element.span();
+        let range = span.id().and_then(|id| {
+            let source = ctx.source_by_id(id).ok()?;
+            let range = source.range(span)?;
+            Some(ctx.to_lsp_range(range, &source))
+        });
+
+        Some(DocumentOutlineItem {
+            title: self.element.body().plain_text().trim().to_owned(),
+            level: self.level,
+            page: pos.page,
+            position: [pos.point.x.to_pt(), y.to_pt()],
+            range,
+            children: self
+                .children
+                .into_iter()
+                .filter_map(|node| node.into_item(ctx, doc))
+                .collect(),
+        })
+    }
+}
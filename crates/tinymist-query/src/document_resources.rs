@@ -0,0 +1,194 @@
+use std::{
+    collections::{HashSet, VecDeque},
+    ops::Range,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{prelude::*, SemanticRequest};
+
+/// The functions reading a file by the path in their first argument.
+const RESOURCE_FUNCS: &[&str] = &[
+    "image",
+    "read",
+    "json",
+    "yaml",
+    "toml",
+    "csv",
+    "xml",
+    "cbor",
+    "bibliography",
+    "plugin",
+];
+
+/// A reference to a resource file, such as `image("logo.png")`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResourceReference {
+    /// The function reading the resource, such as `image`.
+    pub func: String,
+    /// The path written in the source, which is None if the path is computed,
+    /// such as `image(name + ".png")`.
+    pub path: Option<String>,
+    /// The absolute path of the resource resolved by the world.
+    pub resolved: Option<PathBuf>,
+    /// Whether the resource can be read by the world, which is None if the
+    /// path is computed.
+    pub exists: Option<bool>,
+    /// The file referencing the resource.
+    pub uri: Url,
+    /// The range of the call reading the resource.
+    pub range: LspRange,
+}
+
+/// A request to list the resources read by a document, such as images and
+/// data files, along with their resolved paths and whether they exist.
+///
+/// The calls reading files with literal paths are collected from the source
+/// files imported or included by the document, transitively. The paths are
+/// resolved relative to the files calling them, or the root for absolute
+/// paths, as typst does. A path computed at runtime is listed without a
+/// resolved path, since it is only known by evaluating the document.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct DocumentResourcesRequest {
+    /// The path of the document to list resources.
+    pub path: PathBuf,
+}
+
+impl SemanticRequest for DocumentResourcesRequest {
+    type Response = Vec<ResourceReference>;
+
+    fn request(self, ctx: &mut AnalysisContext) -> Option<Self::Response> {
+        let main = ctx.source_by_path(&self.path).ok()?.id();
+
+        // Visits the files depended by the document in breadth first order.
+        let mut files = vec![];
+        let mut visited = HashSet::from([main]);
+        let mut queue = VecDeque::from([main]);
+        while let Some(id) = queue.pop_front() {
+            files.push(id);
+            let deps = ctx.module_dependencies().get(&id);
+            for dep in deps
+                .map(|dep| dep.dependencies.to_vec())
+                .unwrap_or_default()
+            {
+                if visited.insert(dep) {
+                    queue.push_back(dep);
+                }
+            }
+        }
+
+        let mut resources = vec![];
+        for id in files {
+            let Ok(source) = ctx.source_by_id(id) else {
+                continue;
+            };
+            let Ok(uri) = ctx.uri_for_id(id) else {
+                continue;
+            };
+
+            let mut calls = vec![];
+            collect_calls(LinkedNode::new(source.root()), &mut calls);
+            for (func, range, path) in calls {
+                let (resolved, exists) = match &path {
+                    Some(path) => {
+                        let file = id.join(path);
+                        let exists = ctx.world().file(file).is_ok();
+                        (ctx.path_for_id(file).ok(), Some(exists))
+                    }
+                    None => (None, None),
+                };
+                resources.push(ResourceReference {
+                    func,
+                    path,
+                    resolved,
+                    exists,
+                    uri: uri.clone(),
+                    range: ctx.to_lsp_range(range, &source),
+                });
+            }
+        }
+
+        Some(resources)
+    }
+}
+
+/// Collects the calls reading resources, with their ranges and the literal
+/// paths. A call reading multiple files, such as `bibliography(("a.bib",
+/// "b.bib"))`, is collected for each path.
+fn collect_calls(node: LinkedNode, calls: &mut Vec<(String, Range<usize>, Option<String>)>) {
+    if let Some(call) = node.cast::<ast::FuncCall>() {
+        if let ast::Expr::Ident(callee) = call.callee() {
+            let func = callee.get().as_str();
+            if RESOURCE_FUNCS.contains(&func) {
+                let first = call.args().items().find_map(|arg| match arg {
+                    ast::Arg::Pos(expr) => Some(expr),
+                    _ => None,
+                });
+                let paths = match first {
+                    Some(ast::Expr::Str(path)) => vec![Some(path.get().to_string())],
+                    Some(ast::Expr::Array(array)) => array
+                        .items()
+                        .map(|item| match item {
+                            ast::ArrayItem::Pos(ast::Expr::Str(path)) => {
+                                Some(path.get().to_string())
+                            }
+                            _ => None,
+                        })
+                        .collect(),
+                    // A path computed at runtime.
+                    Some(_) => vec![None],
+                    None => vec![],
+                };
+                for path in paths {
+                    calls.push((func.to_owned(), node.range(), path));
+                }
+            }
+        }
+    }
+
+    for child in node.children() {
+        collect_calls(child, calls);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    #[test]
+    fn test_document_resources() {
+        let source = r#"= Data
+-----
+#read("s0.typ")
+#image("/img/missing.png", width: 50%)
+#let name = "logo"
+#image(name + ".png")
+#bibliography(("a.bib", "b.bib"))"#;
+
+        run_with_ctx(source, |ctx, path| {
+            let resources = DocumentResourcesRequest { path }.request(ctx).unwrap();
+            let summary = resources
+                .iter()
+                .map(|r| (r.func.as_str(), r.path.as_deref(), r.exists))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                summary,
+                [
+                    ("read", Some("s0.typ"), Some(true)),
+                    ("image", Some("/img/missing.png"), Some(false)),
+                    ("image", None, None),
+                    ("bibliography", Some("a.bib"), Some(false)),
+                    ("bibliography", Some("b.bib"), Some(false)),
+                ]
+            );
+            assert!(resources[1]
+                .resolved
+                .as_ref()
+                .is_some_and(|p| p.ends_with("img/missing.png")));
+        });
+    }
+}
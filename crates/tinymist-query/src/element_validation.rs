@@ -0,0 +1,120 @@
+use std::fmt;
+
+use typst::{
+    foundations::{Content, NativeElement, Selector, Value},
+    model::{Document as TypstDocument, FigureElem},
+};
+
+use crate::{diagnostics::diagnostic_at_span, prelude::*, DiagnosticsMap};
+
+/// A validator checking the elements in the compiled document, which lets
+/// templates enforce their own rules on the usage of elements.
+///
+/// The validators are registered by [`Analysis::element_validators`] and run
+/// after every successful compilation. The issues are reported at the source
+/// of the elements along with other diagnostics, see
+/// [`crate::convert_diagnostics`].
+///
+/// [`Analysis::element_validators`]: crate::analysis::Analysis::element_validators
+pub trait ElementValidator: fmt::Debug + Send + Sync {
+    /// The selector of the elements to validate.
+    fn selector(&self) -> Selector;
+
+    /// Validates an element selected in the document, returning the message of
+    /// the issue if it is invalid.
+    fn validate(&self, elem: &Content) -> Option<String>;
+
+    /// The severity of the issues found by the validator.
+    fn severity(&self) -> LspSeverity {
+        LspSeverity::WARNING
+    }
+}
+
+/// Requires every figure in the document to have a caption.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FigureCaptionValidator;
+
+impl ElementValidator for FigureCaptionValidator {
+    fn selector(&self) -> Selector {
+        FigureElem::elem().select()
+    }
+
+    fn validate(&self, elem: &Content) -> Option<String> {
+        match elem.get_by_name("caption") {
+            None | Some(Value::None) => Some("figure must have a caption".to_owned()),
+            Some(_) => None,
+        }
+    }
+}
+
+/// Runs the registered validators over the compiled document and converts the
+/// issues to diagnostics.
+///
+/// The elements without a source, such as the ones created by packages outside
+/// of the workspace, are skipped.
+pub fn validate_elements(ctx: &mut AnalysisContext, doc: &TypstDocument) -> DiagnosticsMap {
+    let validators = ctx.analysis.element_validators.clone();
+    let introspector = &doc.introspector;
+
+    let mut diagnostics = DiagnosticsMap::new();
+    for validator in validators {
+        for elem in introspector.query(&validator.selector()).iter() {
+            let Some(message) = validator.validate(elem) else {
+                continue;
+            };
+            let diagnostic = LspDiagnostic {
+                severity: Some(validator.severity()),
+                message,
+                source: Some("typst".to_owned()),
+                ..Default::default()
+            };
+            let Some((uri, diagnostic)) = diagnostic_at_span(ctx, elem.span(), diagnostic) else {
+                continue;
+            };
+            diagnostics.entry(uri).or_default().push(diagnostic);
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use typst::eval::Tracer;
+
+    use super::*;
+    use crate::{convert_diagnostics, diagnostic_id, tests::*};
+
+    #[test]
+    fn test_validate_elements() {
+        let source = "#figure[A]\n#figure([B], caption: [B])\n#figure[C]";
+
+        run_with_ctx(source, |ctx, _| {
+            let doc = typst::compile(ctx.world(), &mut Tracer::new()).unwrap();
+            let validate = |ctx: &mut AnalysisContext| {
+                let diagnostics = convert_diagnostics(ctx, std::iter::empty(), Some(&doc));
+                diagnostics.into_values().flatten().collect::<Vec<_>>()
+            };
+
+            assert!(validate(ctx).is_empty());
+
+            ctx.analysis.element_validators = vec![Arc::new(FigureCaptionValidator)];
+            let diagnostics = validate(ctx);
+            assert_eq!(diagnostics.len(), 2);
+            assert!(diagnostics
+                .iter()
+                .all(|d| d.message == "figure must have a caption"));
+            let ids = diagnostics.iter().map(diagnostic_id).collect::<Vec<_>>();
+            assert!(ids.iter().all(Option::is_some));
+            assert_ne!(ids[0], ids[1]);
+
+            // The issues are truncated along with other diagnostics.
+            ctx.analysis.max_diagnostics_per_file = Some(1);
+            let diagnostics = validate(ctx);
+            assert_eq!(diagnostics.len(), 2);
+            assert_eq!(diagnostics[1].message, "1 more diagnostics suppressed");
+        });
+    }
+}
@@ -0,0 +1,2 @@
+#[= Intro]
+/* position after */Ab
\ No newline at end of file
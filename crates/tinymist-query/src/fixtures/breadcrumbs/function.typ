@@ -0,0 +1,5 @@
+= Intro
+== Usage
+#let render(x) = {
+  /* position after */x + 1
+}
\ No newline at end of file
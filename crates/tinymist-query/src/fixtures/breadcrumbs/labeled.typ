@@ -0,0 +1,2 @@
+#figure[/* position after */Ab] <fig>
+= Intro <intro>
\ No newline at end of file
@@ -0,0 +1,4 @@
+= Intro
+#let outer() = {
+  let inner() = /* position after */12
+}
\ No newline at end of file
@@ -0,0 +1,4 @@
+= Intro
+== Usage
+= Reference
+/* position after */Text
\ No newline at end of file
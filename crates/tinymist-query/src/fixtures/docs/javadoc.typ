@@ -0,0 +1,5 @@
+/**
+ * This is X.
+ * Note: this is not Y.
+ */
+#let x /* ident */ = 1;
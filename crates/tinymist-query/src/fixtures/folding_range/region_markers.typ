@@ -0,0 +1,8 @@
+// region Intro
+Hello
+// region
+World
+// endregion
+// endregion
+// endregion
+// region Unclosed
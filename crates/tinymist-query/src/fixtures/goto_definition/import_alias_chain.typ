@@ -0,0 +1,8 @@
+// path: base.typ
+#let f() = 1;
+-----
+// path: reexport.typ
+#import "base.typ": f as g
+-----
+#import "reexport.typ": g as h
+#(/* position after */ h);
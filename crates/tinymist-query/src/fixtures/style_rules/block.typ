@@ -0,0 +1,4 @@
+#set par(justify: true)
+#[#set text(red)]
+/* position after */Ab
+#set text(blue)
\ No newline at end of file
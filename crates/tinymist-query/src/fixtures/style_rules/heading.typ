@@ -0,0 +1,3 @@
+#set text(red)
+#show: it => it
+= Heading /* position after */text
\ No newline at end of file
@@ -0,0 +1,3 @@
+#set page("a4")
+#[#set text(red)
+/* position after */Ab]
\ No newline at end of file
@@ -0,0 +1,3 @@
+#show heading: set text(blue)
+#show raw: it => it
+/* position after */Ab
\ No newline at end of file
@@ -0,0 +1,2 @@
+#show heading.where(level: 1): it => it
+= Heading /* position after */text
\ No newline at end of file
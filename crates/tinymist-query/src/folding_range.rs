@@ -7,6 +7,9 @@ use crate::{
 /// The [`textDocument/foldingRange`] request is sent from the client to the
 /// server to return all folding ranges found in a given text document.
 ///
+/// Besides the syntax structures, regions between `// region <name>` and `//
+/// endregion` comments are folded. Unbalanced markers are ignored.
+///
 /// [`textDocument/foldingRange`]: https://microsoft.github.io/language-server-protocol/specification#textDocument_foldingRange
 ///
 /// # Compatibility
@@ -48,6 +51,7 @@ impl SyntaxRequest for FoldingRangeRequest {
             true,
             &mut results,
         );
+        calc_region_folding_range(source, position_encoding, &mut results);
         if false {
             trace!("FoldingRangeRequest(line_folding_only={line_folding_only}) symbols: {symbols:#?} results: {results:#?}");
         }
@@ -125,6 +129,64 @@ fn calc_folding_range(
     }
 }
 
+/// The marker of a folding region in a line comment.
+enum RegionMarker<'a> {
+    Start(&'a str),
+    End,
+}
+
+fn region_marker(comment: &str) -> Option<RegionMarker> {
+    let text = comment.strip_prefix("//")?.trim();
+    let (marker, name) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    match marker {
+        "region" => Some(RegionMarker::Start(name.trim())),
+        "endregion" => Some(RegionMarker::End),
+        _ => None,
+    }
+}
+
+fn calc_region_folding_range(
+    source: &Source,
+    position_encoding: PositionEncoding,
+    ranges: &mut Vec<FoldingRange>,
+) {
+    fn walk<'a>(node: LinkedNode<'a>, markers: &mut Vec<(usize, RegionMarker<'a>)>) {
+        if node.kind() == SyntaxKind::LineComment {
+            if let Some(marker) = region_marker(node.get().text()) {
+                markers.push((node.offset(), marker));
+            }
+        }
+
+        for child in node.children() {
+            walk(child, markers);
+        }
+    }
+
+    let mut markers = vec![];
+    walk(LinkedNode::new(source.root()), &mut markers);
+
+    let line_of = |offset| typst_to_lsp::offset_to_position(offset, position_encoding, source).line;
+    let mut starts = vec![];
+    for (offset, marker) in markers {
+        match marker {
+            RegionMarker::Start(name) => starts.push((offset, name)),
+            RegionMarker::End => {
+                let Some((start, name)) = starts.pop() else {
+                    continue;
+                };
+                ranges.push(FoldingRange {
+                    start_line: line_of(start),
+                    start_character: None,
+                    end_line: line_of(offset),
+                    end_character: None,
+                    kind: Some(FoldingRangeKind::Region),
+                    collapsed_text: (!name.is_empty()).then(|| name.to_owned()),
+                });
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
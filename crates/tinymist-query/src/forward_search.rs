@@ -0,0 +1,54 @@
+use std::num::NonZeroUsize;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{jump_from_cursor, prelude::*};
+
+/// The location in the compiled document found by a forward search.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ForwardSearchResult {
+    /// The page containing the text at the cursor, starting from 1.
+    pub page: NonZeroUsize,
+    /// The number of pages in the document, so that the client can step
+    /// through the pages, e.g. the slides of a deck.
+    pub page_count: usize,
+    /// The position of the text in the page in pt, in the form of `[x, y]`.
+    pub position: [f64; 2],
+}
+
+/// A request to find the page and the position in the compiled document
+/// showing the text at a cursor in the source.
+///
+/// It requires a successfully compiled document, and only the text in markup
+/// can be found.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct ForwardSearchRequest {
+    /// The path of the source file.
+    pub path: PathBuf,
+    /// The cursor in the source file.
+    pub position: LspPosition,
+}
+
+impl StatefulRequest for ForwardSearchRequest {
+    type Response = ForwardSearchResult;
+
+    fn request(
+        self,
+        ctx: &mut AnalysisContext,
+        doc: Option<VersionedDocument>,
+    ) -> Option<Self::Response> {
+        let doc = doc?;
+        let source = ctx.source_by_path(&self.path).ok()?;
+        let cursor = ctx.to_typst_pos(self.position, &source)?;
+
+        let position = jump_from_cursor(&doc.document, &source, cursor)?;
+        Some(ForwardSearchResult {
+            page: position.page,
+            page_count: doc.document.pages.len(),
+            position: [position.point.x.to_pt(), position.point.y.to_pt()],
+        })
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{analysis::find_definition, prelude::*};
+use crate::{analysis::find_source_definition, prelude::*};
 
 /// The [`textDocument/definition`] request asks the server for the definition
 /// location of a symbol at a given text document position.
@@ -35,7 +35,7 @@ impl StatefulRequest for GotoDefinitionRequest {
         let deref_target = ctx.deref_syntax_at(&source, self.position, 1)?;
         let origin_selection_range = ctx.to_lsp_range(deref_target.node().range(), &source);
 
-        let def = find_definition(ctx, source.clone(), doc.as_ref(), deref_target)?;
+        let def = find_source_definition(ctx, source.clone(), doc.as_ref(), deref_target)?;
 
         let (fid, def_range) = def.def_at?;
 
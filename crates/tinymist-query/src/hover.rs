@@ -1,7 +1,7 @@
 use core::fmt;
 
 use crate::{
-    analysis::{analyze_dyn_signature, find_definition, DefinitionLink, Signature},
+    analysis::{analyze_dyn_signature, find_source_definition, DefinitionLink, Signature},
     jump_from_cursor,
     prelude::*,
     syntax::{find_docs_before, get_deref_target, LexicalKind, LexicalVarKind},
@@ -134,7 +134,7 @@ fn def_tooltip(
 
     let deref_target = get_deref_target(leaf.clone(), cursor)?;
 
-    let lnk = find_definition(ctx, source.clone(), document, deref_target.clone())?;
+    let lnk = find_source_definition(ctx, source.clone(), document, deref_target.clone())?;
 
     let mut results = vec![];
     let mut actions = vec![];
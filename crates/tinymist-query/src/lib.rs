@@ -15,6 +15,7 @@ mod upstream;
 
 pub(crate) mod diagnostics;
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 pub use analysis::AnalysisContext;
@@ -25,22 +26,40 @@ pub(crate) mod code_action;
 pub use code_action::*;
 pub(crate) mod code_context;
 pub use code_context::*;
+pub(crate) mod check_references;
+pub use check_references::*;
+pub(crate) mod document_resources;
+pub use document_resources::*;
 pub(crate) mod code_lens;
 pub use code_lens::*;
 pub(crate) mod completion;
 pub use completion::*;
 pub(crate) mod color_presentation;
 pub use color_presentation::*;
+pub(crate) mod element_validation;
+pub use element_validation::*;
 pub(crate) mod document_color;
 pub use document_color::*;
 pub(crate) mod document_highlight;
 pub use document_highlight::*;
 pub(crate) mod document_symbol;
 pub use document_symbol::*;
+pub(crate) mod document_fonts;
+pub use document_fonts::*;
+pub(crate) mod document_metadata;
+pub use document_metadata::*;
+pub(crate) mod page_text;
+pub use page_text::*;
+pub(crate) mod document_headings;
+pub use document_headings::*;
+pub(crate) mod document_outline;
+pub use document_outline::*;
 pub(crate) mod document_metrics;
 pub use document_metrics::*;
 pub(crate) mod folding_range;
 pub use folding_range::*;
+pub(crate) mod forward_search;
+pub use forward_search::*;
 pub(crate) mod goto_declaration;
 pub use goto_declaration::*;
 pub(crate) mod goto_definition;
@@ -69,6 +88,14 @@ pub(crate) mod prepare_rename;
 pub use prepare_rename::*;
 pub(crate) mod references;
 pub use references::*;
+pub(crate) mod why_depends;
+pub use why_depends::*;
+pub(crate) mod syntax_tree;
+pub use syntax_tree::*;
+pub(crate) mod style_rules;
+pub use style_rules::*;
+pub(crate) mod breadcrumbs;
+pub use breadcrumbs::*;
 
 pub mod lsp_typst_boundary;
 pub use lsp_typst_boundary::*;
@@ -125,6 +152,54 @@ pub trait StatefulRequest {
     ) -> Option<Self::Response>;
 }
 
+/// A sink receiving the partial results of a request in batches, before the
+/// request is finished.
+///
+/// The workspace-wide requests send a batch once they finish processing a file,
+/// so that the client can show the results incrementally. The response of the
+/// request still contains the full results, which shall not be sent to the
+/// client again if any batch is sent.
+pub struct PartialResultSink<T> {
+    f: Arc<dyn Fn(T) + Send + Sync>,
+    sent: Arc<AtomicBool>,
+}
+
+impl<T> PartialResultSink<T> {
+    /// Creates a sink calling the function with each batch.
+    pub fn new(f: impl Fn(T) + Send + Sync + 'static) -> Self {
+        Self {
+            f: Arc::new(f),
+            sent: Arc::default(),
+        }
+    }
+
+    /// Sends a batch of partial results.
+    pub fn send(&self, batch: T) {
+        self.sent.store(true, Ordering::SeqCst);
+        (self.f)(batch)
+    }
+
+    /// Whether any batch of partial results is sent.
+    pub fn is_sent(&self) -> bool {
+        self.sent.load(Ordering::SeqCst)
+    }
+}
+
+impl<T> Clone for PartialResultSink<T> {
+    fn clone(&self) -> Self {
+        Self {
+            f: self.f.clone(),
+            sent: self.sent.clone(),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for PartialResultSink<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PartialResultSink")
+    }
+}
+
 #[allow(missing_docs)]
 mod polymorphic {
     use lsp_types::TextEdit;
@@ -144,8 +219,18 @@ mod polymorphic {
     #[derive(Debug, Clone)]
     pub enum ExportKind {
         Pdf,
-        Svg { page: PageSelection },
-        Png { page: PageSelection },
+        Svg {
+            page: PageSelection,
+        },
+        Png {
+            page: PageSelection,
+        },
+        /// A zip archive of the PDF, the artifacts of `kinds` and, if
+        /// `sources` is set, the files the document depends on.
+        Bundle {
+            sources: bool,
+            kinds: Vec<ExportKind>,
+        },
     }
 
     impl ExportKind {
@@ -154,6 +239,7 @@ mod polymorphic {
                 Self::Pdf => "pdf",
                 Self::Svg { .. } => "svg",
                 Self::Png { .. } => "png",
+                Self::Bundle { .. } => "zip",
             }
         }
     }
@@ -162,6 +248,8 @@ mod polymorphic {
     pub struct OnExportRequest {
         pub path: PathBuf,
         pub kind: ExportKind,
+        /// Whether to write the file even if it is unchanged.
+        pub force: bool,
     }
 
     #[derive(Debug, Clone)]
@@ -185,6 +273,12 @@ mod polymorphic {
         pub font_paths: Vec<PathBuf>,
         pub inputs: Dict,
         pub estimated_memory_usage: HashMap<String, usize>,
+        /// Whether the last compilation reused the state of a previous one.
+        pub last_compile_incremental: Option<bool>,
+        /// The number of compilations reusing the state of a previous one.
+        pub incremental_compiles: usize,
+        /// The number of compilations from scratch.
+        pub full_compiles: usize,
     }
 
     #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -223,6 +317,18 @@ mod polymorphic {
         InteractCodeContext(InteractCodeContextRequest),
 
         DocumentMetrics(DocumentMetricsRequest),
+        DocumentFonts(DocumentFontsRequest),
+        DocumentMetadata(DocumentMetadataRequest),
+        PageText(PageTextRequest),
+        DocumentHeadings(DocumentHeadingsRequest),
+        DocumentOutline(DocumentOutlineRequest),
+        ForwardSearch(ForwardSearchRequest),
+        CheckReferences(CheckReferencesRequest),
+        WhyDepends(WhyDependsRequest),
+        SyntaxTree(SyntaxTreeRequest),
+        DocumentResources(DocumentResourcesRequest),
+        StyleRules(StyleRulesRequest),
+        Breadcrumbs(BreadcrumbsRequest),
         ServerInfo(ServerInfoRequest),
     }
 
@@ -256,6 +362,18 @@ mod polymorphic {
                 CompilerQueryRequest::InteractCodeContext(..) => PinnedFirst,
 
                 CompilerQueryRequest::DocumentMetrics(..) => PinnedFirst,
+                CompilerQueryRequest::DocumentFonts(..) => PinnedFirst,
+                CompilerQueryRequest::DocumentMetadata(..) => PinnedFirst,
+                CompilerQueryRequest::PageText(..) => PinnedFirst,
+                CompilerQueryRequest::DocumentHeadings(..) => PinnedFirst,
+                CompilerQueryRequest::DocumentOutline(..) => PinnedFirst,
+                CompilerQueryRequest::ForwardSearch(..) => PinnedFirst,
+                CompilerQueryRequest::CheckReferences(..) => PinnedFirst,
+                CompilerQueryRequest::WhyDepends(..) => Mergeable,
+                CompilerQueryRequest::SyntaxTree(..) => Mergeable,
+                CompilerQueryRequest::DocumentResources(..) => Mergeable,
+                CompilerQueryRequest::StyleRules(..) => ContextFreeUnique,
+                CompilerQueryRequest::Breadcrumbs(..) => ContextFreeUnique,
                 CompilerQueryRequest::ServerInfo(..) => Mergeable,
             }
         }
@@ -288,6 +406,18 @@ mod polymorphic {
                 CompilerQueryRequest::InteractCodeContext(req) => &req.path,
 
                 CompilerQueryRequest::DocumentMetrics(req) => &req.path,
+                CompilerQueryRequest::DocumentFonts(req) => &req.path,
+                CompilerQueryRequest::DocumentMetadata(req) => &req.path,
+                CompilerQueryRequest::PageText(req) => &req.path,
+                CompilerQueryRequest::DocumentHeadings(req) => &req.path,
+                CompilerQueryRequest::DocumentOutline(req) => &req.path,
+                CompilerQueryRequest::ForwardSearch(req) => &req.path,
+                CompilerQueryRequest::CheckReferences(req) => &req.path,
+                CompilerQueryRequest::WhyDepends(..) => return None,
+                CompilerQueryRequest::SyntaxTree(req) => &req.path,
+                CompilerQueryRequest::DocumentResources(req) => &req.path,
+                CompilerQueryRequest::StyleRules(req) => &req.path,
+                CompilerQueryRequest::Breadcrumbs(req) => &req.path,
                 CompilerQueryRequest::ServerInfo(..) => return None,
             })
         }
@@ -321,6 +451,18 @@ mod polymorphic {
         InteractCodeContext(Option<Vec<InteractCodeContextResponse>>),
 
         DocumentMetrics(Option<DocumentMetricsResponse>),
+        DocumentFonts(Option<Vec<DocumentFont>>),
+        DocumentMetadata(Option<DocumentMetadata>),
+        PageText(Option<String>),
+        DocumentHeadings(Option<Vec<DocumentHeading>>),
+        DocumentOutline(Option<Vec<DocumentOutlineItem>>),
+        ForwardSearch(Option<ForwardSearchResult>),
+        CheckReferences(Option<DiagnosticsMap>),
+        WhyDepends(Option<Vec<Vec<PathBuf>>>),
+        SyntaxTree(Option<SyntaxTreeNode>),
+        DocumentResources(Option<Vec<ResourceReference>>),
+        StyleRules(Option<Vec<StyleRule>>),
+        Breadcrumbs(Option<Vec<Breadcrumb>>),
         ServerInfo(Option<HashMap<String, ServerInfoResponse>>),
     }
 }
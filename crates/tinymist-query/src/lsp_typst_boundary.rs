@@ -8,6 +8,7 @@ use std::path::{Path, PathBuf};
 use lsp_types::{self, Url};
 use once_cell::sync::Lazy;
 use reflexo::path::PathClean;
+use typst::syntax::{is_newline, Source};
 
 pub type LspPosition = lsp_types::Position;
 /// The interpretation of an `LspCharacterOffset` depends on the
@@ -83,6 +84,36 @@ pub fn path_to_url(path: &Path) -> anyhow::Result<Url> {
     })
 }
 
+/// Normalizes a path so that paths referring to the same file compare equal.
+///
+/// On Windows, this strips the verbatim prefix (`\\?\`) and uppercases the
+/// drive letter. It is a no-op on other platforms.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    if cfg!(windows) {
+        if let Some(p) = path.to_str() {
+            return PathBuf::from(normalize_windows_path(p));
+        }
+    }
+
+    path.to_owned()
+}
+
+fn normalize_windows_path(path: &str) -> String {
+    let path = if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{unc}")
+    } else {
+        path.strip_prefix(r"\\?\").unwrap_or(path).to_owned()
+    };
+
+    let mut chars = path.chars();
+    match (chars.next(), chars.next()) {
+        (Some(drive), Some(':')) if drive.is_ascii_alphabetic() => {
+            format!("{}{}", drive.to_ascii_uppercase(), &path[1..])
+        }
+        _ => path,
+    }
+}
+
 pub fn url_to_path(uri: Url) -> PathBuf {
     if uri.scheme() == "file" {
         // typst converts an empty path to `Path::new("/")`, which is undesirable.
@@ -112,6 +143,14 @@ pub fn url_to_path(uri: Url) -> PathBuf {
     uri.to_file_path().unwrap()
 }
 
+/// Gets the byte range of a line in the source, excluding the line ending.
+fn line_content_range(source: &Source, line: usize) -> Option<TypstRange> {
+    let range = source.line_to_range(line)?;
+    let text = source.text().get(range.clone())?;
+    let content = text.trim_end_matches(is_newline);
+    Some(range.start..range.start + content.len())
+}
+
 pub mod lsp_to_typst {
     use typst::syntax::Source;
 
@@ -146,41 +185,26 @@ pub mod lsp_to_typst {
             return Some(typst_source.len_bytes());
         }
 
-        match lsp_position_encoding {
-            LspPositionEncoding::Utf8 => {
-                let line_index = lsp_position.line as usize;
-                let column_index = lsp_position.character as usize;
-                typst_source.line_column_to_byte(line_index, column_index)
-            }
-            LspPositionEncoding::Utf16 => {
-                // We have a line number and a UTF-16 offset into that line. We want a byte
-                // offset into the file.
-                //
-                // Typst's `Source` provides several UTF-16 methods:
-                //  - `len_utf16` for the length of the file
-                //  - `byte_to_utf16` to convert a byte offset from the start of the file to a
-                //    UTF-16 offset from the start of the file
-                //  - `utf16_to_byte` to do the opposite of `byte_to_utf16`
-                //
-                // Unfortunately, none of these address our needs well, so we do some math
-                // instead. This is not the fastest possible implementation, but
-                // it's the most reasonable without access to the internal state
-                // of `Source`.
-
-                // TODO: Typst's `Source` could easily provide an implementation of the method
-                // we need   here. Submit a PR against `typst` to add it, then
-                // update this if/when merged.
-
-                let line_index = lsp_position.line as usize;
-                let utf16_offset_in_line = lsp_position.character as usize;
-
-                let byte_line_offset = typst_source.line_to_byte(line_index)?;
-                let utf16_line_offset = typst_source.byte_to_utf16(byte_line_offset)?;
-                let utf16_offset = utf16_line_offset + utf16_offset_in_line;
-
-                typst_source.utf16_to_byte(utf16_offset)
+        // Clamps the column to the line content, so that positions past the end of
+        // a line never land between the `\r` and `\n` of a CRLF line ending, as
+        // required by the LSP specification.
+        let line_index = lsp_position.line as usize;
+        let line_range = line_content_range(typst_source, line_index)?;
+        let line_text = &typst_source.text()[line_range.clone()];
+
+        let column = lsp_position.character as usize;
+        let mut units = 0;
+        for (offset, ch) in line_text.char_indices() {
+            if units >= column {
+                return Some(line_range.start + offset);
             }
+            units += match lsp_position_encoding {
+                LspPositionEncoding::Utf8 => 1,
+                LspPositionEncoding::Utf16 => ch.len_utf16(),
+            };
         }
+
+        Some(line_range.end)
     }
 
     pub fn range(
@@ -218,6 +242,12 @@ pub mod typst_to_lsp {
         }
 
         let line_index = typst_source.byte_to_line(typst_offset).unwrap();
+        // Offsets inside a line ending, such as between the `\r` and `\n` of a
+        // CRLF, are reported at the end of the line.
+        let typst_offset = match line_content_range(typst_source, line_index) {
+            Some(range) => typst_offset.min(range.end),
+            None => typst_offset,
+        };
         let column_index = typst_source.byte_to_column(typst_offset).unwrap();
 
         let lsp_line = line_index as u32;
@@ -288,6 +318,22 @@ mod test {
         assert_eq!(path, Path::new("/untitled/test").clean());
     }
 
+    #[test]
+    fn test_normalize_windows_path() {
+        assert_eq!(normalize_windows_path(r"c:\a\b.typ"), r"C:\a\b.typ");
+        assert_eq!(normalize_windows_path(r"C:\a\b.typ"), r"C:\a\b.typ");
+        assert_eq!(normalize_windows_path(r"\\?\c:\a\b.typ"), r"C:\a\b.typ");
+        assert_eq!(
+            normalize_windows_path(r"\\?\UNC\server\share\b.typ"),
+            r"\\server\share\b.typ"
+        );
+        assert_eq!(
+            normalize_windows_path(r"\\server\share\b.typ"),
+            r"\\server\share\b.typ"
+        );
+        assert_eq!(normalize_windows_path(r"a\b.typ"), r"a\b.typ");
+    }
+
     #[test]
     fn unnamed_buffer() {
         // https://github.com/neovim/nvim-lspconfig/pull/2226
@@ -472,4 +518,42 @@ mod test {
         assert_eq!(post_emoji_position, post_emoji_actual);
         assert_eq!(end_position, end_actual);
     }
+
+    #[test]
+    fn crlf_position_to_offset() {
+        let source = Source::detached("a😀b\r\ncd\r\n");
+
+        let position = |line, character| {
+            let position = LspPosition { line, character };
+            lsp_to_typst::position(position, PositionEncoding::Utf16, &source).unwrap()
+        };
+
+        assert_eq!(position(0, 3), 5);
+        assert_eq!(position(0, 4), 6);
+        // Positions past the end of a line are clamped before the CRLF.
+        assert_eq!(position(0, 5), 6);
+        assert_eq!(position(0, 100), 6);
+        assert_eq!(position(1, 0), 8);
+        assert_eq!(position(1, 3), 10);
+        assert_eq!(position(2, 0), 12);
+    }
+
+    #[test]
+    fn crlf_offset_to_position() {
+        let source = Source::detached("a😀b\r\ncd\r\n");
+
+        let position = |offset| {
+            let position =
+                typst_to_lsp::offset_to_position(offset, PositionEncoding::Utf16, &source);
+            (position.line, position.character)
+        };
+
+        assert_eq!(position(6), (0, 4));
+        // Offsets inside the CRLF are reported at the end of the line.
+        assert_eq!(position(7), (0, 4));
+        assert_eq!(position(8), (1, 0));
+        assert_eq!(position(10), (1, 2));
+        assert_eq!(position(11), (1, 2));
+        assert_eq!(position(12), (2, 0));
+    }
 }
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use typst::{
+    layout::{Abs, Frame, FrameItem, Point},
+    model::Document,
+};
+
+use crate::{AnalysisContext, StatefulRequest, VersionedDocument};
+
+/// A request to get the plain text of a page in the compiled document, for
+/// accessibility tooling and text extraction. It requires a successfully
+/// compiled document.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct PageTextRequest {
+    /// The path of the document to get the text.
+    pub path: PathBuf,
+    /// The page to get the text, starting from 1.
+    pub page: usize,
+}
+
+impl StatefulRequest for PageTextRequest {
+    type Response = String;
+
+    fn request(
+        self,
+        _ctx: &mut AnalysisContext,
+        doc: Option<VersionedDocument>,
+    ) -> Option<Self::Response> {
+        page_text(&doc?.document, self.page)
+    }
+}
+
+/// Gets the plain text of a page, starting from 1, or None if the page doesn't
+/// exist.
+///
+/// The text runs are visited in the order of the frames laid out by typst,
+/// which is the reading order of the page, so that the columns are read one
+/// after another. A line break is inserted between two runs if the latter
+/// starts on another baseline or goes back to the left, such as the first run
+/// of the next line or the next column.
+pub fn page_text(doc: &Document, page: usize) -> Option<String> {
+    let page = doc.pages.get(page.checked_sub(1)?)?;
+
+    let mut writer = TextWriter::default();
+    writer.write_frame(&page.frame, Point::zero());
+    Some(writer.text)
+}
+
+#[derive(Default)]
+struct TextWriter {
+    text: String,
+    /// The baseline, the end and the font size of the last text run.
+    last: Option<(Abs, Abs, Abs)>,
+}
+
+impl TextWriter {
+    fn write_frame(&mut self, frame: &Frame, offset: Point) {
+        for (pos, item) in frame.items() {
+            let pos = offset + *pos;
+            match item {
+                FrameItem::Group(group) => {
+                    let pos = pos + Point::new(group.transform.tx, group.transform.ty);
+                    self.write_frame(&group.frame, pos);
+                }
+                FrameItem::Text(text) => {
+                    if let Some((baseline, end, size)) = self.last {
+                        let tolerance = size.max(text.size) / 2.0;
+                        if (pos.y - baseline).abs() > tolerance || pos.x + tolerance < end {
+                            self.text.push('\n');
+                        }
+                    }
+
+                    self.text.push_str(&text.text);
+                    self.last = Some((pos.y, pos.x + text.width(), text.size));
+                }
+                FrameItem::Shape(..) | FrameItem::Image(..) | FrameItem::Meta(..) => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use typst::eval::Tracer;
+
+    use super::*;
+    use crate::tests::*;
+
+    #[test]
+    fn test_page_text() {
+        let source = r#"#set page(width: 200pt, height: 100pt, columns: 2)
+First column
+#colbreak()
+Second column
+#pagebreak()
+Next page"#;
+
+        run_with_ctx(source, |ctx, path| {
+            let doc = typst::compile(ctx.world(), &mut Tracer::new()).unwrap();
+            let doc = Arc::new(doc);
+            let mut text = |page| {
+                let doc = VersionedDocument {
+                    version: 0,
+                    document: doc.clone(),
+                };
+                let path = path.clone();
+                PageTextRequest { path, page }.request(ctx, Some(doc))
+            };
+
+            assert_eq!(text(1).as_deref(), Some("First column\nSecond column"));
+            assert_eq!(text(2).as_deref(), Some("Next page"));
+            assert_eq!(text(0), None);
+            assert_eq!(text(3), None);
+        });
+    }
+}
@@ -3,7 +3,7 @@ use log::debug;
 use crate::{
     prelude::*,
     syntax::{DerefTarget, IdentRef},
-    SemanticRequest,
+    PartialResultSink, SemanticRequest,
 };
 
 /// The [`textDocument/references`] request is sent from the client to the
@@ -17,6 +17,8 @@ pub struct ReferencesRequest {
     pub path: PathBuf,
     /// The source code position to request for.
     pub position: LspPosition,
+    /// The sink receiving the references in each file once they are found.
+    pub partial: Option<PartialResultSink<Vec<LspLocation>>>,
 }
 
 impl SemanticRequest for ReferencesRequest {
@@ -27,7 +29,13 @@ impl SemanticRequest for ReferencesRequest {
         let deref_target = ctx.deref_syntax_at(&source, self.position, 1)?;
 
         let def_use = ctx.def_use(source.clone())?;
-        let locations = find_references(ctx, def_use, deref_target, ctx.position_encoding())?;
+        let locations = find_references(
+            ctx,
+            def_use,
+            deref_target,
+            ctx.position_encoding(),
+            self.partial.as_ref(),
+        )?;
 
         debug!("references: {locations:?}");
         Some(locations)
@@ -39,6 +47,7 @@ pub(crate) fn find_references(
     def_use: Arc<crate::analysis::DefUseInfo>,
     deref_target: DerefTarget<'_>,
     position_encoding: PositionEncoding,
+    partial: Option<&PartialResultSink<Vec<LspLocation>>>,
 ) -> Option<Vec<LspLocation>> {
     let node = match deref_target {
         DerefTarget::VarAccess(node) => node,
@@ -102,6 +111,7 @@ pub(crate) fn find_references(
         root_def_id,
         def_ident,
         position_encoding,
+        partial,
     )
 }
 
@@ -112,6 +122,7 @@ pub(crate) fn find_references_root(
     def_id: DefId,
     def_ident: IdentRef,
     position_encoding: PositionEncoding,
+    partial: Option<&PartialResultSink<Vec<LspLocation>>>,
 ) -> Option<Vec<LspLocation>> {
     let def_source = ctx.source_by_id(def_fid).ok()?;
     let uri = ctx.uri_for_id(def_fid).ok()?;
@@ -128,6 +139,9 @@ pub(crate) fn find_references_root(
             }
         })
         .collect::<Vec<_>>();
+    if let Some(partial) = partial.filter(|_| !references.is_empty()) {
+        partial.send(references.clone());
+    }
 
     if def_use.is_exported(def_id) {
         // Find dependents
@@ -141,7 +155,7 @@ pub(crate) fn find_references_root(
 
             let mut redefines = vec![];
             if let Some((id, _def)) = def_use.get_def(def_fid, &def_ident) {
-                references.extend(def_use.get_refs(id).map(|r| {
+                let batch = def_use.get_refs(id).map(|r| {
                     let range =
                         typst_to_lsp::range(r.range.clone(), &ref_source, position_encoding);
 
@@ -149,7 +163,12 @@ pub(crate) fn find_references_root(
                         uri: uri.clone(),
                         range,
                     }
-                }));
+                });
+                let batch = batch.collect::<Vec<_>>();
+                if let Some(partial) = partial.filter(|_| !batch.is_empty()) {
+                    partial.send(batch.clone());
+                }
+                references.extend(batch);
                 redefines.push(id);
 
                 if def_use.is_exported(id) {
@@ -178,6 +197,7 @@ mod tests {
             let request = ReferencesRequest {
                 path: path.clone(),
                 position: find_test_position(&source),
+                partial: None,
             };
 
             let result = request.request(world);
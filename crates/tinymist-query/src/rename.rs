@@ -31,7 +31,8 @@ impl StatefulRequest for RenameRequest {
         validate_renaming_definition(&lnk)?;
 
         let def_use = ctx.def_use(source.clone())?;
-        let references = find_references(ctx, def_use, deref_target, ctx.position_encoding())?;
+        let references =
+            find_references(ctx, def_use, deref_target, ctx.position_encoding(), None)?;
 
         let mut editions = HashMap::new();
 
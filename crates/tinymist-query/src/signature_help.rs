@@ -2,7 +2,7 @@ use once_cell::sync::OnceCell;
 
 use crate::{
     adt::interner::Interned,
-    analysis::{analyze_dyn_signature, find_definition, Ty},
+    analysis::{analyze_dyn_signature, find_source_definition, Ty},
     prelude::*,
     syntax::{get_check_target, get_deref_target, CheckTarget, ParamTarget},
     DocTooltip, LspParamInfo, SemanticRequest,
@@ -40,7 +40,7 @@ impl SemanticRequest for SignatureHelpRequest {
 
         let deref_target = get_deref_target(callee, cursor)?;
 
-        let def_link = find_definition(ctx, source.clone(), None, deref_target)?;
+        let def_link = find_source_definition(ctx, source.clone(), None, deref_target)?;
 
         let type_sig = ctx.user_type_of_def(&source, &def_link);
 
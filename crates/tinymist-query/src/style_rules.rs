@@ -0,0 +1,214 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{prelude::*, SyntaxRequest};
+
+/// The elements which are only affected by the rules targeting them, when they
+/// are written in the source. The rules targeting other functions, such as
+/// `text` and `par`, may affect the content anywhere.
+const STRUCTURAL_ELEMENTS: &[&str] = &[
+    "heading",
+    "strong",
+    "emph",
+    "raw",
+    "link",
+    "ref",
+    "list",
+    "enum",
+    "terms",
+    "math.equation",
+    "figure",
+    "table",
+    "footnote",
+    "quote",
+    "cite",
+];
+
+/// The kind of a style rule.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum StyleRuleKind {
+    /// A `set` rule.
+    Set,
+    /// A `show` rule.
+    Show,
+}
+
+/// A style rule in scope at a position.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StyleRule {
+    /// The kind of the rule.
+    pub kind: StyleRuleKind,
+    /// The function set by a `set` rule, or the selector of a `show` rule,
+    /// which is None if the `show` rule applies to everything.
+    pub target: Option<String>,
+    /// Whether the rule applies only if a condition holds, written as
+    /// `set text(red) if cond`.
+    pub conditional: bool,
+    /// The range of the rule.
+    pub range: LspRange,
+}
+
+/// A request to list the `set` and `show` rules in scope at a position, which
+/// may style the content there, in the order they are applied.
+///
+/// A rule applies to the content after it until the end of the enclosing
+/// markup or code block, so the rules are collected from the enclosing blocks,
+/// the outermost first. The rules targeting an element written in the source,
+/// such as `show heading: ..`, are omitted unless the position is inside such
+/// an element. The rules applied to the file by other files, such as a
+/// template applied before including the file, are not known.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct StyleRulesRequest {
+    /// The path of the document to list the rules.
+    pub path: PathBuf,
+    /// The position in the document.
+    pub position: LspPosition,
+}
+
+impl SyntaxRequest for StyleRulesRequest {
+    type Response = Vec<StyleRule>;
+
+    fn request(
+        self,
+        source: &Source,
+        position_encoding: PositionEncoding,
+    ) -> Option<Self::Response> {
+        let offset = lsp_to_typst::position(self.position, position_encoding, source)?;
+        let root = LinkedNode::new(source.root());
+        let leaf = root.leaf_at(offset)?;
+
+        let mut ancestors = vec![];
+        let mut node = Some(leaf);
+        while let Some(n) = node {
+            node = n.parent().cloned();
+            ancestors.push(n);
+        }
+        ancestors.reverse();
+
+        let elements = ancestors
+            .iter()
+            .filter_map(enclosing_element)
+            .collect::<Vec<_>>();
+        let affects = |target: Option<&str>| {
+            target.map_or(true, |target| {
+                !STRUCTURAL_ELEMENTS.contains(&target) || elements.iter().any(|e| e == target)
+            })
+        };
+
+        let mut rules = vec![];
+        for pair in ancestors.windows(2) {
+            let (block, child) = (&pair[0], &pair[1]);
+            if !matches!(block.kind(), SyntaxKind::Markup | SyntaxKind::Code) {
+                continue;
+            }
+
+            for prev in block.children().take_while(|c| c.offset() < child.offset()) {
+                let Some(rule) = style_rule(&prev) else {
+                    continue;
+                };
+                if affects(rule.element.as_deref()) {
+                    rules.push(StyleRule {
+                        kind: rule.kind,
+                        target: rule.target,
+                        conditional: rule.conditional,
+                        range: typst_to_lsp::range(prev.range(), source, position_encoding),
+                    });
+                }
+            }
+        }
+
+        Some(rules)
+    }
+}
+
+/// A style rule parsed from the source.
+struct ParsedRule {
+    kind: StyleRuleKind,
+    target: Option<String>,
+    /// The element targeted by the rule, if it is known.
+    element: Option<String>,
+    conditional: bool,
+}
+
+fn style_rule(node: &LinkedNode) -> Option<ParsedRule> {
+    if let Some(rule) = node.cast::<ast::SetRule>() {
+        let target = rule.target();
+        return Some(ParsedRule {
+            kind: StyleRuleKind::Set,
+            target: Some(target.to_untyped().clone().into_text().into()),
+            element: element_name(target),
+            conditional: rule.condition().is_some(),
+        });
+    }
+
+    let rule = node.cast::<ast::ShowRule>()?;
+    let selector = rule.selector();
+    Some(ParsedRule {
+        kind: StyleRuleKind::Show,
+        target: selector.map(|s| s.to_untyped().clone().into_text().into()),
+        element: selector.and_then(element_name),
+        conditional: false,
+    })
+}
+
+/// Gets the name of the element targeted by an expression, such as `heading`
+/// in `heading.where(level: 1)`.
+fn element_name(expr: ast::Expr) -> Option<String> {
+    match expr {
+        ast::Expr::Ident(ident) => Some(ident.get().to_string()),
+        ast::Expr::FieldAccess(access) => {
+            if matches!(access.field().as_str(), "where" | "with") {
+                element_name(access.target())
+            } else {
+                Some(access.to_untyped().clone().into_text().into())
+            }
+        }
+        ast::Expr::FuncCall(call) => element_name(call.callee()),
+        _ => None,
+    }
+}
+
+/// Gets the name of the element created by a node.
+fn enclosing_element(node: &LinkedNode) -> Option<String> {
+    let name = match node.kind() {
+        SyntaxKind::Heading => "heading",
+        SyntaxKind::Strong => "strong",
+        SyntaxKind::Emph => "emph",
+        SyntaxKind::Raw => "raw",
+        SyntaxKind::Link => "link",
+        SyntaxKind::Ref => "ref",
+        SyntaxKind::ListItem => "list",
+        SyntaxKind::EnumItem => "enum",
+        SyntaxKind::TermItem => "terms",
+        SyntaxKind::Equation => "math.equation",
+        SyntaxKind::FuncCall => {
+            return element_name(node.cast::<ast::FuncCall>()?.callee());
+        }
+        _ => return None,
+    };
+    Some(name.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    #[test]
+    fn test() {
+        snapshot_testing("style_rules", &|ctx, path| {
+            let source = ctx.source_by_path(&path).unwrap();
+
+            let request = StyleRulesRequest {
+                path: path.clone(),
+                position: find_test_position_after(&source),
+            };
+
+            let result = request.request(&source, PositionEncoding::Utf16);
+            assert_snapshot!(JsonRepr::new_redacted(result, &REDACT_LOC));
+        });
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{
     prelude::*,
     syntax::{get_lexical_hierarchy, LexicalHierarchy, LexicalScopeKind},
-    SemanticRequest,
+    PartialResultSink, SemanticRequest,
 };
 
 /// The [`workspace/symbol`] request is sent from the client to the server to
@@ -26,6 +26,8 @@ pub struct SymbolRequest {
     /// The query string to filter symbols by. It is usually the exact content
     /// of the user's input box in the UI.
     pub pattern: Option<String>,
+    /// The sink receiving the symbols of each file once they are found.
+    pub partial: Option<PartialResultSink<Vec<SymbolInformation>>>,
 }
 
 impl SemanticRequest for SymbolRequest {
@@ -34,31 +36,46 @@ impl SemanticRequest for SymbolRequest {
     fn request(self, ctx: &mut AnalysisContext) -> Option<Self::Response> {
         // todo: let typst.ts expose source
 
-        let mut symbols = vec![];
+        let mut paths = vec![];
+        ctx.resources
+            .iter_dependencies(&mut |path, _| paths.push(path.clone()));
 
-        ctx.resources.iter_dependencies(&mut |path, _| {
-            let Ok(source) = ctx.source_by_path(path) else {
-                return;
+        let mut sources = vec![];
+        let mut uris = vec![];
+        for path in paths {
+            let Ok(source) = ctx.source_by_path(&path) else {
+                continue;
             };
-            let uri = path_to_url(path).unwrap();
-            let res = get_lexical_hierarchy(source.clone(), LexicalScopeKind::Symbol).and_then(
-                |symbols| {
-                    self.pattern.as_ref().map(|pattern| {
-                        filter_document_symbols(
-                            &symbols,
-                            pattern,
-                            &source,
-                            &uri,
-                            ctx.position_encoding(),
-                        )
-                    })
-                },
-            );
+            sources.push(source);
+            uris.push(path_to_url(&path).unwrap());
+        }
+
+        // The files are independent, so their symbols are collected in parallel.
+        let hierarchies = ctx.par_map_sources(&sources, |source| {
+            get_lexical_hierarchy(source.clone(), LexicalScopeKind::Symbol)
+        });
+
+        let mut symbols = vec![];
+        for ((source, uri), hierarchy) in sources.iter().zip(uris).zip(hierarchies) {
+            let res = hierarchy.and_then(|symbols| {
+                self.pattern.as_ref().map(|pattern| {
+                    filter_document_symbols(
+                        &symbols,
+                        pattern,
+                        source,
+                        &uri,
+                        ctx.position_encoding(),
+                    )
+                })
+            });
 
             if let Some(mut res) = res {
+                if let Some(partial) = self.partial.as_ref().filter(|_| !res.is_empty()) {
+                    partial.send(res.clone());
+                }
                 symbols.append(&mut res)
             }
-        });
+        }
 
         Some(symbols)
     }
@@ -59,7 +59,7 @@ fn extract_document_between(
                 if text.starts_with('*') {
                     text = text.strip_prefix('*')?.trim();
                 }
-                comments.push(text.to_owned());
+                comments.push(strip_leading_stars(text));
             }
             _ => {
                 newline_count = 0;
@@ -85,6 +85,26 @@ fn extract_document_between(
     Some(docs)
 }
 
+/// Strips the leading stars of javadoc-style block comments, i.e. every line
+/// starts with a `*`.
+fn strip_leading_stars(text: &str) -> String {
+    let is_starred = text
+        .lines()
+        .all(|line| line.trim().is_empty() || line.trim_start().starts_with('*'));
+    if !is_starred || !text.contains('\n') {
+        return text.to_owned();
+    }
+
+    text.lines()
+        .map(|line| {
+            let line = line.trim_start();
+            let line = line.strip_prefix('*').unwrap_or(line);
+            line.strip_prefix(' ').unwrap_or(line)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub fn find_docs_before(src: &Source, cursor: usize) -> Option<String> {
     log::debug!("finding docs at: {id:?}, {cursor}", id = src.id());
 
@@ -0,0 +1,151 @@
+use std::path::Path;
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+
+/// The names of the ignore files respected when scanning the workspace.
+const IGNORE_FILES: &[&str] = &[".gitignore", ".ignore"];
+
+/// Loads the ignore files in a directory, returning None if there is no rule.
+fn load_ignore_files(dir: &Path) -> Option<Gitignore> {
+    let mut builder = GitignoreBuilder::new(dir);
+    for name in IGNORE_FILES {
+        let path = dir.join(name);
+        if !path.is_file() {
+            continue;
+        }
+        if let Some(err) = builder.add(&path) {
+            log::warn!("failed to parse ignore file {path:?}: {err}");
+        }
+    }
+
+    match builder.build() {
+        Ok(ignores) => (!ignores.is_empty()).then_some(ignores),
+        Err(err) => {
+            log::warn!("failed to load ignore files in {dir:?}: {err}");
+            None
+        }
+    }
+}
+
+/// The ignore files of the directories being walked, such as `.gitignore`,
+/// which follows the matching rules of git.
+///
+/// The paths must be visited in depth-first order, which is the order of
+/// `walkdir`.
+#[derive(Debug, Default)]
+pub struct IgnoreStack {
+    dirs: Vec<Gitignore>,
+}
+
+impl IgnoreStack {
+    /// Visits a path, returning whether it is ignored. The ignore files are
+    /// loaded when a directory not ignored is visited.
+    pub fn visit(&mut self, path: &Path, is_dir: bool) -> bool {
+        // Leaves the directories which are not ancestors of the path.
+        let parent = path.parent();
+        while let Some(last) = self.dirs.last() {
+            if parent.is_some_and(|parent| parent.starts_with(last.path())) {
+                break;
+            }
+            self.dirs.pop();
+        }
+
+        if self.is_ignored(path, is_dir) {
+            return true;
+        }
+
+        if is_dir {
+            self.dirs.extend(load_ignore_files(path));
+        }
+        false
+    }
+
+    /// Checks whether a path is ignored by the loaded ignore files. The rules
+    /// in deeper directories and the later rules in a file take precedence.
+    fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for dir in self.dirs.iter().rev() {
+            if !path.starts_with(dir.path()) {
+                continue;
+            }
+            match dir.matched(path, is_dir) {
+                Match::None => continue,
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+            }
+        }
+
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack(dirs: &[(&str, &str)]) -> IgnoreStack {
+        let dirs = dirs.iter().map(|(dir, rules)| {
+            let mut builder = GitignoreBuilder::new(dir);
+            for line in rules.lines() {
+                builder.add_line(None, line).unwrap();
+            }
+            builder.build().unwrap()
+        });
+        IgnoreStack {
+            dirs: dirs.collect(),
+        }
+    }
+
+    fn ignored(rules: &str, path: &str, is_dir: bool) -> bool {
+        let stack = stack(&[("/root", rules)]);
+        stack.is_ignored(&Path::new("/root").join(path), is_dir)
+    }
+
+    #[test]
+    fn test_ignore_rules() {
+        assert!(ignored("node_modules", "node_modules", true));
+        assert!(ignored("node_modules", "a/node_modules", true));
+        assert!(ignored("*.pdf", "a/b/main.pdf", false));
+        assert!(!ignored("*.pdf", "main.typ", false));
+        assert!(ignored("build/", "build", true));
+        assert!(!ignored("build/", "build", false));
+        assert!(ignored("main.[ot]yp", "main.typ", false));
+        assert!(!ignored("# comment", "# comment", false));
+    }
+
+    #[test]
+    fn test_anchored_rules() {
+        assert!(ignored("/out", "out", true));
+        assert!(!ignored("/out", "a/out", true));
+        // A pattern with a separator in the middle is also anchored.
+        assert!(ignored("docs/out", "docs/out", true));
+        assert!(!ignored("docs/out", "a/docs/out", true));
+    }
+
+    #[test]
+    fn test_double_star_rules() {
+        assert!(ignored("docs/**/*.svg", "docs/a/b/x.svg", false));
+        assert!(ignored("docs/**/*.svg", "docs/x.svg", false));
+        assert!(!ignored("docs/**/*.svg", "a/docs/x.svg", false));
+        assert!(ignored("**/out", "a/b/out", true));
+        assert!(ignored("**/out", "out", true));
+        assert!(ignored("docs/**", "docs/a/x.typ", false));
+    }
+
+    #[test]
+    fn test_negated_rules() {
+        assert!(!ignored("*.typ\n!main.typ", "main.typ", false));
+        assert!(ignored("*.typ\n!main.typ", "lib.typ", false));
+        assert!(ignored("!main.typ\n*.typ", "main.typ", false));
+        assert!(ignored("\\!main.typ", "!main.typ", false));
+    }
+
+    #[test]
+    fn test_nested_ignore_files() {
+        let stack = stack(&[("/root", "*.typ"), ("/root/docs", "!main.typ")]);
+        let ignored = |path: &str| stack.is_ignored(Path::new(path), false);
+        assert!(!ignored("/root/docs/main.typ"));
+        assert!(ignored("/root/docs/lib.typ"));
+        assert!(ignored("/root/main.typ"));
+    }
+}
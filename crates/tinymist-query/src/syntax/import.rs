@@ -1,3 +1,7 @@
+use std::collections::HashSet;
+
+use typst::syntax::SyntaxNode;
+
 use crate::prelude::*;
 
 /// Resolve a file id by its import path.
@@ -43,3 +47,164 @@ pub fn find_source_by_expr(
         _ => None,
     }
 }
+
+/// Find the packages imported or included by a source file.
+pub fn find_package_imports(source: &Source) -> Vec<PackageSpec> {
+    fn walk(node: &SyntaxNode, specs: &mut Vec<PackageSpec>) {
+        let path = match node.cast::<ast::Expr>() {
+            Some(ast::Expr::Import(import)) => Some(import.source()),
+            Some(ast::Expr::Include(include)) => Some(include.source()),
+            _ => None,
+        };
+        if let Some(ast::Expr::Str(path)) = path {
+            if let Ok(spec) = path.get().parse::<PackageSpec>() {
+                specs.push(spec);
+            }
+        }
+
+        for child in node.children() {
+            walk(child, specs);
+        }
+    }
+
+    let mut specs = vec![];
+    walk(source.root(), &mut specs);
+    specs
+}
+
+/// An import or include of a file in an import cycle.
+#[derive(Debug, Clone)]
+pub struct ImportCycleEdge {
+    /// The file importing the next file in the cycle.
+    pub file: TypstFileId,
+    /// The span of the path of the import.
+    pub span: TypstSpan,
+}
+
+/// Finds a cycle of imports and includes reachable from the main file, where
+/// each file imports the next one and the last one imports the first one.
+///
+/// Only the paths written as string literals are followed, and the imports of
+/// packages are skipped since a package can never import the files of the
+/// workspace. The imports of the files without a source are not followed.
+pub fn find_import_cycle(
+    main: TypstFileId,
+    source: impl Fn(TypstFileId) -> Option<Source>,
+) -> Option<Vec<ImportCycleEdge>> {
+    /// The files being visited, which are the ancestors of the current file.
+    struct Walker<F> {
+        source: F,
+        stack: Vec<ImportCycleEdge>,
+        done: HashSet<TypstFileId>,
+    }
+
+    impl<F: Fn(TypstFileId) -> Option<Source>> Walker<F> {
+        fn visit(&mut self, id: TypstFileId) -> Option<Vec<ImportCycleEdge>> {
+            if let Some(pos) = self.stack.iter().position(|edge| edge.file == id) {
+                return Some(self.stack[pos..].to_vec());
+            }
+            if !self.done.insert(id) {
+                return None;
+            }
+
+            let source = (self.source)(id)?;
+            for (path, span) in find_file_imports(&source) {
+                let Some(dep) = resolve_import_in_workspace(id, &path) else {
+                    continue;
+                };
+
+                self.stack.push(ImportCycleEdge { file: id, span });
+                let cycle = self.visit(dep);
+                self.stack.pop();
+                if cycle.is_some() {
+                    return cycle;
+                }
+            }
+
+            None
+        }
+    }
+
+    let mut walker = Walker {
+        source,
+        stack: vec![],
+        done: HashSet::new(),
+    };
+    walker.visit(main)
+}
+
+/// Resolves a path of the import in a file, returning None for the imports of
+/// packages.
+fn resolve_import_in_workspace(current: TypstFileId, import_path: &str) -> Option<TypstFileId> {
+    if import_path.starts_with('@') {
+        return None;
+    }
+
+    Some(current.join(import_path))
+}
+
+/// Finds the paths imported or included by a source file, written as string
+/// literals, along with their spans.
+fn find_file_imports(source: &Source) -> Vec<(EcoString, TypstSpan)> {
+    fn walk(node: &SyntaxNode, paths: &mut Vec<(EcoString, TypstSpan)>) {
+        let path = match node.cast::<ast::Expr>() {
+            Some(ast::Expr::Import(import)) => Some(import.source()),
+            Some(ast::Expr::Include(include)) => Some(include.source()),
+            _ => None,
+        };
+        if let Some(ast::Expr::Str(path)) = path {
+            paths.push((path.get(), path.span()));
+        }
+
+        for child in node.children() {
+            walk(child, paths);
+        }
+    }
+
+    let mut paths = vec![];
+    walk(source.root(), &mut paths);
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle_files(files: &[(&str, &str)]) -> Option<Vec<String>> {
+        let id = |path: &str| TypstFileId::new(None, VirtualPath::new(path));
+        let sources: HashMap<_, _> = files
+            .iter()
+            .map(|(path, text)| (id(path), Source::new(id(path), text.to_string())))
+            .collect();
+
+        let cycle = find_import_cycle(id(files[0].0), |id| sources.get(&id).cloned())?;
+        let files = cycle.into_iter().map(|edge| {
+            let path = edge.file.vpath().as_rootless_path();
+            path.to_string_lossy().into_owned()
+        });
+        Some(files.collect())
+    }
+
+    #[test]
+    fn test_import_cycle() {
+        let files = [
+            ("main.typ", "#import \"a.typ\": *"),
+            ("a.typ", "#include \"sub/b.typ\""),
+            ("sub/b.typ", "#import \"../a.typ\""),
+        ];
+        assert_eq!(cycle_files(&files).unwrap(), ["a.typ", "sub/b.typ"]);
+
+        let files = [("main.typ", "#import \"/main.typ\"")];
+        assert_eq!(cycle_files(&files).unwrap(), ["main.typ"]);
+    }
+
+    #[test]
+    fn test_no_import_cycle() {
+        let files = [
+            ("main.typ", "#import \"a.typ\"\n#import \"b.typ\""),
+            ("a.typ", "#import \"b.typ\""),
+            ("b.typ", "#import \"@preview/example:0.1.0\""),
+        ];
+        assert!(cycle_files(&files).is_none());
+    }
+}
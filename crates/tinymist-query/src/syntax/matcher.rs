@@ -55,7 +55,7 @@ fn is_mark(sk: SyntaxKind) -> bool {
 }
 
 /// A mode in which a text document is interpreted.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum InterpretMode {
     /// The position is in a comment.
@@ -104,6 +104,42 @@ pub(crate) fn interpret_mode_at(k: SyntaxKind) -> Option<InterpretMode> {
     })
 }
 
+/// Gets the mode in which the text at the cursor is interpreted.
+///
+/// Unlike [`interpret_mode_at`], a cursor placed right after a closed string,
+/// raw block or block comment is considered to be outside of it.
+pub(crate) fn interpret_mode_at_cursor(leaf: Option<&LinkedNode>, cursor: usize) -> InterpretMode {
+    let mut node = leaf.cloned();
+
+    if let Some(leaf) = leaf {
+        let closed = match leaf.kind() {
+            SyntaxKind::Str => leaf.len() >= 2 && leaf.text().ends_with('"'),
+            SyntaxKind::BlockComment => leaf.len() >= 4 && leaf.text().ends_with("*/"),
+            SyntaxKind::RawDelim => leaf.index() > 0,
+            _ => false,
+        };
+        if closed && cursor >= leaf.range().end {
+            let scope = match leaf.kind() {
+                SyntaxKind::RawDelim => leaf.parent(),
+                _ => Some(leaf),
+            };
+            node = scope.and_then(|scope| scope.parent().cloned());
+        }
+    }
+
+    while let Some(n) = node {
+        // The text in a raw block is not markup.
+        if n.parent_kind() != Some(SyntaxKind::Raw) {
+            if let Some(mode) = interpret_mode_at(n.kind()) {
+                return mode;
+            }
+        }
+        node = n.parent().cloned();
+    }
+
+    InterpretMode::Markup
+}
+
 #[derive(Debug, Clone)]
 pub enum DerefTarget<'a> {
     Label(LinkedNode<'a>),
@@ -646,6 +682,24 @@ mod tests {
         })
     }
 
+    fn mode_at(source: &str, cursor: usize) -> InterpretMode {
+        let source = Source::detached(source.to_owned());
+        let root = LinkedNode::new(source.root());
+        interpret_mode_at_cursor(root.leaf_at(cursor).as_ref(), cursor)
+    }
+
+    #[test]
+    fn test_interpret_mode_at_cursor() {
+        assert_eq!(mode_at(r#"#let x = "ab""#, 11), InterpretMode::String);
+        assert_eq!(mode_at(r#"#let x = "ab""#, 13), InterpretMode::Code);
+        assert_eq!(mode_at("// abc", 4), InterpretMode::Comment);
+        assert_eq!(mode_at("/* a */ b", 3), InterpretMode::Comment);
+        assert_eq!(mode_at("/* a */ b", 7), InterpretMode::Markup);
+        assert_eq!(mode_at("`ab`", 2), InterpretMode::Raw);
+        assert_eq!(mode_at("`ab`", 4), InterpretMode::Markup);
+        assert_eq!(mode_at("$x$", 2), InterpretMode::Math);
+    }
+
     #[test]
     fn test_get_deref_target() {
         assert_snapshot!(map_deref(r#"#let x = 1  
@@ -15,6 +15,10 @@ pub(crate) mod module;
 pub use module::*;
 pub(crate) mod comment;
 pub use comment::*;
+pub(crate) mod redefinition;
+pub use redefinition::*;
+pub(crate) mod ignores;
+pub use ignores::*;
 
 use core::fmt;
 use std::ops::Range;
@@ -3,6 +3,7 @@ use std::sync::Once;
 use once_cell::sync::Lazy;
 use regex::RegexSet;
 
+use super::IgnoreStack;
 use crate::prelude::*;
 
 /// The dependency information of a module (file).
@@ -71,15 +72,19 @@ fn is_hidden(entry: &walkdir::DirEntry) -> bool {
         .unwrap_or(false)
 }
 
-/// Scan the files in the workspace and return the file ids.
+/// Scan the files in the workspace and return the file ids. The paths ignored
+/// by the ignore files such as `.gitignore` are skipped if `respect_ignores` is
+/// set.
 ///
 /// Note: this function will touch the physical file system.
 pub(crate) fn scan_workspace_files<T>(
     root: &Path,
     ext: &RegexSet,
+    respect_ignores: bool,
     f: impl Fn(&Path) -> T,
 ) -> Vec<T> {
     let mut res = vec![];
+    let mut ignores = respect_ignores.then(IgnoreStack::default);
     let mut it = walkdir::WalkDir::new(root).follow_links(false).into_iter();
     loop {
         let de = match it.next() {
@@ -117,6 +122,15 @@ pub(crate) fn scan_workspace_files<T>(
             continue;
         }
 
+        if let Some(ignores) = &mut ignores {
+            if ignores.visit(de.path(), de.file_type().is_dir()) {
+                if de.file_type().is_dir() {
+                    it.skip_current_dir();
+                }
+                continue;
+            }
+        }
+
         if !de.file_type().is_file() {
             continue;
         }
@@ -0,0 +1,185 @@
+use std::collections::{HashMap, HashSet};
+
+use ecow::EcoString;
+use typst::syntax::{
+    ast::{self, AstNode},
+    Source, Span, SyntaxKind, SyntaxNode,
+};
+
+/// A `let` binding rebinding a name already bound in the same scope.
+#[derive(Debug, Clone)]
+pub struct Redefinition {
+    /// The rebound name.
+    pub name: EcoString,
+    /// The span of the identifier rebinding the name.
+    pub span: Span,
+    /// The span of the identifier binding the name previously.
+    pub prev: Span,
+    /// Whether the previous binding is referenced before the name is rebound,
+    /// which is dead code otherwise.
+    pub prev_used: bool,
+}
+
+/// The names bound in the scopes, with the spans of their bindings and whether
+/// they are referenced.
+type Scopes = Vec<HashMap<EcoString, (Span, bool)>>;
+
+/// Finds the `let` bindings rebinding names already bound by `let` bindings in
+/// the same scope.
+///
+/// Shadowing in nested blocks and closures is not reported, and neither are
+/// the bindings whose initializers reference the name, such as `let x = x + 1`,
+/// which are usually intentional.
+///
+/// Any identifier with the name counts as a reference to the previous binding,
+/// such as a field or an argument name, so an unused binding may be missed but
+/// is never reported wrongly.
+pub fn find_redefinitions(source: &Source) -> Vec<Redefinition> {
+    let mut walker = Walker {
+        scopes: vec![HashMap::new()],
+        bindings: HashSet::new(),
+        redefinitions: vec![],
+    };
+    walker.walk(source.root());
+    walker.redefinitions
+}
+
+struct Walker {
+    scopes: Scopes,
+    /// The spans of the identifiers binding names, which are not references.
+    bindings: HashSet<Span>,
+    redefinitions: Vec<Redefinition>,
+}
+
+impl Walker {
+    fn walk(&mut self, node: &SyntaxNode) {
+        let binding = node.cast::<ast::LetBinding>();
+        if let Some(binding) = binding {
+            let idents = binding.kind().bindings();
+            self.bindings
+                .extend(idents.iter().map(|ident| ident.span()));
+        }
+
+        if matches!(node.kind(), SyntaxKind::Ident | SyntaxKind::MathIdent)
+            && !self.bindings.contains(&node.span())
+        {
+            let name = node.text();
+            let scope = self.scopes.iter_mut().rev();
+            if let Some((_, used)) = scope.find_map(|scope| scope.get_mut(name)) {
+                *used = true;
+            }
+        }
+
+        let is_scope = matches!(
+            node.kind(),
+            SyntaxKind::CodeBlock | SyntaxKind::ContentBlock | SyntaxKind::Closure
+        );
+        if is_scope {
+            self.scopes.push(HashMap::new());
+        }
+
+        // The initializer is evaluated before the names are bound.
+        for child in node.children() {
+            self.walk(child);
+        }
+
+        if let Some(binding) = binding {
+            let scope = self.scopes.last_mut().unwrap();
+            for ident in binding.kind().bindings() {
+                let name = ident.get().clone();
+                let intended = init_references(binding, &name);
+                if let Some((prev, prev_used)) = scope.get(&name).filter(|_| !intended) {
+                    self.redefinitions.push(Redefinition {
+                        name: name.clone(),
+                        span: ident.span(),
+                        prev: *prev,
+                        prev_used: *prev_used,
+                    });
+                }
+                scope.insert(name, (ident.span(), false));
+            }
+        }
+
+        if is_scope {
+            self.scopes.pop();
+        }
+    }
+}
+
+/// Checks whether the initializer of the binding references the name.
+fn init_references(binding: ast::LetBinding, name: &str) -> bool {
+    match binding.init() {
+        // The closure of `let f(x) = ..` contains the name `f` it binds, so
+        // only its body and the defaults of its parameters are checked.
+        Some(ast::Expr::Closure(closure))
+            if matches!(binding.kind(), ast::LetBindingKind::Closure(..)) =>
+        {
+            let mut defaults = closure.params().children().filter_map(|param| match param {
+                ast::Param::Named(named) => Some(named.expr()),
+                _ => None,
+            });
+            references(closure.body().to_untyped(), name)
+                || defaults.any(|default| references(default.to_untyped(), name))
+        }
+        Some(init) => references(init.to_untyped(), name),
+        None => false,
+    }
+}
+
+/// Checks whether the node references the name.
+fn references(node: &SyntaxNode, name: &str) -> bool {
+    if matches!(node.kind(), SyntaxKind::Ident | SyntaxKind::MathIdent) && node.text() == name {
+        return true;
+    }
+
+    node.children().any(|child| references(child, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redefined_names(text: &str) -> Vec<EcoString> {
+        let source = Source::detached(text);
+        let redefinitions = find_redefinitions(&source);
+        redefinitions.into_iter().map(|r| r.name).collect()
+    }
+
+    #[test]
+    fn test_redefinitions() {
+        assert_eq!(redefined_names("#let x = 1\n#let x = 2"), ["x"]);
+        assert_eq!(redefined_names("#let (x, y) = (1, 2)\n#let y = 3"), ["y"]);
+        assert_eq!(redefined_names("#let f(x) = x\n#let f(y) = y"), ["f"]);
+    }
+
+    #[test]
+    fn test_unused_before_redefinition() {
+        let unused = |text: &str| {
+            let source = Source::detached(text);
+            let redefinitions = find_redefinitions(&source);
+            redefinitions
+                .into_iter()
+                .map(|r| !r.prev_used)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(unused("#let x = 1\n#let x = 2"), [true]);
+        assert_eq!(unused("#let x = 1\n#x\n#let x = 2"), [false]);
+        assert_eq!(unused("#let x = 1\n#{ x }\n#let x = 2"), [false]);
+        assert_eq!(unused("#let x = 1\n#let y = x\n#let x = 2"), [false]);
+        assert_eq!(
+            unused("#let x = 1\n#let x = 2\n#x\n#let x = 3"),
+            [true, false]
+        );
+    }
+
+    #[test]
+    fn test_intended_shadowing() {
+        assert!(redefined_names("#let x = 1\n#let x = x + 1").is_empty());
+        assert!(redefined_names("#let x = 1\n#{ let x = 2 }").is_empty());
+        assert!(redefined_names("#let x = 1\n#[#let x = 2]").is_empty());
+        assert!(redefined_names("#let x = 1\n#let f(x) = { let x = x }").is_empty());
+        assert!(redefined_names("#let f(x) = x\n#let f(x) = f(x)").is_empty());
+        assert!(redefined_names("#let f(x) = x\n#let f(x, g: f) = g(x)").is_empty());
+    }
+}
@@ -0,0 +1,113 @@
+use std::ops::Range;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{prelude::*, SemanticRequest};
+
+/// A node of the syntax tree of a source file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyntaxTreeNode {
+    /// The kind of the node, such as `heading` or `identifier`.
+    pub kind: String,
+    /// The text of the node if it is a leaf.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// The message of the error if the node is an error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The number of the span of the node, which is unique in the file. It is
+    /// zero if the span is detached.
+    pub span: u64,
+    /// The range of the node in bytes.
+    pub byte_range: Range<usize>,
+    /// The range of the node in the position encoding of the client.
+    pub range: LspRange,
+    /// The children of the node.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<SyntaxTreeNode>,
+}
+
+impl SyntaxTreeNode {
+    /// Converts a node and its descendants.
+    fn new(node: &LinkedNode, source: &Source, encoding: PositionEncoding) -> Self {
+        let span = node.span();
+        let byte_range = node.range();
+        let error = node.kind().is_error().then(|| node.errors());
+        let error = error.and_then(|errors| Some(errors.first()?.message.to_string()));
+
+        Self {
+            kind: node.kind().name().to_owned(),
+            text: (node.get().children().len() == 0).then(|| node.text().to_string()),
+            error,
+            span: if span.is_detached() { 0 } else { span.number() },
+            range: typst_to_lsp::range(byte_range.clone(), source, encoding),
+            byte_range,
+            children: node
+                .children()
+                .map(|child| Self::new(&child, source, encoding))
+                .collect(),
+        }
+    }
+}
+
+/// A request to get the syntax tree of a source file, which lets external
+/// tools build on the parser of the server.
+///
+/// Every node, including the trivia such as spaces and comments, is returned
+/// along with its range in bytes and in the position encoding of the client.
+/// The text is returned for the leaves only, which concatenate to the source.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct SyntaxTreeRequest {
+    /// The path of the source file.
+    pub path: PathBuf,
+}
+
+impl SemanticRequest for SyntaxTreeRequest {
+    type Response = SyntaxTreeNode;
+
+    fn request(self, ctx: &mut AnalysisContext) -> Option<Self::Response> {
+        let source = ctx.source_by_path(&self.path).ok()?;
+        let encoding = ctx.position_encoding();
+        Some(SyntaxTreeNode::new(
+            &LinkedNode::new(source.root()),
+            &source,
+            encoding,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaves(node: &SyntaxTreeNode, text: &mut String) {
+        text.push_str(node.text.as_deref().unwrap_or_default());
+        for child in &node.children {
+            leaves(child, text);
+        }
+    }
+
+    #[test]
+    fn test_syntax_tree() {
+        let source = Source::detached("= Title\n#let x = (1");
+        let root = LinkedNode::new(source.root());
+        let tree = SyntaxTreeNode::new(&root, &source, PositionEncoding::Utf16);
+
+        assert_eq!(tree.kind, "markup");
+        assert_eq!(tree.byte_range, 0..source.text().len());
+        assert_eq!(tree.children[0].kind, "heading");
+        assert_eq!(tree.children[0].range.end, LspPosition::new(0, 7));
+
+        let mut text = String::new();
+        leaves(&tree, &mut text);
+        assert_eq!(text, source.text());
+
+        fn has_error(node: &SyntaxTreeNode) -> bool {
+            node.error.is_some() || node.children.iter().any(has_error)
+        }
+        assert!(has_error(&tree));
+    }
+}
@@ -31,6 +31,7 @@ pub use typst_ts_compiler::TypstSystemWorld;
 
 use crate::{
     analysis::{Analysis, AnalysisResources},
+    normalize_path,
     prelude::AnalysisContext,
     typst_to_lsp, LspPosition, PositionEncoding,
 };
@@ -46,11 +47,13 @@ impl<'a> AnalysisResources for WrapWorld<'a> {
         self.0.registry.resolve(spec)
     }
 
-    fn iter_dependencies<'b>(
-        &'b self,
-        f: &mut dyn FnMut(&'b reflexo::ImmutPath, FileResult<&typst_ts_compiler::Time>),
+    fn iter_dependencies(
+        &self,
+        f: &mut dyn FnMut(&reflexo::ImmutPath, FileResult<&typst_ts_compiler::Time>),
     ) {
-        self.0.iter_dependencies(f)
+        self.0.iter_dependencies(&mut |path, time| {
+            f(&normalize_path(path).into(), time);
+        })
     }
 }
 
@@ -65,33 +68,43 @@ pub fn snapshot_testing(name: &str, f: &impl Fn(&mut AnalysisContext, PathBuf))
             #[cfg(windows)]
             let contents = contents.replace("\r\n", "\n");
 
-            run_with_sources(&contents, |w: &mut TypstSystemWorld, p| {
-                let root = w.workspace_root().unwrap();
-                let paths = w
-                    .shadow_paths()
-                    .into_iter()
-                    .map(|p| {
-                        TypstFileId::new(None, VirtualPath::new(p.strip_prefix(&root).unwrap()))
-                    })
-                    .collect::<Vec<_>>();
-                let w = WrapWorld(w);
-                let mut ctx = AnalysisContext::new(
-                    &w,
-                    Analysis {
-                        root,
-                        position_encoding: PositionEncoding::Utf16,
-                        enable_periscope: false,
-                        caches: Default::default(),
-                    },
-                );
-                ctx.test_completion_files(Vec::new);
-                ctx.test_files(|| paths);
-                f(&mut ctx, p);
-            });
+            run_with_ctx(&contents, f);
         });
     });
 }
 
+pub fn run_with_ctx<T>(source: &str, f: impl FnOnce(&mut AnalysisContext, PathBuf) -> T) -> T {
+    run_with_sources(source, |w: &mut TypstSystemWorld, p| {
+        let root = w.workspace_root().unwrap();
+        let paths = w
+            .shadow_paths()
+            .into_iter()
+            .map(|p| TypstFileId::new(None, VirtualPath::new(p.strip_prefix(&root).unwrap())))
+            .collect::<Vec<_>>();
+        let w = WrapWorld(w);
+        let mut ctx = AnalysisContext::new(
+            &w,
+            Analysis {
+                root,
+                position_encoding: PositionEncoding::Utf16,
+                enable_periscope: false,
+                max_diagnostics_per_file: None,
+                lint_redefinitions: false,
+                scan_ignored_files: false,
+                element_validators: vec![],
+                suppressed_diagnostics: Default::default(),
+                out_of_root_diagnostics: Default::default(),
+                diagnostic_verbosity: Default::default(),
+                analysis_threads: 0,
+                caches: Default::default(),
+            },
+        );
+        ctx.test_completion_files(Vec::new);
+        ctx.test_files(|| paths);
+        f(&mut ctx, p)
+    })
+}
+
 pub fn get_test_properties(s: &str) -> HashMap<&'_ str, &'_ str> {
     let mut props = HashMap::new();
     for line in s.lines() {
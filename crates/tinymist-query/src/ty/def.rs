@@ -873,6 +873,20 @@ pub struct TypeScheme {
 }
 
 impl TypeScheme {
+    /// Get the estimated memory usage of the type scheme.
+    pub fn estimated_memory(&self) -> usize {
+        std::mem::size_of::<Self>()
+            + self.vars.capacity()
+                * (std::mem::size_of::<DefId>() + std::mem::size_of::<TypeVarBounds>() + 32)
+            + self.mapping.capacity()
+                * (std::mem::size_of::<Span>() + std::mem::size_of::<Vec<Ty>>() + 32)
+            + self
+                .mapping
+                .values()
+                .map(|e| e.capacity() * std::mem::size_of::<Ty>())
+                .sum::<usize>()
+    }
+
     /// Get the type of a definition
     pub fn type_of_def(&self, def: DefId) -> Option<Ty> {
         Some(self.simplify(self.vars.get(&def).map(|e| e.as_type())?, false))
@@ -1,6 +1,7 @@
 use std::cmp::Reverse;
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 use std::ops::Range;
+use std::sync::Arc;
 
 use ecow::{eco_format, EcoString};
 use if_chain::if_chain;
@@ -19,7 +20,7 @@ use unscanny::Scanner;
 use super::{plain_docs_sentence, summarize_font_family};
 use crate::adt::interner::Interned;
 use crate::analysis::{analyze_expr, analyze_import, analyze_labels, DynLabel, Ty};
-use crate::AnalysisContext;
+use crate::{AnalysisContext, CompletionDocs};
 
 mod ext;
 pub use ext::complete_path;
@@ -73,6 +74,9 @@ pub struct Completion {
     pub apply: Option<EcoString>,
     /// An optional short description, at most one sentence.
     pub detail: Option<EcoString>,
+    /// The optional full documentation in markdown, which is only computed
+    /// when the completion is resolved.
+    pub docs: Option<EcoString>,
     /// An optional command to run when the completion is selected.
     pub command: Option<&'static str>,
 }
@@ -366,7 +370,10 @@ fn complete_field_accesses(ctx: &mut CompletionContext) -> bool {
         if prev.is::<ast::Expr>();
         if prev.parent_kind() != Some(SyntaxKind::Markup) ||
            prev.prev_sibling_kind() == Some(SyntaxKind::Hash);
-        if let Some((value, styles)) = analyze_expr(ctx.world(), &prev).into_iter().next();
+        if let Some((value, styles)) = analyze_expr(ctx.world(), &prev)
+            .into_iter()
+            .next()
+            .or_else(|| Some((resolve_module_by_syntax(ctx.ctx, &prev)?, None)));
         then {
             ctx.from = ctx.cursor;
             field_access_completions(ctx, &value, &styles);
@@ -381,7 +388,10 @@ fn complete_field_accesses(ctx: &mut CompletionContext) -> bool {
         if prev.kind() == SyntaxKind::Dot;
         if let Some(prev_prev) = prev.prev_sibling();
         if prev_prev.is::<ast::Expr>();
-        if let Some((value, styles)) = analyze_expr(ctx.world(), &prev_prev).into_iter().next();
+        if let Some((value, styles)) = analyze_expr(ctx.world(), &prev_prev)
+            .into_iter()
+            .next()
+            .or_else(|| Some((resolve_module_by_syntax(ctx.ctx, &prev_prev)?, None)));
         then {
             ctx.from = ctx.leaf.offset();
             field_access_completions(ctx, &value, &styles);
@@ -839,6 +849,24 @@ fn code_completions(ctx: &mut CompletionContext, hash: bool) {
         "Provides contextual data.",
     );
 
+    ctx.snippet_completion(
+        "figure with image",
+        "figure(\n\timage(\"${path}\"),\n\tcaption: [${caption}],\n)",
+        "Inserts a figure containing an image.",
+    );
+
+    ctx.snippet_completion(
+        "table with cells",
+        "table(\n\tcolumns: ${2},\n\t[${a}], [${b}],\n)",
+        "Inserts a table of cells.",
+    );
+
+    ctx.snippet_completion(
+        "grid with cells",
+        "grid(\n\tcolumns: ${2},\n\tgutter: ${1em},\n\t[${a}], [${b}],\n)",
+        "Inserts a grid of cells.",
+    );
+
     ctx.snippet_completion(
         "let binding",
         "let ${name} = ${value}",
@@ -948,6 +976,8 @@ fn code_completions(ctx: &mut CompletionContext, hash: bool) {
             "Creates an unnamed function.",
         );
     }
+
+    ctx.user_snippet_completions();
 }
 
 /// Context for autocompletion.
@@ -968,6 +998,8 @@ pub struct CompletionContext<'a, 'w> {
     pub seen_casts: HashSet<u128>,
     pub seen_types: HashSet<Ty>,
     pub seen_fields: HashSet<Interned<str>>,
+    pub user_snippets: Arc<BTreeMap<String, String>>,
+    pub docs: CompletionDocs,
 }
 
 impl<'a, 'w> CompletionContext<'a, 'w> {
@@ -999,6 +1031,8 @@ impl<'a, 'w> CompletionContext<'a, 'w> {
             seen_casts: HashSet::new(),
             seen_types: HashSet::new(),
             seen_fields: HashSet::new(),
+            user_snippets: Default::default(),
+            docs: CompletionDocs::default(),
         })
     }
 
@@ -1176,15 +1210,28 @@ impl<'a, 'w> CompletionContext<'a, 'w> {
         let at = label.as_deref().is_some_and(|field| !is_ident(field));
         let label = label.unwrap_or_else(|| value.repr());
 
-        let detail = docs.map(Into::into).or_else(|| match value {
-            Value::Symbol(c) => Some(symbol_detail(c.get())),
-            Value::Func(func) => func.docs().map(plain_docs_sentence),
-            Value::Type(ty) => Some(plain_docs_sentence(ty.docs())),
-            v => {
-                let repr = v.repr();
-                (repr.as_str() != label).then_some(repr)
+        let detail = if self.docs.wants(&label) {
+            docs.map(Into::into).or_else(|| match value {
+                Value::Symbol(c) => Some(symbol_detail(c.get())),
+                Value::Func(func) => func.docs().map(plain_docs_sentence),
+                Value::Type(ty) => Some(plain_docs_sentence(ty.docs())),
+                v => {
+                    let repr = v.repr();
+                    (repr.as_str() != label).then_some(repr)
+                }
+            })
+        } else {
+            None
+        };
+        let full_docs = if self.docs.resolves(&label) {
+            match value {
+                Value::Func(func) => func.docs().map(Into::into),
+                Value::Type(ty) => Some(ty.docs().into()),
+                _ => None,
             }
-        });
+        } else {
+            None
+        };
 
         let mut apply = None;
         let mut command = None;
@@ -1213,6 +1260,7 @@ impl<'a, 'w> CompletionContext<'a, 'w> {
             label,
             apply,
             detail,
+            docs: full_docs,
             label_detail,
             command,
             ..Completion::default()
@@ -1,7 +1,7 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use ecow::{eco_format, EcoString};
-use lsp_types::{CompletionItem, CompletionTextEdit, InsertTextFormat, TextEdit};
+use lsp_types::{Command, CompletionItem, CompletionTextEdit, InsertTextFormat, TextEdit};
 use reflexo::path::{unix_slash, PathClean};
 use typst::foundations::{AutoValue, Func, Label, NoneValue, Repr, Type, Value};
 use typst::layout::{Dir, Length};
@@ -12,9 +12,10 @@ use typst::visualize::Color;
 use super::{Completion, CompletionContext, CompletionKind};
 use crate::adt::interner::Interned;
 use crate::analysis::{
-    analyze_dyn_signature, analyze_import, resolve_call_target, BuiltinTy, PathPreference, Ty,
+    analyze_dyn_signature, analyze_import, find_source_definition, resolve_call_target, BuiltinTy,
+    PathPreference, Ty,
 };
-use crate::syntax::{param_index_at_leaf, CheckTarget};
+use crate::syntax::{param_index_at_leaf, CheckTarget, DerefTarget, LexicalKind, LexicalModKind};
 use crate::upstream::complete::complete_code;
 use crate::upstream::plain_docs_sentence;
 
@@ -33,6 +34,23 @@ impl<'a, 'w> CompletionContext<'a, 'w> {
         self.scope_completions_(parens, |v| v.map_or(false, &filter));
     }
 
+    /// Add completions for the snippets configured by the user.
+    pub fn user_snippet_completions(&mut self) {
+        let snippets = self.user_snippets.clone();
+        for (label, snippet) in snippets.iter() {
+            self.completions.push(Completion {
+                kind: CompletionKind::Syntax,
+                label: label.as_str().into(),
+                apply: Some(snippet.as_str().into()),
+                detail: Some("User snippet".into()),
+                command: snippet
+                    .contains("${")
+                    .then_some("editor.action.triggerSuggest"),
+                ..Completion::default()
+            });
+        }
+    }
+
     fn seen_field(&mut self, field: Interned<str>) -> bool {
         !self.seen_fields.insert(field)
     }
@@ -542,6 +560,44 @@ fn sort_and_explicit_code_completion(ctx: &mut CompletionContext) {
     log::debug!("sort_and_explicit_code_completion: {:?}", ctx.completions);
 }
 
+/// Resolves the module accessed by an expression, e.g. `m` in `m.`, by
+/// analyzing the import that introduces it.
+///
+/// This is used when the expression has no traced value, e.g. the document has
+/// not been compiled successfully since the module was imported.
+pub(crate) fn resolve_module_by_syntax(
+    ctx: &mut AnalysisContext,
+    node: &LinkedNode,
+) -> Option<Value> {
+    if !matches!(node.kind(), SyntaxKind::Ident) {
+        return None;
+    }
+
+    let source = ctx.source_by_id(node.span().id()?).ok()?;
+    let def = find_source_definition(ctx, source, None, DerefTarget::VarAccess(node.clone()))?;
+    if !matches!(
+        def.kind,
+        LexicalKind::Mod(LexicalModKind::ModuleAlias | LexicalModKind::PathVar)
+    ) {
+        return None;
+    }
+
+    let (def_fid, def_range) = def.def_at?;
+    let def_source = ctx.source_by_id(def_fid).ok()?;
+    let root = LinkedNode::new(def_source.root());
+    let mut import_node = root.leaf_at(def_range.start + 1)?;
+    while import_node.kind() != SyntaxKind::ModuleImport {
+        import_node = import_node.parent()?.clone();
+    }
+
+    let import = import_node.cast::<ast::ModuleImport>()?;
+    let import_source = import_node.find(import.source().span())?;
+    match analyze_import(ctx.world(), &import_source)? {
+        value @ Value::Module(..) => Some(value),
+        _ => None,
+    }
+}
+
 pub fn value_to_completion_kind(value: &Value) -> CompletionKind {
     match value {
         Value::Func(..) => CompletionKind::Func,
@@ -634,7 +690,14 @@ pub fn param_completions<'a>(
                 kind: CompletionKind::Field,
                 label: param.name.as_ref().into(),
                 apply: Some(eco_format!("{}: ${{}}", param.name)),
-                detail: Some(plain_docs_sentence(&param.docs)),
+                detail: ctx
+                    .docs
+                    .wants(&param.name)
+                    .then(|| plain_docs_sentence(&param.docs)),
+                docs: ctx
+                    .docs
+                    .resolves(&param.name)
+                    .then(|| param.docs.as_ref().into()),
                 label_detail: None,
                 command: Some("tinymist.triggerNamedCompletion"),
                 ..Completion::default()
@@ -1133,7 +1196,20 @@ pub fn complete_path(
 
     let dirs = ctx.analysis.root.clone();
     log::debug!("compl_dirs: {dirs:?}");
+    let path_label = |path: &Path| -> Option<EcoString> {
+        Some(if has_root {
+            // diff with root
+            let w = path.strip_prefix(&ctx.analysis.root).ok()?;
+            eco_format!("/{}", unix_slash(w))
+        } else {
+            let base = base.parent()?;
+            let w = pathdiff::diff_paths(path, base)?;
+            unix_slash(&w).into()
+        })
+    };
+
     // find directory or files in the path
+    let mut folders = BTreeSet::new();
     let mut folder_completions = vec![];
     let mut module_completions = vec![];
     // todo: test it correctly
@@ -1143,20 +1219,21 @@ pub fn complete_path(
         // diff with root
         let path = dirs.join(path);
 
+        // Collect the directories containing the file, so that the path can
+        // also be completed level by level.
+        for dir in path.ancestors().skip(1) {
+            if dir == dirs.as_ref() || !dir.starts_with(&dirs) {
+                break;
+            }
+            folders.insert(dir.to_owned());
+        }
+
         // Skip self smartly
         if path.clean() == base.clean() {
             continue;
         }
 
-        let label = if has_root {
-            // diff with root
-            let w = path.strip_prefix(&ctx.analysis.root).ok()?;
-            eco_format!("/{}", unix_slash(w))
-        } else {
-            let base = base.parent()?;
-            let w = pathdiff::diff_paths(&path, base)?;
-            unix_slash(&w).into()
-        };
+        let label = path_label(&path)?;
         log::debug!("compl_label: {label:?}");
 
         if path.is_dir() {
@@ -1166,6 +1243,15 @@ pub fn complete_path(
         }
     }
 
+    for dir in folders {
+        let label = path_label(&dir)?;
+        // Skip the directory of the current file
+        if label.is_empty() {
+            continue;
+        }
+        folder_completions.push((eco_format!("{label}/"), CompletionKind::Folder));
+    }
+
     let replace_range = ctx.to_lsp_range(rng, source);
 
     let path_priority_cmp = |a: &str, b: &str| {
@@ -1215,6 +1301,13 @@ pub fn complete_path(
                     sort_text: Some(sort_text),
                     filter_text: Some("".to_owned()),
                     insert_text_format: Some(InsertTextFormat::PLAIN_TEXT),
+                    // keep completing inside of the directory
+                    command: matches!(typst_completion.1, CompletionKind::Folder).then(|| {
+                        Command {
+                            command: "editor.action.triggerSuggest".to_owned(),
+                            ..Default::default()
+                        }
+                    }),
                     ..Default::default()
                 };
 
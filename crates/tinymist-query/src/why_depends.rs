@@ -0,0 +1,137 @@
+use std::{collections::VecDeque, hash::Hash};
+
+use crate::{prelude::*, SemanticRequest};
+
+/// The maximum number of import chains returned by a [`WhyDependsRequest`].
+const MAX_CHAINS: usize = 16;
+
+/// A request to explain why a file is a dependency of the main file, by the
+/// shortest chains of imports and includes from the main file to the file.
+///
+/// The chains are found in the import graph of the source files in the
+/// workspace. Each chain starts with the main file and ends with the target
+/// file, and at most 16 chains are returned.
+///
+/// This is not part of the LSP protocol.
+#[derive(Debug, Clone)]
+pub struct WhyDependsRequest {
+    /// The path of the file to explain.
+    pub path: PathBuf,
+}
+
+impl SemanticRequest for WhyDependsRequest {
+    type Response = Vec<Vec<PathBuf>>;
+
+    fn request(self, ctx: &mut AnalysisContext) -> Option<Self::Response> {
+        let main = ctx.world().main().id();
+        let target = ctx.source_by_path(&self.path).ok()?.id();
+
+        let deps = ctx.module_dependencies();
+        let chains = shortest_chains(main, target, MAX_CHAINS, |id| {
+            deps.get(&id)
+                .map(|dep| dep.dependencies.to_vec())
+                .unwrap_or_default()
+        });
+
+        let chains = chains.into_iter().map(|chain| {
+            let paths = chain.into_iter().map(|id| ctx.path_for_id(id).ok());
+            paths.collect::<Option<Vec<_>>>()
+        });
+        Some(chains.flatten().collect())
+    }
+}
+
+/// Finds the shortest chains from a node to another node in a graph, up to a
+/// limit. Both ends are included in the chains.
+fn shortest_chains<T: Copy + Eq + Hash>(
+    from: T,
+    to: T,
+    limit: usize,
+    edges: impl Fn(T) -> Vec<T>,
+) -> Vec<Vec<T>> {
+    // Finds the predecessors of the nodes on the shortest chains by a breadth
+    // first search, stopping at the level of the target.
+    let mut depths = HashMap::from([(from, 0usize)]);
+    let mut preds = HashMap::<T, Vec<T>>::new();
+    let mut queue = VecDeque::from([from]);
+    while let Some(node) = queue.pop_front() {
+        let depth = depths[&node];
+        if depths.get(&to).is_some_and(|&target| depth >= target) {
+            break;
+        }
+
+        for next in edges(node) {
+            match depths.get(&next) {
+                Some(&d) if d == depth + 1 => preds.entry(next).or_default().push(node),
+                Some(_) => {}
+                None => {
+                    depths.insert(next, depth + 1);
+                    preds.entry(next).or_default().push(node);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    if !depths.contains_key(&to) {
+        return vec![];
+    }
+
+    // Walks back from the target along the predecessors.
+    fn walk<T: Copy + Eq + Hash>(
+        node: T,
+        preds: &HashMap<T, Vec<T>>,
+        suffix: &mut Vec<T>,
+        chains: &mut Vec<Vec<T>>,
+        limit: usize,
+    ) {
+        if chains.len() >= limit {
+            return;
+        }
+
+        suffix.push(node);
+        match preds.get(&node) {
+            Some(preds_of_node) => {
+                for &pred in preds_of_node {
+                    walk(pred, preds, suffix, chains, limit);
+                }
+            }
+            None => chains.push(suffix.iter().rev().copied().collect()),
+        }
+        suffix.pop();
+    }
+
+    let mut chains = vec![];
+    walk(to, &preds, &mut vec![], &mut chains, limit);
+    chains
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chains(edges: &[(u32, u32)], to: u32, limit: usize) -> Vec<Vec<u32>> {
+        let mut chains = shortest_chains(0, to, limit, |node| {
+            let next = edges.iter().filter(|(from, _)| *from == node);
+            next.map(|(_, to)| *to).collect()
+        });
+        chains.sort();
+        chains
+    }
+
+    #[test]
+    fn test_shortest_chains() {
+        let edges = [(0, 1), (0, 2), (1, 3), (2, 3), (3, 4), (0, 4), (1, 5)];
+        assert_eq!(chains(&edges, 3, 16), [vec![0, 1, 3], vec![0, 2, 3]]);
+        assert_eq!(chains(&edges, 4, 16), [vec![0, 4]]);
+        assert_eq!(chains(&edges, 3, 1).len(), 1);
+        assert_eq!(chains(&edges, 0, 16), [vec![0]]);
+        assert!(chains(&edges, 6, 16).is_empty());
+    }
+
+    #[test]
+    fn test_shortest_chains_with_cycle() {
+        let edges = [(0, 1), (1, 0), (1, 2), (2, 1)];
+        assert_eq!(chains(&edges, 2, 16), [vec![0, 1, 2]]);
+    }
+}
@@ -6,6 +6,11 @@
 //! This crate provides rendering features for tinymist server.
 
 use core::fmt;
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    sync::{Arc, Mutex},
+};
 
 use base64::Engine;
 use tinymist_query::{AnalysisContext, FramePosition, VersionedDocument};
@@ -37,6 +42,11 @@ pub struct PeriscopeArgs {
     pub scale: f32,
     /// Whether to invert the color. (will become smarter in the future)
     pub invert_color: String,
+    /// The maximum number of pixels of the displayed image, zero means
+    /// unlimited. The image is scaled down to fit into it, which is set by the
+    /// server rather than the user.
+    #[serde(skip)]
+    pub max_pixels: u64,
 }
 
 impl Default for PeriscopeArgs {
@@ -46,15 +56,46 @@ impl Default for PeriscopeArgs {
             y_below: 55.,
             scale: 1.5,
             invert_color: "never".to_owned(),
+            max_pixels: 0,
         }
     }
 }
 
+/// The periscope image with its metadata.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeriscopeResult {
+    /// The SVG image.
+    pub svg: String,
+    /// The SVG image encoded as a data URI.
+    pub data_uri: String,
+    /// The width of the displayed image, which is scaled.
+    pub width: f32,
+    /// The height of the displayed image, which is scaled.
+    pub height: f32,
+    /// The page the image is cropped from, starting from 1.
+    pub page: NonZeroUsize,
+    /// The region of the page the image represents in pt, in the form of `[x,
+    /// y, width, height]`.
+    pub bbox: [f32; 4],
+}
+
+/// The rendered periscope images of a document, keyed by the page and the
+/// vertical position.
+#[derive(Debug, Default)]
+struct PeriscopeCache {
+    /// The version of the document the images are rendered from.
+    version: usize,
+    images: HashMap<(NonZeroUsize, u64), (String, f32, f32)>,
+}
+
 /// The renderer in periscope mode.
 #[derive(Debug, Clone)]
 pub struct PeriscopeRenderer {
     /// The arguments for periscope renderer.
     p: PeriscopeArgs,
+    /// The images rendered from the latest document.
+    cache: Arc<Mutex<PeriscopeCache>>,
 }
 
 impl Default for PeriscopeRenderer {
@@ -66,7 +107,23 @@ impl Default for PeriscopeRenderer {
 impl PeriscopeRenderer {
     /// Create a new periscope renderer.
     pub fn new(args: PeriscopeArgs) -> Self {
-        Self { p: args }
+        Self {
+            p: args,
+            cache: Default::default(),
+        }
+    }
+
+    /// Get estimated memory usage of the cached images.
+    pub fn estimated_memory(&self) -> usize {
+        let cache = self.cache.lock().unwrap();
+        cache.images.values().map(|(svg, ..)| svg.len()).sum()
+    }
+
+    /// Evicts the cached images, returning the number of bytes freed.
+    pub fn clear_cache(&self) -> usize {
+        let freed = self.estimated_memory();
+        self.cache.lock().unwrap().images = HashMap::new();
+        freed
     }
 
     /// Render the periscope image for the given document into markdown format.
@@ -76,26 +133,79 @@ impl PeriscopeRenderer {
         doc: VersionedDocument,
         pos: FramePosition,
     ) -> Option<String> {
-        let (svg_payload, w, h) = self.render(ctx, doc, pos)?;
-
-        let sw = w * self.p.scale;
-        let sh = h * self.p.scale;
-
-        log::debug!("periscope image: {sw}x{sh}, {svg_payload}");
+        let res = self.render_result(ctx, doc, pos)?;
 
         // encode as markdown dataurl image
-        let base64 = base64::engine::general_purpose::STANDARD.encode(svg_payload);
+        let PeriscopeResult {
+            data_uri,
+            width: sw,
+            height: sh,
+            ..
+        } = res;
         Some(enlarge_image(format_args!(
-            "![Periscope Mode](data:image/svg+xml;base64,{base64}|width={sw}|height={sh})"
+            "![Periscope Mode]({data_uri}|width={sw}|height={sh})"
         )))
     }
 
-    /// Render the periscope image for the given document.
+    /// Render the periscope image for the given document with its metadata.
+    pub fn render_result(
+        &self,
+        ctx: &mut AnalysisContext,
+        doc: VersionedDocument,
+        pos: FramePosition,
+    ) -> Option<PeriscopeResult> {
+        let (svg, w, h) = self.render(ctx, doc, pos)?;
+
+        let scale = self.scale(w, h);
+        let sw = w * scale;
+        let sh = h * scale;
+
+        log::debug!("periscope image: {sw}x{sh}, {svg}");
+
+        let base64 = base64::engine::general_purpose::STANDARD.encode(&svg);
+        let y_lo = pos.point.y.to_pt() as f32 - self.p.y_above;
+        Some(PeriscopeResult {
+            svg,
+            data_uri: format!("data:image/svg+xml;base64,{base64}"),
+            width: sw,
+            height: sh,
+            page: pos.page,
+            bbox: [0., y_lo, w, h],
+        })
+    }
+
+    /// Render the periscope image for the given document, reusing the image
+    /// rendered at the same position of the same document.
     pub fn render(
         &self,
         _ctx: &mut AnalysisContext,
         doc: VersionedDocument,
         pos: FramePosition,
+    ) -> Option<(String, f32, f32)> {
+        let key = (pos.page, pos.point.y.to_pt().to_bits());
+        {
+            let mut cache = self.cache.lock().unwrap();
+            if cache.version != doc.version {
+                cache.version = doc.version;
+                cache.images.clear();
+            }
+            if let Some(image) = cache.images.get(&key) {
+                return Some(image.clone());
+            }
+        }
+
+        let image = self.render_uncached(doc.clone(), pos)?;
+        let mut cache = self.cache.lock().unwrap();
+        if cache.version == doc.version {
+            cache.images.insert(key, image.clone());
+        }
+        Some(image)
+    }
+
+    fn render_uncached(
+        &self,
+        doc: VersionedDocument,
+        pos: FramePosition,
     ) -> Option<(String, f32, f32)> {
         // todo: svg viewer compablity
         type UsingExporter = SvgExporter<PeriscopeExportFeature>;
@@ -118,7 +228,7 @@ impl PeriscopeRenderer {
             page0.size.x.0,
             y_lo,
             y_hi,
-            self.p.scale,
+            self.scale(width, height),
             self.p.invert_color == "always",
         ));
 
@@ -126,6 +236,20 @@ impl PeriscopeRenderer {
     }
 }
 
+impl PeriscopeRenderer {
+    /// Gets the scale of an image of the given size, which is scaled down if
+    /// the displayed image exceeds the maximum number of pixels.
+    fn scale(&self, w: f32, h: f32) -> f32 {
+        let max_pixels = self.p.max_pixels as f32;
+        let pixels = w * h * self.p.scale * self.p.scale;
+        if max_pixels > 0. && pixels > max_pixels {
+            self.p.scale * (max_pixels / pixels).sqrt()
+        } else {
+            self.p.scale
+        }
+    }
+}
+
 fn enlarge_image(md: fmt::Arguments) -> String {
     format!("```\n```\n{md}\n```\n```")
 }
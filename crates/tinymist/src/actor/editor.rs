@@ -1,18 +1,33 @@
 //! The actor that send notifications to the client.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Duration};
 
 use log::info;
-use lsp_types::{Diagnostic, Url};
+use lsp_types::{
+    notification::ShowMessage, Diagnostic, DiagnosticSeverity, MessageType, ShowMessageParams, Url,
+};
 use tinymist_query::{DiagnosticsMap, LspDiagnostic};
-use tokio::sync::mpsc;
+use tokio::{sync::mpsc, time::Instant};
 
+use super::export::ExportEvent;
 use crate::{tools::word_count::WordsCount, LspHost, TypstLanguageServer};
 
+/// The delay before a compiling status is sent, which is dropped if the result
+/// of the compilation arrives in time, so that the status doesn't flicker on
+/// fast compilations.
+const COMPILING_STATUS_DELAY: Duration = Duration::from_millis(50);
+
 pub enum EditorRequest {
-    Diag(String, Option<DiagnosticsMap>),
+    /// The diagnostics of a group, with the version of the document they
+    /// correspond to.
+    Diag(String, Option<DiagnosticsMap>, usize),
     Status(String, TinymistCompileStatusEnum),
     WordCount(String, WordsCount),
+    Export(String, ExportEvent),
+    /// The entry of a group is changed, from the former to the latter.
+    EntryChanged(String, TinymistEntry, TinymistEntry),
+    /// The command run after an export of a group failed, with the message.
+    PostExportFailed(String, String),
 }
 
 pub struct EditorActor {
@@ -20,9 +35,28 @@ pub struct EditorActor {
     editor_rx: mpsc::UnboundedReceiver<EditorRequest>,
 
     diagnostics: HashMap<Url, HashMap<String, Vec<LspDiagnostic>>>,
+    /// The diagnostics shown by the client for each file, which are not
+    /// published again if unchanged.
+    published: HashMap<Url, Vec<LspDiagnostic>>,
     affect_map: HashMap<String, Vec<Url>>,
     published_primary: bool,
     notify_compile_status: bool,
+    /// The compile status last sent to the client.
+    compile_status: TinymistCompileStatusEnum,
+    words_count: Option<WordsCount>,
+    /// The groups whose compiling status is waiting to be sent, with the time
+    /// to send it.
+    pending_status: HashMap<String, Instant>,
+
+    /// The minimal interval between two diagnostics publications.
+    diag_interval: Option<Duration>,
+    /// The diagnostics that are waiting for the next publication.
+    pending_diags: HashMap<String, (Option<DiagnosticsMap>, usize)>,
+    /// The time of the last diagnostics publication.
+    last_publish: Option<Instant>,
+    /// The versions of the documents whose diagnostics are published last
+    /// time, by groups.
+    doc_versions: HashMap<String, usize>,
 }
 
 impl EditorActor {
@@ -30,72 +64,214 @@ impl EditorActor {
         host: LspHost<TypstLanguageServer>,
         editor_rx: mpsc::UnboundedReceiver<EditorRequest>,
         notify_compile_status: bool,
+        diag_max_rate: u32,
     ) -> Self {
         Self {
             host,
             editor_rx,
             diagnostics: HashMap::new(),
+            published: HashMap::new(),
             affect_map: HashMap::new(),
             published_primary: false,
             notify_compile_status,
+            compile_status: TinymistCompileStatusEnum::Compiling,
+            words_count: None,
+            pending_status: HashMap::new(),
+            diag_interval: (diag_max_rate > 0).then(|| Duration::from_secs(1) / diag_max_rate),
+            pending_diags: HashMap::new(),
+            last_publish: None,
+            doc_versions: HashMap::new(),
         }
     }
 
     pub async fn run(mut self) {
-        let mut compile_status = TinymistCompileStatusEnum::Compiling;
-        let mut words_count = None;
-        while let Some(req) = self.editor_rx.recv().await {
+        loop {
+            // Waits for either a new request or the deadline of pending diagnostics or
+            // statuses, so that the latest ones are always delivered after editing stops.
+            let status_deadline = self.pending_status.values().min().copied();
+            let deadline = self
+                .flush_deadline()
+                .into_iter()
+                .chain(status_deadline)
+                .min();
+            let req = match deadline {
+                Some(deadline) => tokio::select! {
+                    req = self.editor_rx.recv() => req,
+                    _ = tokio::time::sleep_until(deadline) => {
+                        if self.flush_deadline().is_some_and(|d| d <= Instant::now()) {
+                            self.flush_pending_diags().await;
+                        }
+                        self.flush_pending_status();
+                        continue;
+                    }
+                },
+                None => self.editor_rx.recv().await,
+            };
+            let Some(req) = req else {
+                break;
+            };
+
             match req {
-                EditorRequest::Diag(group, diagnostics) => {
+                EditorRequest::Diag(group, diagnostics, version) => {
                     info!(
-                        "received diagnostics from {group}: diag({:?})",
+                        "received diagnostics from {group}@{version}: diag({:?})",
                         diagnostics.as_ref().map(|e| e.len())
                     );
 
-                    let with_primary = self.affect_map.len() == 1
-                        && self.affect_map.contains_key("primary")
-                        && group == "primary";
-
-                    self.publish(group, diagnostics, with_primary).await;
-
-                    // Check with primary again after publish
-                    let again_with_primary =
-                        self.affect_map.len() == 1 && self.affect_map.contains_key("primary");
-
-                    if !with_primary && self.published_primary != again_with_primary {
-                        self.flush_primary_diagnostics(again_with_primary).await;
-                        self.published_primary = again_with_primary;
+                    // Coalesces the diagnostics of the same group, keeping the latest one.
+                    self.pending_diags.insert(group, (diagnostics, version));
+                    if self.flush_deadline().is_some_and(|d| d <= Instant::now()) {
+                        self.flush_pending_diags().await;
                     }
                 }
                 EditorRequest::Status(group, status) => {
                     log::debug!("received status request");
-                    if self.notify_compile_status && group == "primary" {
-                        compile_status = status;
-                        self.host.send_notification::<TinymistCompileStatus>(
-                            TinymistCompileStatus {
-                                status: compile_status.clone(),
-                                words_count: words_count.clone(),
-                            },
-                        );
+                    if !self.notify_compile_status || group != "primary" {
+                        continue;
+                    }
+
+                    // Delays the compiling status, and drops it if the result arrives in time.
+                    if matches!(status, TinymistCompileStatusEnum::Compiling) {
+                        let deadline = Instant::now() + COMPILING_STATUS_DELAY;
+                        self.pending_status.entry(group).or_insert(deadline);
+                    } else {
+                        self.pending_status.remove(&group);
+                        self.send_status(status);
                     }
                 }
+                EditorRequest::Export(group, event) => {
+                    log::debug!("received export event");
+                    self.host
+                        .send_notification::<TinymistExportDone>(TinymistExportDone {
+                            group,
+                            event,
+                        });
+                }
+                EditorRequest::EntryChanged(group, old, new) => {
+                    log::debug!("received entry change");
+                    self.host
+                        .send_notification::<TinymistEntryChanged>(TinymistEntryChanged {
+                            group,
+                            old,
+                            new,
+                        });
+                }
+                EditorRequest::PostExportFailed(group, message) => {
+                    log::debug!("received post-export failure of {group}");
+                    self.host
+                        .send_notification::<ShowMessage>(ShowMessageParams {
+                            typ: MessageType::WARNING,
+                            message,
+                        });
+                }
                 EditorRequest::WordCount(group, wc) => {
                     log::debug!("received word count request");
                     if self.notify_compile_status && group == "primary" {
-                        words_count = Some(wc);
-                        self.host.send_notification::<TinymistCompileStatus>(
-                            TinymistCompileStatus {
-                                status: compile_status.clone(),
-                                words_count: words_count.clone(),
-                            },
-                        );
+                        self.words_count = Some(wc);
+                        self.send_status(self.compile_status.clone());
                     }
                 }
             }
         }
+        self.flush_pending_diags().await;
         info!("compile cluster actor is stopped");
     }
 
+    /// Sends the compiling statuses whose delay is over.
+    fn flush_pending_status(&mut self) {
+        let now = Instant::now();
+        let len = self.pending_status.len();
+        self.pending_status.retain(|_, deadline| *deadline > now);
+        if self.pending_status.len() < len {
+            self.send_status(TinymistCompileStatusEnum::Compiling);
+        }
+    }
+
+    fn send_status(&mut self, status: TinymistCompileStatusEnum) {
+        self.compile_status = status;
+        self.host
+            .send_notification::<TinymistCompileStatus>(TinymistCompileStatus {
+                status: self.compile_status.clone(),
+                words_count: self.words_count.clone(),
+            });
+    }
+
+    /// Gets the time when the pending diagnostics should be published.
+    fn flush_deadline(&self) -> Option<Instant> {
+        if self.pending_diags.is_empty() {
+            return None;
+        }
+
+        match (self.diag_interval, self.last_publish) {
+            (Some(interval), Some(last)) => Some(last + interval),
+            _ => Some(Instant::now()),
+        }
+    }
+
+    async fn flush_pending_diags(&mut self) {
+        if self.pending_diags.is_empty() {
+            return;
+        }
+        self.last_publish = Some(Instant::now());
+
+        for (group, (diagnostics, version)) in std::mem::take(&mut self.pending_diags) {
+            self.doc_versions.insert(group.clone(), version);
+            self.on_diagnostics(group, diagnostics).await;
+        }
+
+        self.host
+            .send_notification::<TinymistDiagnosticsSummary>(self.summarize_diagnostics());
+    }
+
+    /// Counts the published diagnostics per file, from the same diagnostics
+    /// that are published to the client.
+    fn summarize_diagnostics(&self) -> TinymistDiagnosticsSummary {
+        let with_primary = self.affect_map.len() == 1 && self.affect_map.contains_key("primary");
+
+        let mut summary = TinymistDiagnosticsSummary {
+            versions: self.doc_versions.clone(),
+            ..Default::default()
+        };
+        for (url, path_diags) in &self.diagnostics {
+            let mut count = DiagnosticsCount::default();
+            let diags = path_diags.iter();
+            let diags =
+                diags.filter_map(|(g, diags)| (with_primary || g != "primary").then_some(diags));
+            for diag in diags.flatten() {
+                match diag.severity {
+                    Some(DiagnosticSeverity::ERROR) => count.errors += 1,
+                    Some(DiagnosticSeverity::WARNING) => count.warnings += 1,
+                    _ => {}
+                }
+            }
+
+            if count.errors > 0 || count.warnings > 0 {
+                summary.total.errors += count.errors;
+                summary.total.warnings += count.warnings;
+                summary.files.insert(url.clone(), count);
+            }
+        }
+
+        summary
+    }
+
+    async fn on_diagnostics(&mut self, group: String, diagnostics: Option<DiagnosticsMap>) {
+        let with_primary = self.affect_map.len() == 1
+            && self.affect_map.contains_key("primary")
+            && group == "primary";
+
+        self.publish(group, diagnostics, with_primary).await;
+
+        // Check with primary again after publish
+        let again_with_primary =
+            self.affect_map.len() == 1 && self.affect_map.contains_key("primary");
+
+        if !with_primary && self.published_primary != again_with_primary {
+            self.flush_primary_diagnostics(again_with_primary).await;
+            self.published_primary = again_with_primary;
+        }
+    }
+
     async fn flush_primary_diagnostics(&mut self, enable: bool) {
         let affected = self.affect_map.get("primary");
 
@@ -106,10 +282,37 @@ impl EditorActor {
             let diags = diags.filter_map(|(g, diags)| (g != "primary" || enable).then_some(diags));
             let to_publish = diags.flatten().cloned().collect();
 
-            self.host.publish_diagnostics(url.clone(), to_publish, None);
+            Self::publish_changed(&self.host, &mut self.published, url.clone(), to_publish);
         }
     }
 
+    /// Publishes the diagnostics of a file unless the client already shows the
+    /// same ones, e.g. when a file is saved without changes to the
+    /// diagnostics, so that the problems panel doesn't flash.
+    fn publish_changed(
+        host: &LspHost<TypstLanguageServer>,
+        published: &mut HashMap<Url, Vec<LspDiagnostic>>,
+        url: Url,
+        diagnostics: Vec<LspDiagnostic>,
+    ) {
+        let unchanged = match published.get(&url) {
+            Some(prev) => *prev == diagnostics,
+            None => diagnostics.is_empty(),
+        };
+        if unchanged {
+            return;
+        }
+
+        if diagnostics.is_empty() {
+            published.remove(&url);
+        } else {
+            published.insert(url.clone(), diagnostics.clone());
+        }
+        // The diagnostics are not of a version of the document given by the
+        // client, and the versions of the compilations are in the summary.
+        host.publish_diagnostics(url, diagnostics, None);
+    }
+
     pub async fn publish(
         &mut self,
         group: String,
@@ -170,7 +373,7 @@ impl EditorActor {
         };
 
         if group != "primary" || with_primary {
-            self.host.publish_diagnostics(url, to_publish, None)
+            Self::publish_changed(&self.host, &mut self.published, url, to_publish);
         }
     }
 }
@@ -195,3 +398,73 @@ impl lsp_types::notification::Notification for TinymistCompileStatus {
     type Params = Self;
     const METHOD: &'static str = "tinymist/compileStatus";
 }
+
+/// The numbers of errors and warnings.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticsCount {
+    pub errors: usize,
+    pub warnings: usize,
+}
+
+/// A summary of the published diagnostics, so that clients don't have to count
+/// the diagnostics by themselves.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TinymistDiagnosticsSummary {
+    /// The counts of the files that have any error or warning.
+    pub files: HashMap<Url, DiagnosticsCount>,
+    /// The total counts over all files.
+    pub total: DiagnosticsCount,
+    /// The versions of the documents the diagnostics are from, by diagnostics
+    /// groups, which are counted by the compilations of each group.
+    pub versions: HashMap<String, usize>,
+}
+
+impl lsp_types::notification::Notification for TinymistDiagnosticsSummary {
+    type Params = Self;
+    const METHOD: &'static str = "tinymist/diagnosticsSummary";
+}
+
+/// A notification sent when an export finishes, successfully or not.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TinymistExportDone {
+    /// The diagnostics group of the compiler, e.g. `primary`.
+    pub group: String,
+    #[serde(flatten)]
+    pub event: ExportEvent,
+}
+
+impl lsp_types::notification::Notification for TinymistExportDone {
+    type Params = Self;
+    const METHOD: &'static str = "tinymist/exportDone";
+}
+
+/// The entry of a compiler, which is inactive if the main file is absent.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TinymistEntry {
+    /// The root of the entry.
+    pub root: Option<Url>,
+    /// The main file of the entry.
+    pub main: Option<Url>,
+}
+
+/// A notification sent when the entry of a compiler changes, either by the
+/// client, by the configuration, or by detecting the main file.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TinymistEntryChanged {
+    /// The diagnostics group of the compiler, e.g. `primary`.
+    pub group: String,
+    /// The entry before the change.
+    pub old: TinymistEntry,
+    /// The entry after the change.
+    pub new: TinymistEntry,
+}
+
+impl lsp_types::notification::Notification for TinymistEntryChanged {
+    type Params = Self;
+    const METHOD: &'static str = "tinymist/entryChanged";
+}
@@ -1,48 +1,234 @@
 //! The actor that handles PDF export.
 
 use std::{
+    collections::HashMap,
+    io::Write,
+    num::NonZeroUsize,
     path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use anyhow::bail;
 use anyhow::Context;
 use log::{error, info};
 use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use tinymist_query::{ExportKind, PageSelection};
-use tokio::sync::{mpsc, oneshot, watch};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
 use typst::{foundations::Smart, layout::Abs, layout::Frame, visualize::Color};
 use typst_ts_core::{config::compiler::EntryState, path::PathClean, ImmutPath, TypstDocument};
 
-use crate::{tools::word_count, ExportMode};
+use crate::{
+    tools::{pdf_info::override_pdf_info, word_count},
+    world::WorkspaceBase,
+    ExportMode,
+};
 
-use super::editor::EditorRequest;
+use super::{editor::EditorRequest, typ_client::PageChanges};
 
-#[derive(Debug, Clone, Default)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
 pub struct ExportConfig {
     pub substitute_pattern: String,
     pub mode: ExportMode,
+    /// Whether to export PNG with a transparent page background instead of an
+    /// opaque white one.
+    pub png_transparent: bool,
+    /// The maximum number of pixels of an exported PNG, zero means unlimited.
+    pub png_max_pixels: u64,
+    /// Whether to export each page as a separate PDF.
+    pub pdf_split_pages: bool,
+    /// The unix permissions of the exported files, e.g. `0o640`. The
+    /// directories created for the files are also accessible by the users
+    /// who can read the files. It is ignored on other platforms.
+    pub output_mode: Option<u32>,
+    /// The creator of exported PDFs, which is typst by default. An empty
+    /// string removes the creator.
+    pub pdf_creator: Option<String>,
+    /// The producer of exported PDFs, which is absent by default. An empty
+    /// string removes the producer.
+    pub pdf_producer: Option<String>,
+    /// The command run after writing exported files, with the path of the
+    /// exported file appended to its arguments. It is None if no command is
+    /// set or running commands is disabled.
+    pub post_export_command: Option<Vec<String>>,
+    /// The thumbnail exported alongside the document, which is not exported
+    /// by default.
+    pub thumbnail: Option<ThumbnailConfig>,
+}
+
+impl ExportConfig {
+    /// Applies the fields changed from the old default config to the new one
+    /// to this config of an entry, keeping the other fields it overrides.
+    fn merge_default(&mut self, old: &ExportConfig, new: &ExportConfig) {
+        macro_rules! merge {
+            ($($field:ident),* $(,)?) => {
+                // Fails to compile if a field is missed.
+                let ExportConfig { $($field: _),* } = new;
+                $(
+                    if old.$field != new.$field {
+                        self.$field = new.$field.clone();
+                    }
+                )*
+            };
+        }
+
+        merge!(
+            substitute_pattern,
+            mode,
+            png_transparent,
+            png_max_pixels,
+            pdf_split_pages,
+            output_mode,
+            pdf_creator,
+            pdf_producer,
+            post_export_command,
+            thumbnail,
+        );
+    }
+}
+
+/// The config of a small PNG image of a page, exported next to the document
+/// for file browsers and dashboards.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ThumbnailConfig {
+    /// The maximum width and height of the thumbnail in pixels.
+    #[serde(default = "ThumbnailConfig::default_max_size")]
+    pub max_size: u32,
+    /// The page rendered into the thumbnail, starting from 1. The last page is
+    /// rendered if the document has fewer pages.
+    #[serde(default = "ThumbnailConfig::default_page")]
+    pub page: NonZeroUsize,
+}
+
+impl ThumbnailConfig {
+    fn default_max_size() -> u32 {
+        256
+    }
+
+    fn default_page() -> NonZeroUsize {
+        NonZeroUsize::MIN
+    }
+}
+
+/// The files written by an export.
+#[derive(Debug, Clone)]
+pub struct ExportOutput {
+    /// The path of the exported file. A document exported into multiple files,
+    /// such as a PDF split into pages, is located by its first file.
+    pub path: PathBuf,
+    /// The path of the thumbnail exported alongside, if any.
+    pub thumbnail: Option<PathBuf>,
+    /// Whether any file is written, which is false if all of them are
+    /// unchanged.
+    pub written: bool,
 }
 
 #[derive(Debug)]
 pub enum ExportRequest {
     OnTyped,
     OnSaved,
-    Oneshot(Option<ExportKind>, oneshot::Sender<Option<PathBuf>>),
+    /// Exports the document instantly. The boolean flag forces writing the
+    /// file even if its content is unchanged since the last export.
+    Oneshot(Option<ExportKind>, bool, oneshot::Sender<Option<PathBuf>>),
+    /// Exports the document instantly and sends back the names and the data of
+    /// the exported files, without writing to the disk. A kind may be exported
+    /// into multiple files, e.g. a PDF split into pages. A previous version of
+    /// the document is exported if it is given.
+    OneshotBytes(
+        ExportKind,
+        Option<Arc<TypstDocument>>,
+        oneshot::Sender<anyhow::Result<ExportedFiles>>,
+    ),
+    /// Changes the default config, whose changes also apply to the configs of
+    /// the entries.
     ChangeConfig(ExportConfig),
+    /// Changes the config of the active entry, or the default config if there
+    /// is no active entry.
+    ChangeEntryConfig(ExportConfig),
     ChangeExportPath(EntryState),
+    ChangeDependencies(Vec<ImmutPath>),
+}
+
+/// The names and the data of exported files.
+pub type ExportedFiles = Vec<(String, Vec<u8>)>;
+
+/// An event emitted when an export finishes, successfully or not.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportEvent {
+    /// The extension of the exported file, e.g. `pdf`.
+    pub kind: String,
+    /// The path of the exported file, which is None on failure.
+    pub path: Option<PathBuf>,
+    /// The path of the thumbnail exported alongside, if any.
+    pub thumbnail: Option<PathBuf>,
+    /// The error message on failure.
+    pub error: Option<String>,
+    /// The time spent on the export.
+    pub duration: Duration,
+}
+
+/// The state of exporting a kind of files, such as PDF.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ExportState {
+    /// No export is running, and the latest one succeeded if any.
+    #[default]
+    Idle,
+    /// An export is running.
+    Running,
+    /// The latest export failed.
+    Failed,
 }
 
+/// The status of exporting a kind of files, which is updated by the export
+/// actor and read by the client.
+#[derive(Debug, Clone, Default)]
+pub struct ExportTarget {
+    pub state: ExportState,
+    /// The error message of the latest export if it failed.
+    pub error: Option<String>,
+    /// The time when the latest successful export finished.
+    pub last_success: Option<Instant>,
+}
+
+/// The statuses of the exports, keyed by the extensions of the kinds.
+pub type ExportTargets = Arc<Mutex<HashMap<String, ExportTarget>>>;
+
 pub struct ExportActor {
     pub group: String,
     pub editor_tx: mpsc::UnboundedSender<EditorRequest>,
     pub export_rx: mpsc::UnboundedReceiver<ExportRequest>,
     pub doc_rx: watch::Receiver<Option<Arc<TypstDocument>>>,
+    /// The channel to broadcast export events to subscribers.
+    pub event_tx: broadcast::Sender<ExportEvent>,
 
     pub entry: EntryState,
+    /// The export config for entries without their own config.
     pub config: ExportConfig,
+    /// The export configs of specific entries, keyed by the path of their main
+    /// files.
+    pub entry_configs: HashMap<ImmutPath, ExportConfig>,
     pub kind: ExportKind,
     pub count_words: bool,
+    /// The dependencies of the document, used for bundling sources.
+    pub deps: Vec<ImmutPath>,
+    /// The content hashes of the last exported files, used to skip writing
+    /// identical files.
+    pub last_hashes: HashMap<PathBuf, u128>,
+    /// The workspace base to read the bundled resources from.
+    pub workspace_base: WorkspaceBase,
+    /// The pages changed by the latest compilation, shared with the client.
+    pub page_changes: Arc<Mutex<PageChanges>>,
+    /// The hashes of the pages in the files of split PDFs, see
+    /// `split_page_hash`, used to skip rendering the pages unchanged since
+    /// they were written.
+    pub page_hashes: HashMap<PathBuf, u128>,
+    /// The statuses of the exports, shared with the client.
+    pub targets: ExportTargets,
 }
 
 impl ExportActor {
@@ -58,22 +244,37 @@ impl ExportActor {
             'accumulate: loop {
                 log::debug!("RenderActor: received request: {req:?}");
                 match req {
-                    ExportRequest::ChangeConfig(config) => self.config = config,
+                    ExportRequest::ChangeConfig(config) => self.change_config(config),
+                    ExportRequest::ChangeEntryConfig(config) => match self.entry_path() {
+                        Some(path) => {
+                            self.entry_configs.insert(path, config);
+                        }
+                        None => self.config = config,
+                    },
                     ExportRequest::ChangeExportPath(entry) => self.entry = entry,
-                    ExportRequest::OnTyped => need_export |= self.config.mode == ExportMode::OnType,
-                    ExportRequest::OnSaved => match self.config.mode {
+                    ExportRequest::ChangeDependencies(deps) => self.deps = deps,
+                    ExportRequest::OnTyped => {
+                        need_export |= self.active_config().mode == ExportMode::OnType
+                    }
+                    ExportRequest::OnSaved => match self.active_config().mode {
                         ExportMode::OnSave => need_export = true,
                         ExportMode::OnDocumentHasTitle => need_export |= doc.title.is_some(),
                         _ => {}
                     },
-                    ExportRequest::Oneshot(kind, callback) => {
+                    ExportRequest::Oneshot(kind, force, callback) => {
                         // Do oneshot export instantly without accumulation.
-                        let kind = kind.as_ref().unwrap_or(&self.kind);
-                        let resp = self.check_mode_and_export(kind, &doc).await;
+                        let kind = kind.unwrap_or_else(|| self.kind.clone());
+                        let resp = self.check_mode_and_export(&kind, &doc, force).await;
                         if let Err(err) = callback.send(resp) {
                             error!("RenderActor(@{kind:?}): failed to send response: {err:?}");
                         }
                     }
+                    ExportRequest::OneshotBytes(kind, prev, callback) => {
+                        let resp = self.export_bytes(&kind, prev.as_ref().unwrap_or(&doc));
+                        if callback.send(resp).is_err() {
+                            error!("RenderActor(@{kind:?}): failed to send exported data");
+                        }
+                    }
                 }
 
                 // Try to accumulate more requests.
@@ -84,7 +285,8 @@ impl ExportActor {
             }
 
             if need_export {
-                self.check_mode_and_export(&self.kind, &doc).await;
+                let kind = self.kind.clone();
+                self.check_mode_and_export(&kind, &doc, false).await;
             }
 
             if self.count_words {
@@ -98,10 +300,39 @@ impl ExportActor {
         info!("RenderActor(@{:?}): stopped", &self.kind);
     }
 
+    /// Gets the path of the main file of the active entry.
+    fn entry_path(&self) -> Option<ImmutPath> {
+        let root = self.entry.root()?;
+        let main = self.entry.main()?;
+        if main.package().is_some() {
+            return None;
+        }
+
+        Some(main.vpath().resolve(&root)?.into())
+    }
+
+    /// Changes the default config, merging its changes into the configs of
+    /// the entries.
+    fn change_config(&mut self, config: ExportConfig) {
+        for entry_config in self.entry_configs.values_mut() {
+            entry_config.merge_default(&self.config, &config);
+        }
+        self.config = config;
+    }
+
+    /// Gets the export config of the active entry, falling back to the default
+    /// config for unknown entries.
+    fn active_config(&self) -> &ExportConfig {
+        self.entry_path()
+            .and_then(|path| self.entry_configs.get(&path))
+            .unwrap_or(&self.config)
+    }
+
     async fn check_mode_and_export(
-        &self,
+        &mut self,
         kind: &ExportKind,
         doc: &TypstDocument,
+        force: bool,
     ) -> Option<PathBuf> {
         // pub entry: EntryState,
         let root = self.entry.root();
@@ -109,7 +340,9 @@ impl ExportActor {
 
         info!(
             "RenderActor: check path {:?} and root {:?} with output directory {}",
-            main, root, self.config.substitute_pattern
+            main,
+            root,
+            self.active_config().substitute_pattern
         );
 
         let root = root?;
@@ -122,7 +355,50 @@ impl ExportActor {
 
         let path = main.vpath().resolve(&root)?;
 
-        match self.export(kind, doc, &root, &path).await {
+        let target = kind.extension().to_owned();
+        self.targets.lock().entry(target.clone()).or_default().state = ExportState::Running;
+
+        let start = Instant::now();
+        let res = self.export(kind, doc, &root, &path, force);
+
+        {
+            let mut targets = self.targets.lock();
+            let status = targets.entry(target).or_default();
+            match &res {
+                Ok(_) => {
+                    status.state = ExportState::Idle;
+                    status.error = None;
+                    status.last_success = Some(Instant::now());
+                }
+                Err(err) => {
+                    status.state = ExportState::Failed;
+                    status.error = Some(err.to_string());
+                }
+            }
+        }
+        if let (Ok(output), Some(command)) = (&res, &self.active_config().post_export_command) {
+            if output.written {
+                self.spawn_post_export_command(command.clone(), &root, &output.path);
+            }
+        }
+        let event = ExportEvent {
+            kind: kind.extension().to_owned(),
+            path: res.as_ref().ok().map(|output| output.path.clone()),
+            thumbnail: res
+                .as_ref()
+                .ok()
+                .and_then(|output| output.thumbnail.clone()),
+            error: res.as_ref().err().map(|err| err.to_string()),
+            duration: start.elapsed(),
+        };
+        let res = res.map(|output| output.path);
+        // There may be no subscriber.
+        let _ = self.event_tx.send(event.clone());
+        let _ = self
+            .editor_tx
+            .send(EditorRequest::Export(self.group.clone(), event));
+
+        match res {
             Ok(pdf) => Some(pdf),
             Err(err) => {
                 error!("RenderActor({kind:?}): failed to export {err}");
@@ -131,17 +407,33 @@ impl ExportActor {
         }
     }
 
-    async fn export(
-        &self,
+    /// Runs the post-export command on a blocking thread without waiting for
+    /// it, whose failure is reported to the editor.
+    fn spawn_post_export_command(&self, command: Vec<String>, root: &Path, output: &Path) {
+        let group = self.group.clone();
+        let editor_tx = self.editor_tx.clone();
+        let root = root.to_owned();
+        let output = output.to_owned();
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = run_post_export_command(&command, &root, &output) {
+                error!("RenderActor: post-export command failed: {err:#}");
+                let msg = format!("post-export command failed: {err:#}");
+                let _ = editor_tx.send(EditorRequest::PostExportFailed(group, msg));
+            }
+        });
+    }
+
+    /// Exports the document to the path substituted from the main file, along
+    /// with the thumbnail if it is configured.
+    pub(super) fn export(
+        &mut self,
         kind: &ExportKind,
         doc: &TypstDocument,
         root: &Path,
         path: &Path,
-    ) -> anyhow::Result<PathBuf> {
-        use ExportKind::*;
-        use PageSelection::*;
-
-        let Some(to) = substitute_path(&self.config.substitute_pattern, root, path) else {
+        force: bool,
+    ) -> anyhow::Result<ExportOutput> {
+        let Some(to) = substitute_path(&self.active_config().substitute_pattern, root, path) else {
             bail!("RenderActor({kind:?}): failed to substitute path");
         };
         if to.is_relative() {
@@ -154,40 +446,416 @@ impl ExportActor {
         let to = to.with_extension(kind.extension());
         info!("RenderActor({kind:?}): exporting {path:?} to {to:?}");
 
+        let output_mode = self.active_config().output_mode;
         if let Some(e) = to.parent() {
             if !e.exists() {
-                std::fs::create_dir_all(e).with_context(|| {
+                create_output_dir(e, output_mode).with_context(|| {
                     format!("RenderActor({kind:?}): failed to create directory")
                 })?;
             }
         }
 
-        static BLANK: Lazy<Frame> = Lazy::new(Frame::default);
-        let first_frame = || doc.pages.first().map(|f| &f.frame).unwrap_or(&*BLANK);
-        let data = match kind {
-            Pdf => {
-                // todo: Some(pdf_uri.as_str())
-                // todo: timestamp world.now()
-                typst_pdf::pdf(doc, Smart::Auto, None)
-            }
-            Svg { page: First } => typst_svg::svg(first_frame()).into_bytes(),
-            Svg { page: Merged } => typst_svg::svg_merged(doc, Abs::zero()).into_bytes(),
-            Png { page: First } => typst_render::render(first_frame(), 3., Color::WHITE)
-                .encode_png()
-                .map_err(|err| anyhow::anyhow!("failed to encode PNG ({err})"))?,
-            Png { page: Merged } => {
-                typst_render::render_merged(doc, 3., Color::WHITE, Abs::zero(), Color::WHITE)
-                    .encode_png()
-                    .map_err(|err| anyhow::anyhow!("failed to encode PNG ({err})"))?
+        let split = matches!(kind, ExportKind::Pdf) && self.active_config().pdf_split_pages;
+        let page_hashes = (self.page_changes.lock().page_hashes(doc))
+            .filter(|_| split)
+            .map(|hashes| {
+                let config = self.active_config();
+                let hashes = hashes
+                    .iter()
+                    .map(|hash| split_page_hash(*hash, doc, config));
+                hashes.collect::<Vec<_>>()
+            });
+        let up_to_date = |page: usize, to: &Path| {
+            let hash = page_hashes.as_ref().and_then(|hashes| hashes.get(page));
+            !force && to.exists() && hash.is_some_and(|hash| self.page_hashes.get(to) == Some(hash))
+        };
+
+        let outputs = self.outputs(kind, doc, Some((root, path)), &to, &up_to_date)?;
+        let first = match split {
+            true => (!doc.pages.is_empty()).then(|| split_pdf_path(&to, 0, doc.pages.len())),
+            false => outputs.first().map(|(to, _)| to.clone()),
+        };
+        let first = first.with_context(|| format!("RenderActor({kind:?}): no page to export"))?;
+
+        let mut written = false;
+        for (to, data) in &outputs {
+            self.page_hashes.remove(to);
+            written |= self.write_if_changed(kind, to, data, force)?;
+        }
+        for (page, hash) in page_hashes.into_iter().flatten().enumerate() {
+            let to = split_pdf_path(&to, page, doc.pages.len());
+            self.page_hashes.insert(to, hash);
+        }
+
+        // The thumbnail is only written along with the configured kind, and
+        // doesn't count as a written file.
+        let thumbnail = self.active_config().thumbnail;
+        let thumbnail = match thumbnail.filter(|_| kind.extension() == self.kind.extension()) {
+            Some(thumbnail) => {
+                let stem = to.file_stem().unwrap_or_default().to_string_lossy();
+                let thumbnail_to = to.with_file_name(format!("{stem}.thumbnail.png"));
+                let data = render_thumbnail(doc, &thumbnail, self.active_config())?;
+                self.write_if_changed(kind, &thumbnail_to, &data, force)?;
+                Some(thumbnail_to)
             }
+            None => None,
         };
 
-        std::fs::write(&to, data)
+        info!("RenderActor({kind:?}): export complete");
+        Ok(ExportOutput {
+            path: first,
+            thumbnail,
+            written,
+        })
+    }
+
+    /// Writes an exported file, returning whether it is written. Writing the
+    /// file is skipped if it is unchanged, to avoid waking up file watchers
+    /// downstream.
+    fn write_if_changed(
+        &mut self,
+        kind: &ExportKind,
+        to: &Path,
+        data: &[u8],
+        force: bool,
+    ) -> anyhow::Result<bool> {
+        let hash = typst::util::hash128(data);
+        if !force && to.exists() && self.last_hashes.get(to) == Some(&hash) {
+            info!("RenderActor({kind:?}): export to {to:?} skipped, content is unchanged");
+            return Ok(false);
+        }
+
+        write_output(to, data, self.active_config().output_mode)
             .with_context(|| format!("RenderActor({kind:?}): failed to export"))?;
+        self.last_hashes.insert(to.to_owned(), hash);
+        Ok(true)
+    }
 
-        info!("RenderActor({kind:?}): export complete");
-        Ok(to)
+    /// Exports the document in memory, naming the files after the main file of
+    /// the entry.
+    fn export_bytes(
+        &self,
+        kind: &ExportKind,
+        doc: &TypstDocument,
+    ) -> anyhow::Result<ExportedFiles> {
+        let root = self.entry.root();
+        let path = self.entry_path();
+        let name = path
+            .as_deref()
+            .and_then(Path::file_stem)
+            .unwrap_or("main".as_ref());
+        let to = Path::new(name).with_extension(kind.extension());
+
+        let entry = root.as_deref().zip(path.as_deref());
+        let outputs = self.outputs(kind, doc, entry, &to, &|_, _| false)?;
+        let outputs = outputs.into_iter().map(|(to, data)| {
+            let name = to
+                .file_name()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned();
+            (name, data)
+        });
+        Ok(outputs.collect())
+    }
+
+    /// Renders the files to export, given the root and the path of the main
+    /// file, and the path to export to. The pages of a split PDF which are
+    /// `up_to_date` are left out.
+    fn outputs(
+        &self,
+        kind: &ExportKind,
+        doc: &TypstDocument,
+        entry: Option<(&Path, &Path)>,
+        to: &Path,
+        up_to_date: &dyn Fn(usize, &Path) -> bool,
+    ) -> anyhow::Result<Vec<(PathBuf, Vec<u8>)>> {
+        use ExportKind::*;
+
+        Ok(match kind {
+            Pdf if self.active_config().pdf_split_pages => {
+                split_pdf(doc, to, self.active_config(), up_to_date)
+            }
+            Bundle { sources, kinds } => {
+                let Some((root, path)) = entry else {
+                    bail!("RenderActor({kind:?}): cannot bundle a document without an entry");
+                };
+                let deps = sources.then_some(self.deps.as_slice());
+                let base = &self.workspace_base;
+                let data = bundle(doc, root, path, kinds, deps, base, self.active_config())?;
+                vec![(to.to_owned(), data)]
+            }
+            _ => vec![(to.to_owned(), render(kind, doc, self.active_config())?)],
+        })
+    }
+}
+
+/// Bundles the PDF, the artifacts of `kinds` and the dependencies of the
+/// document, if any, into a zip archive.
+///
+/// Dependencies under the root keep their relative paths, and the others, e.g.
+/// packages, are stored under `external/` with their absolute paths. The
+/// resources under the root are read from the workspace base if it is set.
+fn bundle(
+    doc: &TypstDocument,
+    root: &Path,
+    path: &Path,
+    kinds: &[ExportKind],
+    deps: Option<&[ImmutPath]>,
+    workspace_base: &WorkspaceBase,
+    config: &ExportConfig,
+) -> anyhow::Result<Vec<u8>> {
+    let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let options = zip::write::FileOptions::default();
+
+    let name = path.file_stem().unwrap_or_default().to_string_lossy();
+    for kind in std::iter::once(&ExportKind::Pdf).chain(kinds) {
+        if matches!(kind, ExportKind::Bundle { .. }) {
+            bail!("RenderActor: cannot bundle a bundle");
+        }
+        let data = render(kind, doc, config)?;
+        zip.start_file(format!("{name}.{}", kind.extension()), options)?;
+        zip.write_all(&data)?;
+    }
+
+    for dep in deps.unwrap_or_default() {
+        let file = workspace_base.resolve(dep);
+        let file = file.as_deref().unwrap_or(dep);
+        if !file.is_file() {
+            continue;
+        }
+
+        let data =
+            std::fs::read(file).with_context(|| format!("RenderActor: failed to read {file:?}"))?;
+        zip.start_file(bundle_entry_name(root, dep), options)?;
+        zip.write_all(&data)?;
+    }
+
+    Ok(zip.finish()?.into_inner())
+}
+
+/// The name of a dependency in the bundle.
+fn bundle_entry_name(root: &Path, dep: &Path) -> String {
+    let (mut parts, rel) = match dep.strip_prefix(root) {
+        Ok(rel) => (vec![], rel),
+        Err(_) => (vec!["external".to_owned()], dep),
+    };
+    parts.extend(rel.components().filter_map(|c| match c {
+        std::path::Component::Normal(e) => Some(e.to_string_lossy().into_owned()),
+        _ => None,
+    }));
+    parts.join("/")
+}
+
+/// The resolution of exported PNG images.
+const PNG_PIXEL_PER_PT: f32 = 3.;
+
+/// Renders the document into the given format.
+///
+/// If `png_transparent` is set, PNG is rendered with a transparent page
+/// background. PNG images exceeding `png_max_pixels` are rejected, to avoid
+/// running out of memory on huge pages.
+fn render(
+    kind: &ExportKind,
+    doc: &TypstDocument,
+    config: &ExportConfig,
+) -> anyhow::Result<Vec<u8>> {
+    use ExportKind::*;
+    use PageSelection::*;
+
+    static BLANK: Lazy<Frame> = Lazy::new(Frame::default);
+    let first_frame = || doc.pages.first().map(|f| &f.frame).unwrap_or(&*BLANK);
+
+    if let Png { page } = kind {
+        let (width, height) = match page {
+            First => (first_frame().width(), first_frame().height()),
+            Merged => doc
+                .pages
+                .iter()
+                .fold((Abs::zero(), Abs::zero()), |(w, h), p| {
+                    (w.max(p.frame.width()), h + p.frame.height())
+                }),
+        };
+        check_png_pixels(width, height, PNG_PIXEL_PER_PT, config)?;
+    }
+
+    let fill = if config.png_transparent {
+        Color::WHITE.with_alpha(0.)
+    } else {
+        Color::WHITE
+    };
+    Ok(match kind {
+        Pdf => {
+            // todo: Some(pdf_uri.as_str())
+            // todo: timestamp world.now()
+            pdf(doc, config)
+        }
+        Svg { page: First } => typst_svg::svg(first_frame()).into_bytes(),
+        Svg { page: Merged } => typst_svg::svg_merged(doc, Abs::zero()).into_bytes(),
+        Png { page: First } => typst_render::render(first_frame(), PNG_PIXEL_PER_PT, fill)
+            .encode_png()
+            .map_err(|err| anyhow::anyhow!("failed to encode PNG ({err})"))?,
+        Png { page: Merged } => {
+            typst_render::render_merged(doc, PNG_PIXEL_PER_PT, fill, Abs::zero(), fill)
+                .encode_png()
+                .map_err(|err| anyhow::anyhow!("failed to encode PNG ({err})"))?
+        }
+        Bundle { .. } => bail!("cannot render a bundle directly"),
+    })
+}
+
+/// Checks that a PNG image of the size rendered at the resolution doesn't
+/// exceed `png_max_pixels`.
+fn check_png_pixels(
+    width: Abs,
+    height: Abs,
+    pixel_per_pt: f32,
+    config: &ExportConfig,
+) -> anyhow::Result<()> {
+    let to_px = |len: Abs| (pixel_per_pt * len.to_pt() as f32).round().max(1.) as u64;
+    let (width, height) = (to_px(width), to_px(height));
+    let max_pixels = config.png_max_pixels;
+    if max_pixels > 0 && width * height > max_pixels {
+        bail!(
+            "the PNG image of {width}x{height} pixels exceeds the limit of {max_pixels} pixels, \
+            which can be changed by `tinymist.exportPngMaxPixels`"
+        );
+    }
+
+    Ok(())
+}
+
+/// The largest maximum size of thumbnails in pixels, which the configured
+/// maximum size is clamped to.
+const THUMBNAIL_MAX_SIZE: u32 = 2048;
+
+/// Renders a page of the document into a thumbnail, scaled down so that both
+/// its width and height fit into the maximum size.
+fn render_thumbnail(
+    doc: &TypstDocument,
+    thumbnail: &ThumbnailConfig,
+    config: &ExportConfig,
+) -> anyhow::Result<Vec<u8>> {
+    let page = doc.pages.get(thumbnail.page.get() - 1);
+    let Some(page) = page.or_else(|| doc.pages.last()) else {
+        bail!("no page to render a thumbnail");
+    };
+
+    let frame = &page.frame;
+    let max_size = thumbnail.max_size.clamp(1, THUMBNAIL_MAX_SIZE);
+    let longest = frame.width().max(frame.height()).to_pt() as f32;
+    let pixel_per_pt = if longest > 0. {
+        max_size as f32 / longest
+    } else {
+        1.
+    };
+    check_png_pixels(frame.width(), frame.height(), pixel_per_pt, config)?;
+    let fill = if config.png_transparent {
+        Color::WHITE.with_alpha(0.)
+    } else {
+        Color::WHITE
+    };
+
+    typst_render::render(frame, pixel_per_pt, fill)
+        .encode_png()
+        .map_err(|err| anyhow::anyhow!("failed to encode thumbnail ({err})"))
+}
+
+/// Creates the directory of exported files. On unix, the directory is created
+/// with the permissions of the files, plus the execute permission for the
+/// users who can read the files.
+fn create_output_dir(dir: &Path, mode: Option<u32>) -> std::io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(mode | ((mode & 0o444) >> 2));
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    builder.create(dir)
+}
+
+/// Writes an exported file. On unix, a new file is created with the
+/// permissions, and the permissions are also set if the file already exists.
+fn write_output(to: &Path, data: &[u8], mode: Option<u32>) -> std::io::Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
     }
+    let mut file = options.open(to)?;
+
+    // The mode of the options is masked by the umask, and is not applied to
+    // an existing file.
+    #[cfg(unix)]
+    if let Some(mode) = mode {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = mode;
+
+    file.write_all(data)
+}
+
+/// Writes a PDF with the creator and the producer in the config.
+fn pdf(doc: &TypstDocument, config: &ExportConfig) -> Vec<u8> {
+    override_pdf_info(
+        typst_pdf::pdf(doc, Smart::Auto, None),
+        config.pdf_creator.as_deref(),
+        config.pdf_producer.as_deref(),
+    )
+}
+
+/// Renders each page of the document into a separate PDF, which is named after
+/// `to` with the page number, e.g. `main-001.pdf`. The pages which are
+/// `up_to_date` with their files are skipped.
+///
+/// The outline and the links across pages are not kept in the split PDFs.
+fn split_pdf(
+    doc: &TypstDocument,
+    to: &Path,
+    config: &ExportConfig,
+    up_to_date: &dyn Fn(usize, &Path) -> bool,
+) -> Vec<(PathBuf, Vec<u8>)> {
+    let pages = doc.pages.iter().enumerate();
+    pages
+        .filter_map(|(i, page)| {
+            let to = split_pdf_path(to, i, doc.pages.len());
+            if up_to_date(i, &to) {
+                return None;
+            }
+
+            let doc = TypstDocument {
+                pages: vec![page.clone()],
+                introspector: Default::default(),
+                ..doc.clone()
+            };
+            Some((to, pdf(&doc, config)))
+        })
+        .collect()
+}
+
+/// Hashes a page of a split PDF, given the hash of its frame, along with the
+/// document information written to its file.
+fn split_page_hash(frame_hash: u128, doc: &TypstDocument, config: &ExportConfig) -> u128 {
+    let info = (&doc.title, &doc.author, &doc.keywords, &doc.date);
+    let creator = (&config.pdf_creator, &config.pdf_producer);
+    typst::util::hash128(&(frame_hash, info, creator))
+}
+
+/// Gets the path of a page, starting from 0, in a PDF split into `count` pages.
+fn split_pdf_path(to: &Path, page: usize, count: usize) -> PathBuf {
+    let stem = to.file_stem().unwrap_or_default().to_string_lossy();
+    let width = count.to_string().len().max(3);
+    to.with_file_name(format!("{stem}-{:0width$}.pdf", page + 1))
 }
 
 #[comemo::memoize]
@@ -220,10 +888,269 @@ fn substitute_path(substitute_pattern: &str, root: &Path, path: &Path) -> Option
     Some(PathBuf::from(path).clean().into())
 }
 
+/// Runs a post-export command in the root with the path of the exported file,
+/// logging its output. A command exiting with a failure is an error.
+fn run_post_export_command(command: &[String], root: &Path, output: &Path) -> anyhow::Result<()> {
+    let Some((program, args)) = command.split_first() else {
+        bail!("the command is empty");
+    };
+
+    info!("RenderActor: running post-export command {command:?} on {output:?}");
+    let res = std::process::Command::new(program)
+        .args(args)
+        .arg(output)
+        .current_dir(root)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .with_context(|| format!("failed to run {program:?}"))?;
+
+    let stdout = String::from_utf8_lossy(&res.stdout);
+    let stderr = String::from_utf8_lossy(&res.stderr);
+    info!(
+        "RenderActor: post-export command exited with {}, stdout: {:?}, stderr: {:?}",
+        res.status,
+        stdout.trim(),
+        stderr.trim()
+    );
+    if !res.status.success() {
+        bail!("{program:?} exited with {}: {}", res.status, stderr.trim());
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_merge_default_config() {
+        let old = ExportConfig::default();
+        let new = ExportConfig {
+            mode: ExportMode::OnSave,
+            png_transparent: true,
+            ..ExportConfig::default()
+        };
+
+        let mut entry = ExportConfig {
+            substitute_pattern: "$root/slides/$name".to_owned(),
+            png_transparent: true,
+            ..ExportConfig::default()
+        };
+        entry.merge_default(&old, &new);
+        assert_eq!(entry.substitute_pattern, "$root/slides/$name");
+        assert_eq!(entry.mode, ExportMode::OnSave);
+        assert!(entry.png_transparent);
+
+        // A change of the default config overrides the field of the entry.
+        entry.merge_default(&new, &old);
+        assert_eq!(entry.mode, ExportMode::Never);
+        assert!(!entry.png_transparent);
+    }
+
+    #[test]
+    fn test_render_transparent_png() {
+        let doc = TypstDocument::default();
+        let kind = ExportKind::Png {
+            page: PageSelection::First,
+        };
+
+        let config = ExportConfig {
+            png_transparent: true,
+            ..ExportConfig::default()
+        };
+
+        // The color type in the IHDR chunk is RGBA.
+        let transparent = render(&kind, &doc, &config).unwrap();
+        assert_eq!(&transparent[1..4], b"PNG");
+        assert_eq!(transparent[25], 6);
+
+        let opaque = render(&kind, &doc, &ExportConfig::default()).unwrap();
+        assert_ne!(transparent, opaque);
+    }
+
+    #[test]
+    fn test_render_png_max_pixels() {
+        let mut doc = TypstDocument::default();
+        doc.pages.push(typst::layout::Page {
+            frame: Frame::soft(typst::layout::Size::new(Abs::pt(100.), Abs::pt(100.))),
+            numbering: None,
+            number: 1,
+        });
+        let kind = ExportKind::Png {
+            page: PageSelection::First,
+        };
+
+        // The page is rendered into 300x300 pixels.
+        let mut config = ExportConfig {
+            png_max_pixels: 300 * 300,
+            ..ExportConfig::default()
+        };
+        assert!(render(&kind, &doc, &config).is_ok());
+
+        config.png_max_pixels = 300 * 300 - 1;
+        assert!(render(&kind, &doc, &config).is_err());
+    }
+
+    #[test]
+    fn test_render_thumbnail() {
+        let mut doc = TypstDocument::default();
+        for width in [200., 50.] {
+            doc.pages.push(typst::layout::Page {
+                frame: Frame::soft(typst::layout::Size::new(Abs::pt(width), Abs::pt(100.))),
+                numbering: None,
+                number: doc.pages.len() + 1,
+            });
+        }
+
+        // The width and the height in the IHDR chunk.
+        let size = |page, max_size| {
+            let thumbnail = ThumbnailConfig {
+                max_size,
+                page: NonZeroUsize::new(page).unwrap(),
+            };
+            let png = render_thumbnail(&doc, &thumbnail, &ExportConfig::default()).unwrap();
+            let width = u32::from_be_bytes(png[16..20].try_into().unwrap());
+            let height = u32::from_be_bytes(png[20..24].try_into().unwrap());
+            (width, height)
+        };
+
+        assert_eq!(size(1, 64), (64, 32));
+        assert_eq!(size(2, 64), (32, 64));
+        assert_eq!(size(3, 64), (32, 64));
+        // The maximum size is clamped.
+        assert_eq!(
+            size(1, u32::MAX),
+            (THUMBNAIL_MAX_SIZE, THUMBNAIL_MAX_SIZE / 2)
+        );
+
+        // The limit of pixels of PNG images also applies to thumbnails.
+        let config = ExportConfig {
+            png_max_pixels: 64 * 32 - 1,
+            ..ExportConfig::default()
+        };
+        let thumbnail = ThumbnailConfig {
+            max_size: 64,
+            page: NonZeroUsize::MIN,
+        };
+        assert!(render_thumbnail(&doc, &thumbnail, &config).is_err());
+    }
+
+    #[test]
+    fn test_bundle() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("proj");
+        let path = root.join("main.typ");
+        let package = dir.path().join("packages/lib.typ");
+        std::fs::create_dir_all(package.parent().unwrap()).unwrap();
+        std::fs::create_dir_all(root.join("chapters")).unwrap();
+        std::fs::write(&path, "#include \"chapters/intro.typ\"").unwrap();
+        std::fs::write(root.join("chapters/intro.typ"), "Hello").unwrap();
+        std::fs::write(&package, "#let f = 1").unwrap();
+
+        let deps = [&path, &root.join("chapters/intro.typ"), &package]
+            .map(|p| ImmutPath::from(p.as_path()));
+        let kinds = [ExportKind::Svg {
+            page: PageSelection::First,
+        }];
+        let config = ExportConfig::default();
+        let doc = TypstDocument::default();
+
+        let base = WorkspaceBase::default();
+        let names = |deps: Option<&[ImmutPath]>| {
+            let data = bundle(&doc, &root, &path, &kinds, deps, &base, &config).unwrap();
+            let zip = zip::ZipArchive::new(std::io::Cursor::new(data)).unwrap();
+            let mut names = zip.file_names().map(str::to_owned).collect::<Vec<_>>();
+            names.sort();
+            names
+        };
+
+        let external = bundle_entry_name(&root, &package);
+        assert!(external.starts_with("external/"));
+        assert!(external.ends_with("/packages/lib.typ"));
+        assert_eq!(
+            names(Some(deps.as_slice())),
+            [
+                "chapters/intro.typ".to_owned(),
+                external,
+                "main.pdf".to_owned(),
+                "main.svg".to_owned(),
+                "main.typ".to_owned(),
+            ]
+        );
+        assert_eq!(names(None), ["main.pdf", "main.svg"]);
+
+        // The resources are read from the workspace base, but keep their paths
+        // under the root.
+        std::fs::create_dir_all(root.join("assets")).unwrap();
+        std::fs::write(root.join("assets/data.csv"), "a,b").unwrap();
+        base.set_root(Some(root.as_path().into()));
+        base.set_base(Some(root.join("assets").as_path().into()));
+        let deps = [ImmutPath::from(root.join("data.csv").as_path())];
+        assert_eq!(
+            names(Some(deps.as_slice())),
+            ["data.csv", "main.pdf", "main.svg"]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_output_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let to = dir.path().join("out/main.pdf");
+        create_output_dir(to.parent().unwrap(), Some(0o640)).unwrap();
+        write_output(&to, b"%PDF", Some(0o640)).unwrap();
+        let mode = std::fs::metadata(&to).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+
+        // The permissions of an existing file are also set.
+        write_output(&to, b"%PDF", Some(0o600)).unwrap();
+        let mode = std::fs::metadata(&to).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(std::fs::read(&to).unwrap(), b"%PDF");
+    }
+
+    #[test]
+    fn test_split_pdf() {
+        let mut doc = TypstDocument::default();
+        for number in 1..=2 {
+            doc.pages.push(typst::layout::Page {
+                frame: Frame::soft(typst::layout::Size::new(Abs::pt(100.), Abs::pt(100.))),
+                numbering: None,
+                number,
+            });
+        }
+
+        let config = ExportConfig {
+            pdf_producer: Some("Acme".to_owned()),
+            ..ExportConfig::default()
+        };
+        let outputs = split_pdf(&doc, Path::new("/out/main.pdf"), &config, &|_, _| false);
+        let paths = outputs.iter().map(|(to, _)| to.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                PathBuf::from("/out/main-001.pdf"),
+                PathBuf::from("/out/main-002.pdf")
+            ]
+        );
+        assert!(outputs.iter().all(|(_, data)| data.starts_with(b"%PDF")));
+        assert!(outputs.iter().all(|(_, data)| {
+            let doc = lopdf::Document::load_mem(data).unwrap();
+            let info = doc.trailer.get(b"Info").unwrap().as_reference().unwrap();
+            let producer = doc.get_dictionary(info).unwrap().get(b"Producer").unwrap();
+            producer.as_str().unwrap() == b"Acme"
+        }));
+
+        // The pages up to date with their files are not rendered again.
+        let up_to_date = |page: usize, to: &Path| page == 0 && to.ends_with("main-001.pdf");
+        let outputs = split_pdf(&doc, Path::new("/out/main.pdf"), &config, &up_to_date);
+        let paths = outputs.iter().map(|(to, _)| to.clone()).collect::<Vec<_>>();
+        assert_eq!(paths, [PathBuf::from("/out/main-002.pdf")]);
+    }
+
     #[test]
     fn test_substitute_path() {
         let root = Path::new("/root");
@@ -246,4 +1173,24 @@ mod tests {
             Some(PathBuf::from("/substitute/target/dir1/dir2/file.txt").into())
         );
     }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_post_export_command() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(dir.join("main.pdf"), b"%PDF").unwrap();
+
+        // The path of the exported file is passed as `$0` of the script.
+        let command = ["sh", "-c", "test -f \"$0\" && touch done"].map(str::to_owned);
+        let exists = run_post_export_command(&command, dir, &dir.join("main.pdf"));
+        let done = dir.join("done").exists();
+        let missing = run_post_export_command(&command, dir, &dir.join("missing.pdf"));
+        let empty = run_post_export_command(&[], dir, &dir.join("main.pdf"));
+
+        assert!(exists.is_ok());
+        assert!(done);
+        assert!(missing.is_err());
+        assert!(empty.is_err());
+    }
 }
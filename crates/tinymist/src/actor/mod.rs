@@ -3,37 +3,49 @@
 pub mod editor;
 pub mod export;
 pub mod format;
+pub mod oneshot;
+pub mod testing;
 pub mod typ_client;
 pub mod typ_server;
 pub mod user_action;
 
-use std::path::Path;
+use std::{
+    path::Path,
+    sync::{atomic::AtomicUsize, Arc},
+};
 
+use parking_lot::{Mutex, RwLock};
 use tinymist_query::analysis::Analysis;
-use tinymist_query::ExportKind;
+use tinymist_query::{DiagnosticsMap, ElementValidator, ExportKind};
 use tinymist_render::PeriscopeRenderer;
-use tokio::sync::{mpsc, watch};
+use tokio::sync::{broadcast, mpsc, watch};
 use typst_ts_compiler::{
     service::CompileDriverImpl,
     vfs::notify::{FileChangeSet, MemoryEvent},
 };
-use typst_ts_core::config::compiler::EntryState;
+use typst_ts_core::{config::compiler::EntryState, typst::prelude::EcoVec};
 
 use self::{
-    export::{ExportActor, ExportConfig},
+    export::{ExportActor, ExportTargets},
     format::run_format_thread,
-    typ_client::{CompileClientActor, CompileDriver, CompileHandler},
+    typ_client::{
+        CompileClientActor, CompileDriver, CompileHandler, CompileHealthState,
+        CompileServerSpawner, DocumentHistory, PageChanges, UntitledMirrors,
+    },
     typ_server::CompileServerActor,
     user_action::run_user_action_thread,
 };
 use crate::{
     compiler::CompileServer,
-    world::{ImmutDict, LspWorld, LspWorldBuilder},
-    TypstLanguageServer,
+    world::{ImmutDict, LspWorld, LspWorldBuilder, WorkspaceBase},
+    ExportMode, LspHost, TypstLanguageServer,
 };
 
 type CompileDriverInner = CompileDriverImpl<LspWorld>;
 
+/// The diagnostics group of the compiler of the check entry.
+pub const CHECK_DIAG_GROUP: &str = "check";
+
 impl CompileServer {
     pub fn restart_server(&mut self, group: &str) {
         let server = self.server(
@@ -56,9 +68,22 @@ impl CompileServer {
         snapshot: FileChangeSet,
     ) -> CompileClientActor {
         let (doc_tx, doc_rx) = watch::channel(None);
+        let (report_tx, report_rx) = watch::channel(None);
+        let health = Arc::new(CompileHealthState::default());
+        let doc_version = Arc::new(AtomicUsize::new(0));
+        let history = Arc::new(Mutex::new(DocumentHistory::default()));
+        history
+            .lock()
+            .set_capacity(self.config.document_history_size);
+        let page_changes = Arc::new(Mutex::new(PageChanges::default()));
+        let diagnostics = Arc::new(Mutex::new(DiagnosticsMap::default()));
+        let last_warnings = Arc::new(Mutex::new(EcoVec::new()));
+        let exports = ExportTargets::default();
+        let element_validators: Arc<Mutex<Vec<Arc<dyn ElementValidator>>>> = Default::default();
+        let workspace_base = WorkspaceBase::default();
+        let untitled_mirrors = UntitledMirrors::default();
         let (export_tx, export_rx) = mpsc::unbounded_channel();
-        let (intr_tx, intr_rx) = mpsc::unbounded_channel();
-        let intr_tx_ = intr_tx.clone();
+        let (export_event_tx, _) = broadcast::channel(16);
 
         // Run Export actors before preparing cluster to avoid loss of events
         self.handle.spawn(
@@ -67,69 +92,169 @@ impl CompileServer {
                 editor_tx: self.editor_tx.clone(),
                 export_rx,
                 doc_rx,
+                event_tx: export_event_tx.clone(),
                 entry: entry.clone(),
-                config: ExportConfig {
-                    substitute_pattern: self.config.output_path.clone(),
-                    mode: self.config.export_pdf,
-                },
+                config: self.config.export_config(),
+                entry_configs: Default::default(),
                 kind: ExportKind::Pdf,
                 count_words: self.config.notify_compile_status,
+                deps: vec![],
+                last_hashes: Default::default(),
+                workspace_base: workspace_base.clone(),
+                page_changes: page_changes.clone(),
+                page_hashes: Default::default(),
+                targets: exports.clone(),
             }
             .run(),
         );
 
-        // Create the server
-        let handler = CompileHandler {
-            #[cfg(feature = "preview")]
-            inner: std::sync::Arc::new(parking_lot::Mutex::new(None)),
-            diag_group: editor_group.clone(),
-            doc_tx,
-            export_tx: export_tx.clone(),
-            editor_tx: self.editor_tx.clone(),
-        };
-
+        // The channels to other actors are shared by all servers spawned for the
+        // client, so that they survive restarts of the server.
+        #[cfg(feature = "preview")]
+        let preview_inner = Arc::new(Mutex::new(None));
+        let doc_tx = Arc::new(doc_tx);
+        let report_tx = Arc::new(report_tx);
+        let export_tx_ = export_tx.clone();
+        let editor_tx = self.editor_tx.clone();
+        let health_ = health.clone();
+        let doc_version_ = doc_version.clone();
+        let history_ = history.clone();
+        let page_changes_ = page_changes.clone();
+        let diagnostics_ = diagnostics.clone();
+        let last_warnings_ = last_warnings.clone();
+        let element_validators_ = element_validators.clone();
+        let workspace_base_ = workspace_base.clone();
+        let untitled_mirrors_ = untitled_mirrors.clone();
+        let handle = self.handle.clone();
         let position_encoding = self.const_config().position_encoding;
-        let enable_periscope = self.config.periscope_args.is_some();
-        let periscope_args = self.config.periscope_args.clone();
         let diag_group = editor_group.clone();
-        let font_resolver = self.config.determine_fonts();
-        let entry_ = entry.clone();
 
-        log::info!(
-            "TypstActor: creating server for {diag_group}, entry: {entry:?}, inputs: {inputs:?}"
-        );
+        let spawner: CompileServerSpawner = Arc::new(move |config, entry| {
+            // Create the server
+            let handler = CompileHandler {
+                #[cfg(feature = "preview")]
+                inner: preview_inner.clone(),
+                diag_group: diag_group.clone(),
+                doc_tx: doc_tx.clone(),
+                report_tx: report_tx.clone(),
+                health: health_.clone(),
+                doc_version: doc_version_.clone(),
+                history: history_.clone(),
+                page_changes: page_changes_.clone(),
+                diagnostics: diagnostics_.clone(),
+                last_warnings: last_warnings_.clone(),
+                untitled_mirrors: untitled_mirrors_.clone(),
+                export_tx: export_tx_.clone(),
+                editor_tx: editor_tx.clone(),
+            };
 
-        self.handle.spawn_blocking(move || {
-            // Create the world
-            let font_resolver = font_resolver.wait().clone();
-            let world = LspWorldBuilder::build(entry_.clone(), font_resolver, inputs)
+            let (intr_tx, intr_rx) = mpsc::unbounded_channel();
+            let intr_tx_ = intr_tx.clone();
+            let enable_periscope = config.periscope_args.is_some();
+            let periscope_args = config.periscope_args.clone();
+            let max_diagnostics_per_file = config.max_diagnostics_per_file;
+            let lint_redefinitions = config.lint_redefinitions;
+            let watch_extensions = config.watch_extensions.clone();
+            let scan_ignored_files = config.scan_ignored_files;
+            let suppressed_diagnostics = config.suppressed_diagnostics.iter().cloned().collect();
+            let out_of_root_diagnostics = config.out_of_root_diagnostics;
+            let diagnostic_verbosity = config.diagnostic_verbosity;
+            let analysis_threads = config.analysis_threads;
+            let element_validators = element_validators_.lock().clone();
+            let font_resolver = config.determine_fonts();
+            let offline = config.offline;
+            // The workspace base is shared by the servers spawned for the client.
+            let workspace_base = workspace_base_.clone();
+            workspace_base.set_root(entry.root());
+            workspace_base.set_base(config.determine_workspace_base());
+            let paper_override = config.determine_paper_override();
+            let abort_on_panic = config.abort_on_panic;
+            let pending_interrupts = health_.pending_interrupts.clone();
+            let world_changes = Arc::new(AtomicUsize::new(0));
+            let inputs = inputs.clone();
+
+            log::info!(
+                "TypstActor: creating server for {diag_group}, entry: {entry:?}, inputs: {inputs:?}"
+            );
+
+            handle.spawn_blocking(move || {
+                // Create the world
+                let font_resolver = font_resolver.wait().clone();
+                let mut world = LspWorldBuilder::build(
+                    entry.clone(),
+                    font_resolver,
+                    inputs,
+                    workspace_base.clone(),
+                )
                 .expect("incorrect options");
+                world.registry.offline = offline;
 
-            // Create the compiler
-            let driver = CompileDriverInner::new(world);
-            let driver = CompileDriver {
-                inner: driver,
-                handler,
-                analysis: Analysis {
-                    position_encoding,
-                    root: Path::new("").into(),
-                    enable_periscope,
-                    caches: Default::default(),
-                },
-                periscope: PeriscopeRenderer::new(periscope_args.unwrap_or_default()),
-            };
+                // Create the compiler
+                let driver = CompileDriverInner::new(world);
+                let driver = CompileDriver {
+                    inner: driver,
+                    handler,
+                    analysis: Analysis {
+                        position_encoding,
+                        root: Path::new("").into(),
+                        enable_periscope,
+                        max_diagnostics_per_file,
+                        lint_redefinitions,
+                        scan_ignored_files,
+                        suppressed_diagnostics,
+                        out_of_root_diagnostics,
+                        diagnostic_verbosity,
+                        analysis_threads,
+                        element_validators,
+                        caches: Default::default(),
+                    },
+                    periscope: PeriscopeRenderer::new(periscope_args.unwrap_or_default()),
+                    revision: 0,
+                    world_changes: world_changes.clone(),
+                    prepared_env: None,
+                    stats: Default::default(),
+                    paper_override,
+                    paper_library: None,
+                    abort_on_panic,
+                };
 
-            // Create the actor
-            tokio::spawn(
-                CompileServerActor::new(driver, entry_, intr_tx, intr_rx)
-                    .with_watch(true)
-                    .spawn(),
-            );
+                // Create the actor
+                tokio::spawn(
+                    CompileServerActor::new(driver, entry, intr_tx, intr_rx)
+                        .with_watch(true)
+                        .with_watch_extensions(watch_extensions)
+                        .with_pending_interrupts(pending_interrupts)
+                        .with_world_changes(world_changes)
+                        .with_workspace_base(workspace_base)
+                        .spawn(),
+                );
+            });
+
+            intr_tx_
         });
 
         // Create the client
         let config = self.config.clone();
-        let client = CompileClientActor::new(editor_group, config, entry, intr_tx_, export_tx);
+        let intr_tx = spawner(&config, entry.clone());
+        let client = CompileClientActor::new(
+            editor_group,
+            config,
+            entry,
+            intr_tx,
+            export_tx,
+            self.editor_tx.clone(),
+            report_rx,
+            export_event_tx,
+            health,
+            spawner,
+        )
+        .with_documents(doc_version, history)
+        .with_page_changes(page_changes)
+        .with_diagnostics(diagnostics, last_warnings)
+        .with_exports(exports)
+        .with_element_validators(element_validators)
+        .with_workspace_base(workspace_base)
+        .with_untitled_mirrors(untitled_mirrors);
         // We do send memory changes instead of initializing compiler with them.
         // This is because there are state recorded inside of the compiler actor, and we
         // must update them.
@@ -150,6 +275,48 @@ impl TypstLanguageServer {
             .server(diag_group, entry, inputs, self.primary.vfs_snapshot())
     }
 
+    /// Restarts the dedicated compiler of the check entry, which is only
+    /// compiled for diagnostics, or stops it if no check entry is configured.
+    pub fn restart_check_server(&mut self) {
+        let previous = self
+            .dedicates
+            .iter()
+            .position(|v| v.compiler().diag_group == CHECK_DIAG_GROUP);
+        if let Some(previous) = previous {
+            let mut previous = self.dedicates.remove(previous);
+            if let Some(mut previous_server) = previous.compiler.take() {
+                std::thread::spawn(move || previous_server.settle());
+            }
+        }
+
+        let Some(entry) = self.config.compile.determine_check_entry_path() else {
+            return;
+        };
+
+        // The check entry is never exported.
+        let mut config = self.config.compile.clone();
+        config.export_pdf = ExportMode::Never;
+
+        let mut dedicate = CompileServer::new(
+            LspHost::new(Arc::new(RwLock::new(None))),
+            config,
+            self.primary.const_config().clone(),
+            self.primary.editor_tx.clone(),
+            self.primary.handle.clone(),
+        );
+        dedicate.memory_changes = self.primary.memory_changes.clone();
+        let entry = dedicate.config.determine_entry(Some(entry));
+        let inputs = dedicate.config.determine_inputs();
+        let server = dedicate.server(
+            CHECK_DIAG_GROUP.to_owned(),
+            entry,
+            inputs,
+            dedicate.vfs_snapshot(),
+        );
+        dedicate.compiler = Some(server);
+        self.dedicates.push(dedicate);
+    }
+
     pub fn run_format_thread(&mut self) {
         if self.format_thread.is_some() {
             log::error!("formatting thread is already started");
@@ -0,0 +1,258 @@
+//! Compiles and exports a document once without spawning the actors, for
+//! headless rendering embedded in other tools.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::{anyhow, Context};
+use tinymist_query::{analysis::Analysis, DiagnosticsMap, ExportKind, PositionEncoding};
+use tinymist_render::PeriscopeRenderer;
+use tokio::sync::{broadcast, mpsc, watch};
+use typst_ts_compiler::{
+    service::{CompileEnv, Compiler},
+    ShadowApi,
+};
+use typst_ts_core::{config::compiler::EntryState, Bytes, ImmutPath, TypstDocument};
+
+use super::{
+    editor::EditorRequest,
+    export::{ExportActor, ExportConfig},
+    typ_client::{CompileDriver, CompileHandler},
+    CompileDriverInner,
+};
+use crate::{
+    compiler_init::{CompileConfig, CompilerConstConfig},
+    world::{ImmutDict, LspWorldBuilder, WorkspaceBase},
+};
+
+/// The result of compiling and exporting a document once.
+#[derive(Debug, Clone)]
+pub struct CompileOnceOutput {
+    /// The diagnostics of the compilation, keyed by the files.
+    pub diagnostics: DiagnosticsMap,
+    /// The path of the exported file, which is None if the compilation fails.
+    /// A document exported into multiple files, such as a PDF split into
+    /// pages, is located by its first file.
+    pub output: Option<PathBuf>,
+    /// The path of the thumbnail exported alongside, if any.
+    pub thumbnail: Option<PathBuf>,
+}
+
+/// Compiles an entry once and exports it, without spawning the actors or
+/// watching the files.
+///
+/// The root, the fonts, the inputs and the export options are determined by
+/// the config as the server does, and the file is exported to the path
+/// substituted by `outputPath` regardless of the export mode. The failure of
+/// the compilation is reported in the diagnostics, while the failure of the
+/// export is returned as an error.
+pub fn compile_once(
+    entry: &Path,
+    kind: ExportKind,
+    config: &CompileConfig,
+) -> anyhow::Result<CompileOnceOutput> {
+    let entry = config.determine_entry(Some(entry.into()));
+    let (Some(root), Some(main)) = (entry.root(), entry.main()) else {
+        return Err(anyhow!("failed to determine the root of the entry"));
+    };
+    let path = main
+        .vpath()
+        .resolve(&root)
+        .context("the entry is not in the root")?;
+
+    // The diagnostics are not sent to any client.
+    let position_encoding = CompilerConstConfig::default().position_encoding;
+    let (mut driver, mut editor_rx) =
+        oneshot_driver(entry.clone(), root.clone(), config, position_encoding)?;
+
+    let mut env = CompileEnv::default();
+    env.tracer = Some(Default::default());
+    let doc = driver.compile(&mut env).ok();
+    let diagnostics = drain_diagnostics(&mut editor_rx).unwrap_or_default();
+
+    let Some(doc) = doc else {
+        return Ok(CompileOnceOutput {
+            diagnostics,
+            output: None,
+            thumbnail: None,
+        });
+    };
+
+    let mut deps = vec![];
+    driver.iter_dependencies(&mut |dep, _| deps.push(dep.clone()));
+
+    // The export actor is driven directly, whose channels are dropped here.
+    let (editor_tx, _) = mpsc::unbounded_channel();
+    let (_, export_rx) = mpsc::unbounded_channel();
+    let mut exporter = ExportActor {
+        group: "oneshot".to_owned(),
+        editor_tx,
+        export_rx,
+        doc_rx: watch::channel(None).1,
+        event_tx: broadcast::channel(1).0,
+        entry,
+        config: ExportConfig {
+            // The commands are only run by the export actors of the server.
+            post_export_command: None,
+            ..config.export_config()
+        },
+        entry_configs: Default::default(),
+        kind: kind.clone(),
+        count_words: false,
+        deps,
+        last_hashes: Default::default(),
+        workspace_base: WorkspaceBase::new(Some(root.clone()), config.determine_workspace_base()),
+        page_changes: Default::default(),
+        page_hashes: Default::default(),
+        targets: Default::default(),
+    };
+    let output = exporter.export(&kind, &doc, &root, &path, true)?;
+
+    Ok(CompileOnceOutput {
+        diagnostics,
+        output: Some(output.path),
+        thumbnail: output.thumbnail,
+    })
+}
+
+/// Compiles an entry once with the given files shadowed in memory, by a new
+/// compile driver which shares nothing with the running compilers, and returns
+/// the document along with the diagnostics.
+pub(crate) fn compile_snapshot(
+    entry: EntryState,
+    root: ImmutPath,
+    config: &CompileConfig,
+    inputs: Option<ImmutDict>,
+    files: HashMap<ImmutPath, Bytes>,
+    position_encoding: PositionEncoding,
+) -> anyhow::Result<(Option<Arc<TypstDocument>>, DiagnosticsMap)> {
+    let (mut driver, mut editor_rx) = oneshot_driver(entry, root, config, position_encoding)?;
+    if let Some(inputs) = inputs {
+        driver.inner.world_mut().inputs = inputs;
+    }
+    for (path, content) in files {
+        driver
+            .map_shadow(&path, content)
+            .map_err(|err| anyhow!("failed to map {path:?}: {err:?}"))?;
+    }
+
+    let mut env = CompileEnv::default();
+    env.tracer = Some(Default::default());
+    let doc = driver.compile(&mut env).ok();
+    let diagnostics = drain_diagnostics(&mut editor_rx).unwrap_or_default();
+
+    Ok((doc, diagnostics))
+}
+
+/// Creates a compile driver for an entry, along with the channel receiving its
+/// requests to the editor, which must be alive while compiling. The diagnostics
+/// are converted with the position encoding.
+pub(crate) fn oneshot_driver(
+    entry: EntryState,
+    root: ImmutPath,
+    config: &CompileConfig,
+    position_encoding: PositionEncoding,
+) -> anyhow::Result<(CompileDriver, mpsc::UnboundedReceiver<EditorRequest>)> {
+    // Create the world
+    let font_resolver = config.determine_fonts().wait().clone();
+    let workspace_base = WorkspaceBase::new(Some(root.clone()), config.determine_workspace_base());
+    let mut world = LspWorldBuilder::build(
+        entry,
+        font_resolver,
+        config.determine_inputs(),
+        workspace_base,
+    )
+    .map_err(|err| anyhow!("failed to create world: {err:?}"))?;
+    world.registry.offline = config.offline;
+
+    // Create the compiler, whose channels to other actors except the editor
+    // are dropped here
+    let (editor_tx, editor_rx) = mpsc::unbounded_channel();
+    let (export_tx, _) = mpsc::unbounded_channel();
+    let handler = CompileHandler {
+        #[cfg(feature = "preview")]
+        inner: Arc::new(parking_lot::Mutex::new(None)),
+        diag_group: "oneshot".to_owned(),
+        doc_tx: Arc::new(watch::channel(None).0),
+        report_tx: Arc::new(watch::channel(None).0),
+        health: Default::default(),
+        doc_version: Default::default(),
+        history: Default::default(),
+        page_changes: Default::default(),
+        diagnostics: Default::default(),
+        last_warnings: Default::default(),
+        untitled_mirrors: Default::default(),
+        export_tx,
+        editor_tx,
+    };
+    let driver = CompileDriver {
+        inner: CompileDriverInner::new(world),
+        handler,
+        analysis: Analysis {
+            position_encoding,
+            root,
+            enable_periscope: false,
+            max_diagnostics_per_file: config.max_diagnostics_per_file,
+            lint_redefinitions: config.lint_redefinitions,
+            scan_ignored_files: config.scan_ignored_files,
+            element_validators: vec![],
+            suppressed_diagnostics: config.suppressed_diagnostics.iter().cloned().collect(),
+            out_of_root_diagnostics: config.out_of_root_diagnostics,
+            diagnostic_verbosity: config.diagnostic_verbosity,
+            analysis_threads: config.analysis_threads,
+            caches: Default::default(),
+        },
+        periscope: PeriscopeRenderer::default(),
+        revision: 0,
+        world_changes: Default::default(),
+        prepared_env: None,
+        stats: Default::default(),
+        paper_override: config.determine_paper_override(),
+        paper_library: None,
+        abort_on_panic: config.abort_on_panic,
+    };
+
+    Ok((driver, editor_rx))
+}
+
+/// Takes the last diagnostics sent to the editor.
+pub(crate) fn drain_diagnostics(
+    editor_rx: &mut mpsc::UnboundedReceiver<EditorRequest>,
+) -> Option<DiagnosticsMap> {
+    let mut diagnostics = None;
+    while let Ok(req) = editor_rx.try_recv() {
+        if let EditorRequest::Diag(_, diags, _) = req {
+            diagnostics = diags;
+        }
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_once() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        std::fs::write(dir.join("main.typ"), "= Hello").unwrap();
+        std::fs::write(dir.join("broken.typ"), "#let x = ").unwrap();
+
+        let config = CompileConfig::default();
+        let main = compile_once(&dir.join("main.typ"), ExportKind::Pdf, &config);
+        let broken = compile_once(&dir.join("broken.typ"), ExportKind::Pdf, &config);
+        let exported = dir.join("main.pdf").exists();
+
+        let main = main.unwrap();
+        assert_eq!(main.output, Some(dir.join("main.pdf")));
+        assert!(exported);
+
+        let broken = broken.unwrap();
+        assert_eq!(broken.output, None);
+        assert!(broken.diagnostics.values().any(|diags| !diags.is_empty()));
+    }
+}
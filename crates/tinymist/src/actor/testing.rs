@@ -0,0 +1,57 @@
+//! Helpers to compile documents without spawning the actors, for testing.
+
+use std::path::Path;
+
+use anyhow::anyhow;
+use tinymist_query::DiagnosticsMap;
+use typst_ts_compiler::{
+    service::{CompileEnv, Compiler},
+    ShadowApi,
+};
+use typst_ts_core::{config::compiler::EntryState, ImmutPath};
+
+use super::oneshot::{drain_diagnostics, oneshot_driver};
+#[cfg(doc)]
+use super::typ_client::CompileDriver;
+use crate::compiler_init::{CompileConfig, CompilerConstConfig};
+
+/// Compiles a source text in memory and returns its diagnostics.
+///
+/// The source is compiled by a [`CompileDriver`] as a rootless entry, so no
+/// actor is spawned and no file is read from the disk for the main file. The
+/// diagnostics are collected the same way as they are sent to the editor.
+pub fn compile_diagnostics(src: &str, config: &CompileConfig) -> anyhow::Result<DiagnosticsMap> {
+    let entry_path: ImmutPath = Path::new("/._test_.typ").into();
+    let entry = EntryState::new_rootless(entry_path.clone())
+        .ok_or_else(|| anyhow!("failed to create entry for the source"))?;
+
+    let position_encoding = CompilerConstConfig::default().position_encoding;
+    let (mut driver, mut editor_rx) =
+        oneshot_driver(entry, Path::new("").into(), config, position_encoding)?;
+
+    driver
+        .map_shadow(&entry_path, src.as_bytes().to_vec().into())
+        .map_err(|err| anyhow!("failed to map the source: {err:?}"))?;
+
+    let mut env = CompileEnv::default();
+    env.tracer = Some(Default::default());
+    let _ = driver.compile(&mut env);
+
+    drain_diagnostics(&mut editor_rx).ok_or_else(|| anyhow!("no diagnostics are reported"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compile_diagnostics() {
+        let config = CompileConfig::default();
+
+        let diagnostics = compile_diagnostics("= Hello", &config).unwrap();
+        assert!(diagnostics.values().all(|diags| diags.is_empty()));
+
+        let diagnostics = compile_diagnostics("#let x = ", &config).unwrap();
+        assert!(diagnostics.values().any(|diags| !diags.is_empty()));
+    }
+}
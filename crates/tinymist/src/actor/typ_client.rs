@@ -26,49 +26,71 @@
 //! information to other actors.
 
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap, HashSet, VecDeque},
     ops::Deref,
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Weak,
+    },
+    time::{Duration, Instant},
 };
 
-use anyhow::{anyhow, bail};
+use anyhow::{anyhow, bail, Context};
+use comemo::Prehashed;
 use log::{error, info, trace};
-use parking_lot::Mutex;
+use lsp_types::{DiagnosticSeverity, Url};
+use once_cell::sync::OnceCell;
+use parking_lot::{Mutex, MutexGuard};
+use serde::Serialize;
 use tinymist_query::{
     analysis::{Analysis, AnalysisContext, AnalysisResources},
-    DiagnosticsMap, ExportKind, ServerInfoResponse, VersionedDocument,
+    lsp_typst_boundary::{lsp_to_typst, normalize_path, path_to_url},
+    syntax::find_package_imports,
+    DiagnosticsMap, ElementValidator, ExportKind, PositionEncoding, ServerInfoResponse,
+    VersionedDocument,
 };
 use tinymist_render::PeriscopeRenderer;
-use tokio::sync::{mpsc, oneshot, watch};
+use tokio::sync::{broadcast, mpsc, oneshot, watch};
 use typst::{
     diag::{FileResult, PackageError, SourceDiagnostic, SourceResult},
-    layout::Position,
+    eval::Tracer,
+    foundations::{Bytes, Datetime, Smart, Value},
+    layout::{PageElem, Paper, Position},
     model::Document as TypstDocument,
-    syntax::package::PackageSpec,
-    World as TypstWorld,
+    syntax::{
+        package::{PackageManifest, PackageSpec},
+        FileId, Source, Span,
+    },
+    text::{Font, FontBook},
+    Library, World as TypstWorld,
 };
 use typst_ts_compiler::{
     service::{CompileDriverImpl, CompileEnv, CompileMiddleware, Compiler, EntryManager, EnvWorld},
-    vfs::notify::MemoryEvent,
+    vfs::notify::{FileChangeSet, FileSnapshot, MemoryEvent},
     Time,
 };
 use typst_ts_core::{
     config::compiler::EntryState, debug_loc::DataSource, error::prelude::*, typst::prelude::EcoVec,
-    Error, ImmutPath, TypstFont,
+    Error, ImmutPath, TypstDict, TypstFont,
 };
 
 use super::{
     editor::{EditorRequest, TinymistCompileStatusEnum},
-    export::ExportConfig,
+    export::{ExportConfig, ExportEvent, ExportState, ExportTargets, ExportedFiles},
+    oneshot::compile_snapshot,
     typ_server::{is_inactive, CompileServerActor, Interrupt},
 };
 use crate::{
     actor::export::ExportRequest,
     compiler_init::CompileConfig,
+    tools::package::{
+        describe_package_error, prefetch_packages, resolve_package, PackagePrefetch,
+        ResolvedPackage,
+    },
     tools::preview::{CompilationHandle, CompileStatus},
     utils,
-    world::LspWorld,
+    world::{FontEntry, ImmutDict, LspRegistry, LspWorld, WorkspaceBase},
 };
 
 type CompileDriverInner = CompileDriverImpl<LspWorld>;
@@ -76,13 +98,308 @@ type CompileService = CompileServerActor<CompileDriver>;
 
 type EditorSender = mpsc::UnboundedSender<EditorRequest>;
 
+/// The health of a compiler shared with its client, which can be read without
+/// waiting for the compiler thread.
+#[derive(Default)]
+pub struct CompileHealthState {
+    /// The approximate number of interrupts that are not yet received.
+    pub pending_interrupts: Arc<AtomicUsize>,
+    /// The time when the latest successful compilation finished.
+    pub last_success: Mutex<Option<Instant>>,
+}
+
+/// The untitled buffers mirrored into the root, by the paths of their mirrors,
+/// which are shared by the client mirroring the buffers with the compiler
+/// reporting their diagnostics.
+#[derive(Default, Clone)]
+pub struct UntitledMirrors(Arc<Mutex<HashMap<ImmutPath, ImmutPath>>>);
+
+impl UntitledMirrors {
+    fn lock(&self) -> MutexGuard<'_, HashMap<ImmutPath, ImmutPath>> {
+        self.0.lock()
+    }
+
+    /// Moves the diagnostics of the mirrors of untitled buffers to the
+    /// buffers, which are the files opened by the client.
+    fn unmirror_diagnostics(&self, diagnostics: DiagnosticsMap) -> DiagnosticsMap {
+        let mirrors = self.lock();
+        if mirrors.is_empty() {
+            return diagnostics;
+        }
+
+        (diagnostics.into_iter())
+            .map(|(url, diags)| {
+                let path = url.to_file_path().ok();
+                let untitled = path.and_then(|path| mirrors.get(path.as_path()).cloned());
+                let untitled = untitled.and_then(|path| path_to_url(&path).ok());
+                (untitled.unwrap_or(url), diags)
+            })
+            .collect()
+    }
+}
+
+/// The pages changed between consecutive successfully compiled documents,
+/// which are compared by the hashes of their frames. It is computed once per
+/// compilation and shared by the consumers, such as the preview skipping the
+/// documents without changed pages, and the export of split PDFs writing only
+/// the changed pages.
+#[derive(Default)]
+pub struct PageChanges {
+    /// The document whose pages are hashed.
+    doc: Weak<TypstDocument>,
+    hashes: Vec<u128>,
+    latest: ChangedPages,
+}
+
+/// The pages changed by a successful compilation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ChangedPages {
+    /// The version of the compiled document, see `doc_version`.
+    pub version: usize,
+    /// The indices of the pages, starting from 0, which differ from the
+    /// previous document or are added. The removed pages are not included,
+    /// which are told by the number of pages.
+    pub changed: Vec<usize>,
+}
+
+impl PageChanges {
+    /// Compares the pages of a new document with the ones of the previous
+    /// document, returning whether any page is changed, added or removed.
+    pub fn update(&mut self, version: usize, doc: &Arc<TypstDocument>) -> bool {
+        let hashes = (doc.pages.iter())
+            .map(|page| typst::util::hash128(&page.frame))
+            .collect::<Vec<_>>();
+        let changed = (hashes.iter().enumerate())
+            .filter(|(idx, hash)| self.hashes.get(*idx) != Some(hash))
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+        let removed = hashes.len() < self.hashes.len();
+
+        self.doc = Arc::downgrade(doc);
+        self.hashes = hashes;
+        self.latest = ChangedPages { version, changed };
+        removed || !self.latest.changed.is_empty()
+    }
+
+    /// Gets the pages changed by the latest successful compilation.
+    pub fn latest(&self) -> &ChangedPages {
+        &self.latest
+    }
+
+    /// Gets the hashes of the pages of a document, which are only kept for the
+    /// latest document.
+    pub fn page_hashes(&self, doc: &TypstDocument) -> Option<&[u128]> {
+        // The address is only unique while the document is alive.
+        let latest = self.doc.strong_count() > 0 && std::ptr::eq(self.doc.as_ptr(), doc);
+        latest.then_some(self.hashes.as_slice())
+    }
+}
+
+/// A ring buffer of the recent successfully compiled documents, the oldest
+/// first, which is disabled if the capacity is zero.
+#[derive(Default)]
+pub struct DocumentHistory {
+    capacity: usize,
+    docs: VecDeque<VersionedDocument>,
+}
+
+impl DocumentHistory {
+    /// Changes the number of kept documents, dropping the oldest ones if there
+    /// are more.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.capacity = capacity;
+        while self.docs.len() > capacity {
+            self.docs.pop_front();
+        }
+    }
+
+    /// Keeps a document, dropping the oldest one if the history is full.
+    pub fn push(&mut self, doc: VersionedDocument) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.docs.len() == self.capacity {
+            self.docs.pop_front();
+        }
+        self.docs.push_back(doc);
+    }
+
+    /// Gets a kept document by its version.
+    pub fn get(&self, version: usize) -> Option<VersionedDocument> {
+        self.docs.iter().find(|doc| doc.version == version).cloned()
+    }
+
+    /// Gets the versions of the kept documents, the oldest first.
+    pub fn versions(&self) -> Vec<usize> {
+        self.docs.iter().map(|doc| doc.version).collect()
+    }
+}
+
+/// The health of a compiler actor.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileHealth {
+    /// Whether the interrupt channel to the compiler thread is open.
+    pub interrupt_channel_open: bool,
+    /// Whether the channel to the export actor is open.
+    pub export_channel_open: bool,
+    /// The approximate number of interrupts that are not yet received.
+    pub pending_interrupts: usize,
+    /// The time elapsed since the latest successful compilation.
+    pub since_last_success: Option<Duration>,
+    /// The version of the latest successfully compiled document.
+    pub doc_version: usize,
+}
+
+/// The current status of exporting a kind of files, such as PDF.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportStatus {
+    /// The extension of the exported files, e.g. `pdf`.
+    pub kind: String,
+    /// Whether the export is idle, running or failed.
+    pub state: ExportState,
+    /// The error message of the latest export if it failed.
+    pub error: Option<String>,
+    /// The time elapsed since the latest successful export.
+    pub since_last_success: Option<Duration>,
+}
+
+/// The location of a diagnostic, for navigating between diagnostics.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticLocation {
+    /// The file the diagnostic is in.
+    pub uri: Url,
+    /// The range of the diagnostic.
+    pub range: lsp_types::Range,
+    /// The severity of the diagnostic.
+    pub severity: Option<lsp_types::DiagnosticSeverity>,
+    /// The message of the diagnostic.
+    pub message: String,
+}
+
+impl DiagnosticLocation {
+    /// The key sorting the locations by file and position.
+    fn sort_key(&self) -> (&str, u32, u32) {
+        let start = self.range.start;
+        (self.uri.as_str(), start.line, start.character)
+    }
+}
+
+/// The diagnostics of a category across files, for an overview of the issues
+/// in the project.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiagnosticCategory {
+    /// The number of diagnostics in the category.
+    pub count: usize,
+    /// The locations of the first few diagnostics in the category, sorted by
+    /// file and position.
+    pub samples: Vec<DiagnosticLocation>,
+}
+
+/// The maximum number of sample locations kept for a category of diagnostics.
+const MAX_CATEGORY_SAMPLES: usize = 5;
+
+/// A report of a compilation.
+#[derive(Debug, Clone)]
+pub struct CompileReport {
+    /// The time taken by the compilation.
+    pub duration: Duration,
+    /// The number of pages in the document, or `None` if the compilation
+    /// failed.
+    pub page_count: Option<usize>,
+    /// The number of errors reported by the compilation.
+    pub error_count: usize,
+    /// The number of warnings reported by the compilation.
+    pub warning_count: usize,
+    /// Whether the compilation reuses the memoized results of a previous
+    /// compilation of the same entry, which are not cleared since then.
+    pub incremental: bool,
+}
+
+/// The overall status of a compilation and the following exports, which
+/// scripts map to the exit code of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase", tag = "kind", content = "count")]
+pub enum BatchStatus {
+    /// The document is compiled without errors and all the exports succeeded.
+    Ok,
+    /// The compilation reported some errors. The exports are not counted,
+    /// since they are skipped or based on a stale document.
+    CompileErrors(usize),
+    /// Some of the exports failed.
+    ExportErrors(usize),
+}
+
+impl BatchStatus {
+    /// Summarizes the errors in the diagnostics and the failed exports.
+    pub fn summarize(diagnostics: &DiagnosticsMap, exports: &[ExportEvent]) -> Self {
+        let compile_errors = diagnostics
+            .values()
+            .flatten()
+            .filter(|diag| diag.severity == Some(DiagnosticSeverity::ERROR))
+            .count();
+        let export_errors = exports.iter().filter(|e| e.error.is_some()).count();
+
+        if compile_errors > 0 {
+            Self::CompileErrors(compile_errors)
+        } else if export_errors > 0 {
+            Self::ExportErrors(export_errors)
+        } else {
+            Self::Ok
+        }
+    }
+
+    /// Gets the exit code of the status: 0 if ok, 1 on compile errors, and 2
+    /// on export errors.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            Self::Ok => 0,
+            Self::CompileErrors(_) => 1,
+            Self::ExportErrors(_) => 2,
+        }
+    }
+}
+
+/// The statistics of compilations of a driver.
+#[derive(Debug, Clone, Default)]
+pub struct CompileStats {
+    /// The entry compiled last, along with the number of times the memoized
+    /// results had been cleared before, see [`utils::cache_clears`].
+    pub last_compiled: Option<(EntryState, usize)>,
+    /// Whether the last compilation reuses the memoized results of a previous
+    /// compilation.
+    pub last_incremental: Option<bool>,
+    /// The number of compilations reusing the memoized results.
+    pub incremental: usize,
+    /// The number of compilations from scratch.
+    pub full: usize,
+}
+
 pub struct CompileHandler {
     pub(super) diag_group: String,
 
     #[cfg(feature = "preview")]
     pub(super) inner: Arc<Mutex<Option<typst_preview::CompilationHandleImpl>>>,
 
-    pub(super) doc_tx: watch::Sender<Option<Arc<TypstDocument>>>,
+    pub(super) doc_tx: Arc<watch::Sender<Option<Arc<TypstDocument>>>>,
+    pub(super) report_tx: Arc<watch::Sender<Option<CompileReport>>>,
+    pub(super) health: Arc<CompileHealthState>,
+    /// The version of the latest successfully compiled document, which is
+    /// increased on every successful compilation.
+    pub(super) doc_version: Arc<AtomicUsize>,
+    /// The recent successfully compiled documents, versioned by `doc_version`.
+    pub(super) history: Arc<Mutex<DocumentHistory>>,
+    /// The pages changed by the latest successful compilation, shared with the
+    /// export actor.
+    pub(super) page_changes: Arc<Mutex<PageChanges>>,
+    /// The latest diagnostics sent to the editor.
+    pub(super) diagnostics: Arc<Mutex<DiagnosticsMap>>,
+    /// The warnings of the latest successful compilation.
+    pub(super) last_warnings: Arc<Mutex<EcoVec<SourceDiagnostic>>>,
+    pub(super) untitled_mirrors: UntitledMirrors,
     pub(super) export_tx: mpsc::UnboundedSender<ExportRequest>,
     pub(super) editor_tx: EditorSender,
 }
@@ -96,7 +413,15 @@ impl CompilationHandle for CompileHandler {
     }
 
     fn notify_compile(&self, res: Result<Arc<TypstDocument>, CompileStatus>) {
+        let mut pages_changed = true;
         if let Ok(doc) = res.clone() {
+            *self.health.last_success.lock() = Some(Instant::now());
+            let version = self.doc_version.fetch_add(1, Ordering::SeqCst) + 1;
+            pages_changed = self.page_changes.lock().update(version, &doc);
+            self.history.lock().push(VersionedDocument {
+                version,
+                document: doc.clone(),
+            });
             let _ = self.doc_tx.send(Some(doc.clone()));
             let _ = self.export_tx.send(ExportRequest::OnTyped);
         }
@@ -112,18 +437,31 @@ impl CompilationHandle for CompileHandler {
             ))
             .unwrap();
 
+        // The preview has nothing to render again if no page is changed.
         #[cfg(feature = "preview")]
-        if let Some(inner) = self.inner.lock().as_ref() {
+        if let Some(inner) = self.inner.lock().as_ref().filter(|_| pages_changed) {
             inner.notify_compile(res);
         }
+        #[cfg(not(feature = "preview"))]
+        let _ = pages_changed;
     }
 }
 
 impl CompileHandler {
+    fn notify_report(&self, report: CompileReport) {
+        let _ = self.report_tx.send(Some(report));
+    }
+
     fn push_diagnostics(&mut self, diagnostics: Option<DiagnosticsMap>) {
-        let res = self
-            .editor_tx
-            .send(EditorRequest::Diag(self.diag_group.clone(), diagnostics));
+        let diagnostics = diagnostics.map(|d| self.untitled_mirrors.unmirror_diagnostics(d));
+        *self.diagnostics.lock() = diagnostics.clone().unwrap_or_default();
+
+        let version = self.doc_version.load(Ordering::SeqCst);
+        let res = self.editor_tx.send(EditorRequest::Diag(
+            self.diag_group.clone(),
+            diagnostics,
+            version,
+        ));
         if let Err(err) = res {
             error!("failed to send diagnostics: {err:#}");
         }
@@ -136,6 +474,28 @@ pub struct CompileDriver {
     pub(super) handler: CompileHandler,
     pub(super) analysis: Analysis,
     pub(super) periscope: PeriscopeRenderer,
+    /// The number of compilations, which is increased before every compilation
+    /// since the world may have changed.
+    ///
+    /// The compiler actor always compiles pending changes before executing
+    /// tasks, so the world is unchanged between compilations.
+    pub(super) revision: usize,
+    /// The number of memory and file system changes applied to the world,
+    /// shared with the compiler actor.
+    pub(super) world_changes: Arc<AtomicUsize>,
+    /// The entry, the revision and the number of changes of the world for
+    /// which the environment was prepared by the last analysis.
+    pub(super) prepared_env: Option<(EntryState, usize, usize)>,
+    /// The statistics of compilations, telling whether they are incremental.
+    pub(super) stats: CompileStats,
+    /// The paper overriding the default page size of the document.
+    pub(super) paper_override: Option<Paper>,
+    /// The library with the default page size set to `paper_override`, along
+    /// with the hash of the library of the world it is built from.
+    pub(super) paper_library: Option<(u128, Arc<Prehashed<Library>>)>,
+    /// Whether to let panics of the compilations and the analyses unwind the
+    /// compiler thread, instead of reporting them as errors.
+    pub(super) abort_on_panic: bool,
 }
 
 impl CompileMiddleware for CompileDriver {
@@ -150,6 +510,7 @@ impl CompileMiddleware for CompileDriver {
     }
 
     fn wrap_compile(&mut self, env: &mut CompileEnv) -> SourceResult<Arc<typst::model::Document>> {
+        self.revision += 1;
         self.handler
             .editor_tx
             .send(EditorRequest::Status(
@@ -158,35 +519,215 @@ impl CompileMiddleware for CompileDriver {
             ))
             .unwrap();
         self.handler.status(CompileStatus::Compiling);
-        match self.inner_mut().compile(env) {
+        let start = Instant::now();
+        let cache_clears = utils::cache_clears();
+        let res = if self.abort_on_panic {
+            self.compile_document(env)
+        } else {
+            utils::catch_panic(|| self.compile_document(env)).unwrap_or_else(|msg| {
+                self.reset_after_panic();
+                let msg = format!("internal error: the compiler panicked: {msg}");
+                let diag = SourceDiagnostic::error(Span::detached(), msg)
+                    .with_hint("this is a bug of typst or tinymist, please report it");
+                Err(EcoVec::from([diag]))
+            })
+        };
+        // A cyclic import fails the compilation, whose whole cycle is reported
+        // along with the errors.
+        let res = res.map_err(|mut errors| {
+            errors.extend(self.import_cycle_errors());
+            errors
+        });
+        let warnings = env.tracer.as_ref().map(|e| e.clone().warnings());
+        let page_count = res.as_ref().ok().map(|doc| doc.pages.len());
+        let report = CompileReport {
+            duration: start.elapsed(),
+            page_count,
+            error_count: res.as_ref().map_or_else(|err| err.len(), |_| 0),
+            warning_count: warnings.as_ref().map_or(0, |w| w.len()),
+            incremental: self.count_compilation(cache_clears),
+        };
+
+        let res = match res {
             Ok(doc) => {
+                *self.handler.last_warnings.lock() = warnings.clone().unwrap_or_default();
                 self.handler.notify_compile(Ok(doc.clone()));
-                self.notify_diagnostics(
-                    EcoVec::new(),
-                    env.tracer.as_ref().map(|e| e.clone().warnings()),
-                );
+                self.notify_diagnostics(EcoVec::new(), warnings, Some(doc.as_ref()));
                 Ok(doc)
             }
             Err(err) => {
                 self.handler
                     .notify_compile(Err(CompileStatus::CompileError));
-                self.notify_diagnostics(err, env.tracer.as_ref().map(|e| e.clone().warnings()));
+                self.notify_diagnostics(err, warnings, None);
                 Err(EcoVec::new())
             }
-        }
+        };
+
+        self.handler.notify_report(report);
+        res
+    }
+}
+
+/// A world whose library sets the default page size to a paper.
+struct PaperWorld<'a> {
+    inner: &'a LspWorld,
+    library: &'a Prehashed<Library>,
+}
+
+/// Builds a library setting the default page size to the paper.
+fn paper_library(base: &Prehashed<Library>, paper: Paper) -> Prehashed<Library> {
+    let mut library = base.deref().clone();
+    library
+        .styles
+        .set(PageElem::set_width(Smart::Custom(paper.width().into())));
+    library
+        .styles
+        .set(PageElem::set_height(Smart::Custom(paper.height().into())));
+    Prehashed::new(library)
+}
+
+impl<'a> TypstWorld for PaperWorld<'a> {
+    fn library(&self) -> &Prehashed<Library> {
+        self.library
+    }
+
+    fn book(&self) -> &Prehashed<FontBook> {
+        self.inner.book()
+    }
+
+    fn main(&self) -> Source {
+        self.inner.main()
+    }
+
+    fn source(&self, id: FileId) -> FileResult<Source> {
+        self.inner.source(id)
+    }
+
+    fn file(&self, id: FileId) -> FileResult<Bytes> {
+        self.inner.file(id)
+    }
+
+    fn font(&self, index: usize) -> Option<Font> {
+        self.inner.font(index)
+    }
+
+    fn today(&self, offset: Option<i64>) -> Option<Datetime> {
+        self.inner.today(offset)
     }
 }
 
 impl CompileDriver {
+    /// Compiles the document, overriding the default page size if a paper is
+    /// set.
+    fn compile_document(&mut self, env: &mut CompileEnv) -> SourceResult<Arc<TypstDocument>> {
+        match self.paper_library() {
+            Some(library) => self.compile_with_paper(&library, env),
+            None => self.inner_mut().compile(env),
+        }
+    }
+
+    /// Sets the paper overriding the default page size, building the library
+    /// with the paper once instead of on every compilation.
+    pub(super) fn set_paper_override(&mut self, paper: Option<Paper>) {
+        self.paper_override = paper;
+        self.paper_library = None;
+        self.paper_library();
+    }
+
+    /// Gets the library with the default page size set to the overriding
+    /// paper, which is built again only if the library of the world changes,
+    /// e.g. when the inputs are changed.
+    fn paper_library(&mut self) -> Option<Arc<Prehashed<Library>>> {
+        let paper = self.paper_override?;
+        let base = self.inner.world().library();
+        let hash = typst::util::hash128(base);
+        if let Some((built_from, library)) = &self.paper_library {
+            if *built_from == hash {
+                return Some(library.clone());
+            }
+        }
+
+        let library = Arc::new(paper_library(base, paper));
+        self.paper_library = Some((hash, library.clone()));
+        Some(library)
+    }
+
+    /// Resets the states which may be left inconsistent by a panic of the
+    /// compiler, so that the next compilation starts from scratch.
+    fn reset_after_panic(&mut self) {
+        utils::evict_all_caches();
+        self.inner.world_mut().reset();
+        self.analysis.caches = Default::default();
+        self.prepared_env = None;
+    }
+
+    /// Compiles the document with the default page size set to the paper,
+    /// without touching the sources. The page size set by the document itself
+    /// still takes precedence, since the library styles are the outermost.
+    fn compile_with_paper(
+        &mut self,
+        library: &Prehashed<Library>,
+        env: &mut CompileEnv,
+    ) -> SourceResult<Arc<TypstDocument>> {
+        self.inner.world_mut().prepare_env(env)?;
+
+        let world = PaperWorld {
+            inner: self.inner.world(),
+            library,
+        };
+        let mut tracer = env.tracer.take().unwrap_or_else(Tracer::new);
+        let res = typst::compile(&world, &mut tracer);
+        env.tracer = Some(tracer);
+        res.map(Arc::new)
+    }
+
+    /// Counts a compilation of the current entry, returning whether it reuses
+    /// the memoized results of a previous compilation of the same entry, i.e.
+    /// the results are not cleared since then, given the number of times they
+    /// had been cleared before the compilation.
+    fn count_compilation(&mut self, cache_clears: usize) -> bool {
+        let compiled = (self.inner.world().entry.clone(), cache_clears);
+        let incremental = self.stats.last_compiled.as_ref() == Some(&compiled);
+        if incremental {
+            self.stats.incremental += 1;
+        } else {
+            self.stats.full += 1;
+        }
+        self.stats.last_compiled = Some(compiled);
+        self.stats.last_incremental = Some(incremental);
+        incremental
+    }
+
+    /// Finds a cycle of the imports evaluated by the last compilation, which
+    /// are the imports in the files loaded by it.
+    fn import_cycle_errors(&self) -> EcoVec<SourceDiagnostic> {
+        let w = self.inner.world();
+        let Some(main) = w.main_id() else {
+            return EcoVec::new();
+        };
+
+        let mut loaded = HashSet::new();
+        self.inner.iter_dependencies(&mut |path, _| {
+            loaded.insert(normalize_path(path));
+        });
+        tinymist_query::import_cycle_diagnostics(w, main, |id| {
+            w.path_for_id(id)
+                .is_ok_and(|path| loaded.contains(&normalize_path(&path)))
+        })
+    }
+
     fn notify_diagnostics(
         &mut self,
         errors: EcoVec<SourceDiagnostic>,
         warnings: Option<EcoVec<SourceDiagnostic>>,
+        doc: Option<&TypstDocument>,
     ) {
         trace!("notify diagnostics: {errors:#?} {warnings:#?}");
 
         let diagnostics = self.run_analysis(|ctx| {
-            tinymist_query::convert_diagnostics(ctx, errors.iter().chain(warnings.iter().flatten()))
+            let diagnostics = errors.iter().chain(warnings.iter().flatten());
+            // The elements are only validated on successfully compiled documents.
+            tinymist_query::convert_diagnostics(ctx, diagnostics, doc)
         });
 
         match diagnostics {
@@ -218,14 +759,39 @@ impl CompileDriver {
             error!("TypstActor: root is not set");
             bail!("root is not set");
         };
-        w.source(main).map_err(|err| {
-            info!("TypstActor: failed to prepare main file: {err:?}");
-            anyhow!("failed to get source: {err}")
-        })?;
-        w.prepare_env(&mut Default::default()).map_err(|err| {
-            error!("TypstActor: failed to prepare env: {err:?}");
-            anyhow!("failed to prepare env")
-        })?;
+
+        // Skips preparing the environment again for consecutive analyses on the
+        // same entry and revision, unless the files are changed in between,
+        // e.g. when the compilation is paused.
+        let changes = self.world_changes.load(Ordering::SeqCst);
+        let prepared = self
+            .prepared_env
+            .as_ref()
+            .is_some_and(|(entry, revision, c)| {
+                *revision == self.revision && *c == changes && *entry == w.entry
+            });
+        if !prepared {
+            self.prepared_env = None;
+            w.source(main)
+                .map_err(|err| {
+                    info!("TypstActor: failed to prepare main file: {err:?}");
+                    err
+                })
+                .context("failed to get source")?;
+            w.prepare_env(&mut Default::default())
+                .map_err(|errors| {
+                    error!("TypstActor: failed to prepare env: {errors:?}");
+                    // The errors are diagnostics of typst, which carry the
+                    // causes in their messages and hints.
+                    let causes = errors.iter().map(|err| {
+                        let hints = err.hints.iter().map(|hint| format!(" (hint: {hint})"));
+                        format!("{}{}", err.message, hints.collect::<String>())
+                    });
+                    anyhow!(causes.collect::<Vec<_>>().join("; "))
+                })
+                .context("failed to prepare env")?;
+            self.prepared_env = Some((w.entry.clone(), self.revision, changes));
+        }
 
         struct WrapWorld<'a>(&'a mut LspWorld, &'a PeriscopeRenderer);
 
@@ -236,20 +802,22 @@ impl CompileDriver {
 
             fn resolve(&self, spec: &PackageSpec) -> Result<Arc<Path>, PackageError> {
                 use typst_ts_compiler::package::Registry;
-                self.0.registry.resolve(spec)
+                self.0
+                    .registry
+                    .resolve(spec)
+                    .map_err(|err| describe_package_error(&*self.0, spec, err))
             }
 
-            fn iter_dependencies<'b>(
-                &'b self,
-                f: &mut dyn FnMut(&'b ImmutPath, FileResult<&Time>),
-            ) {
+            fn iter_dependencies(&self, f: &mut dyn FnMut(&ImmutPath, FileResult<&Time>)) {
                 use typst_ts_compiler::NotifyApi;
-                self.0.iter_dependencies(f)
+                self.0.iter_dependencies(&mut |path, time| {
+                    f(&normalize_path(path).into(), time);
+                })
             }
 
             /// Resolve extra font information.
             fn font_info(&self, font: TypstFont) -> Option<Arc<DataSource>> {
-                self.0.font_resolver.inner.describe_font(&font)
+                self.0.font_resolver.describe_font(&font)
             }
 
             /// Resolve periscope image at the given position.
@@ -266,25 +834,182 @@ impl CompileDriver {
         let w = WrapWorld(w, &self.periscope);
 
         self.analysis.root = root;
-        Ok(f(&mut AnalysisContext::new_borrow(&w, &mut self.analysis)))
+        let mut ctx = AnalysisContext::new_borrow(&w, &mut self.analysis);
+        if self.abort_on_panic {
+            return Ok(f(&mut ctx));
+        }
+
+        let res = utils::catch_panic(|| f(&mut ctx));
+        drop(ctx);
+        res.map_err(|msg| {
+            // The caches may be left inconsistent by the panic.
+            self.analysis.caches = Default::default();
+            anyhow!("the analysis panicked: {msg}")
+        })
     }
 }
 
+/// Spawns a compile server for a client, returning the channel to send
+/// interrupts to the server.
+pub type CompileServerSpawner = Arc<
+    dyn Fn(&CompileConfig, EntryState) -> mpsc::UnboundedSender<Interrupt<CompileService>>
+        + Send
+        + Sync,
+>;
+
 pub struct CompileClientActor {
     pub diag_group: String,
     pub config: CompileConfig,
     entry: EntryState,
     intr_tx: mpsc::UnboundedSender<Interrupt<CompileService>>,
     export_tx: mpsc::UnboundedSender<ExportRequest>,
+    editor_tx: EditorSender,
+    report_rx: watch::Receiver<Option<CompileReport>>,
+    export_event_tx: broadcast::Sender<ExportEvent>,
+    health: Arc<CompileHealthState>,
+    /// The version of the latest successfully compiled document.
+    doc_version: Arc<AtomicUsize>,
+    /// The recent successfully compiled documents.
+    history: Arc<Mutex<DocumentHistory>>,
+    /// The pages changed by the latest successful compilation.
+    page_changes: Arc<Mutex<PageChanges>>,
+    /// The latest diagnostics sent to the editor.
+    diagnostics: Arc<Mutex<DiagnosticsMap>>,
+    /// The warnings of the latest successful compilation.
+    last_warnings: Arc<Mutex<EcoVec<SourceDiagnostic>>>,
+    /// The statuses of the exports, updated by the export actor.
+    exports: ExportTargets,
+    /// The validators checking the elements in the compiled documents, which
+    /// are kept for the servers spawned again, e.g. on restarts.
+    element_validators: Arc<Mutex<Vec<Arc<dyn ElementValidator>>>>,
+    /// The workspace base to resolve resources from, shared with the compiler
+    /// and the export actor.
+    workspace_base: WorkspaceBase,
+    untitled_mirrors: UntitledMirrors,
+    spawner: CompileServerSpawner,
+    /// The memory files sent to the server, which are sent again to a
+    /// restarted server.
+    memory_files: Mutex<HashMap<ImmutPath, FileSnapshot>>,
+    /// Whether the compilation is paused since the entry is not focused.
+    paused: bool,
+    /// The inputs set at runtime, which are set again to a restarted server.
+    input_overrides: Option<ImmutDict>,
+}
+
+/// The result of compiling with a file changed temporarily, see
+/// [`OverlaySnapshot::compile`].
+#[derive(Debug, Clone)]
+pub struct OverlayCompileOutput {
+    /// The document, which is None if the compilation fails.
+    pub document: Option<Arc<TypstDocument>>,
+    /// The diagnostics of the compilation, keyed by the files.
+    pub diagnostics: DiagnosticsMap,
+}
+
+/// A snapshot of an entry with a file changed temporarily, see
+/// [`CompileClientActor::overlay_snapshot`].
+pub struct OverlaySnapshot {
+    entry: EntryState,
+    root: ImmutPath,
+    config: CompileConfig,
+    inputs: Option<ImmutDict>,
+    /// The files in memory when the snapshot is taken.
+    files: HashMap<ImmutPath, Bytes>,
+    /// The changed file.
+    path: ImmutPath,
+    /// The range of the file replaced by the text, or None for the whole file.
+    range: Option<lsp_types::Range>,
+    text: String,
+}
+
+impl OverlaySnapshot {
+    /// Compiles the entry with the change, which blocks the calling thread.
+    ///
+    /// The entry is compiled by a new compiler, so the running compiler and
+    /// its state are untouched, and the change is discarded after compiling.
+    pub fn compile(
+        self,
+        position_encoding: PositionEncoding,
+    ) -> anyhow::Result<OverlayCompileOutput> {
+        let Self {
+            entry,
+            root,
+            config,
+            inputs,
+            mut files,
+            path,
+            range,
+            text,
+        } = self;
+
+        let text = match range {
+            Some(range) => {
+                let base = match files.get(&path) {
+                    Some(content) => String::from_utf8(content.to_vec())?,
+                    None => std::fs::read_to_string(&path)
+                        .with_context(|| format!("failed to read {path:?}"))?,
+                };
+                let mut source = Source::detached(base);
+                let range = lsp_to_typst::range(range, position_encoding, &source)
+                    .context("the range is out of the file")?;
+                source.edit(range, &text);
+                source.text().to_owned()
+            }
+            None => text,
+        };
+        files.insert(path, Bytes::from(text.into_bytes()));
+
+        let (document, diagnostics) =
+            compile_snapshot(entry, root, &config, inputs, files, position_encoding)?;
+        Ok(OverlayCompileOutput {
+            document,
+            diagnostics,
+        })
+    }
+}
+
+/// A handle to the result of a function run on the compiler thread, see
+/// [`CompileClientActor::try_steal`].
+pub struct StealHandle<Ret>(oneshot::Receiver<Ret>);
+
+impl<Ret: Send> StealHandle<Ret> {
+    /// Takes the result if the function has been run, without waiting.
+    ///
+    /// Returns `None` if the function is not run yet.
+    pub fn try_take(&mut self) -> Option<ZResult<Ret>> {
+        match self.0.try_recv() {
+            Ok(ret) => Some(Ok(ret)),
+            Err(oneshot::error::TryRecvError::Empty) => None,
+            Err(err) => Some(Err(map_string_err("failed to take stolen result")(err))),
+        }
+    }
+
+    /// Waits for the result, blocking the current thread.
+    pub fn wait(self) -> ZResult<Ret> {
+        utils::threaded_receive(self.0)
+    }
+
+    /// Waits for the result asynchronously.
+    pub async fn recv(self) -> ZResult<Ret> {
+        self.0
+            .await
+            .map_err(map_string_err("failed to receive stolen result"))
+    }
 }
 
 impl CompileClientActor {
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn new(
         diag_group: String,
         config: CompileConfig,
         entry: EntryState,
         intr_tx: mpsc::UnboundedSender<Interrupt<CompileService>>,
         export_tx: mpsc::UnboundedSender<ExportRequest>,
+        editor_tx: EditorSender,
+        report_rx: watch::Receiver<Option<CompileReport>>,
+        export_event_tx: broadcast::Sender<ExportEvent>,
+        health: Arc<CompileHealthState>,
+        spawner: CompileServerSpawner,
     ) -> Self {
         Self {
             diag_group,
@@ -292,9 +1017,296 @@ impl CompileClientActor {
             entry,
             intr_tx,
             export_tx,
+            editor_tx,
+            report_rx,
+            export_event_tx,
+            health,
+            doc_version: Default::default(),
+            history: Default::default(),
+            page_changes: Default::default(),
+            diagnostics: Default::default(),
+            last_warnings: Default::default(),
+            exports: Default::default(),
+            element_validators: Default::default(),
+            workspace_base: Default::default(),
+            untitled_mirrors: Default::default(),
+            spawner,
+            memory_files: Default::default(),
+            paused: false,
+            input_overrides: None,
+        }
+    }
+
+    /// Shares the version and the history of the successfully compiled
+    /// documents with the compiler.
+    pub(crate) fn with_documents(
+        mut self,
+        doc_version: Arc<AtomicUsize>,
+        history: Arc<Mutex<DocumentHistory>>,
+    ) -> Self {
+        self.doc_version = doc_version;
+        self.history = history;
+        self
+    }
+
+    /// Shares the pages changed by the latest successful compilation with the
+    /// compiler.
+    pub(crate) fn with_page_changes(mut self, page_changes: Arc<Mutex<PageChanges>>) -> Self {
+        self.page_changes = page_changes;
+        self
+    }
+
+    /// Shares the latest diagnostics and warnings with the compiler.
+    pub(crate) fn with_diagnostics(
+        mut self,
+        diagnostics: Arc<Mutex<DiagnosticsMap>>,
+        last_warnings: Arc<Mutex<EcoVec<SourceDiagnostic>>>,
+    ) -> Self {
+        self.diagnostics = diagnostics;
+        self.last_warnings = last_warnings;
+        self
+    }
+
+    /// Shares the statuses of the exports with the export actor.
+    pub(crate) fn with_exports(mut self, exports: ExportTargets) -> Self {
+        self.exports = exports;
+        self
+    }
+
+    /// Shares the validators with the spawner of the compilers.
+    pub(crate) fn with_element_validators(
+        mut self,
+        element_validators: Arc<Mutex<Vec<Arc<dyn ElementValidator>>>>,
+    ) -> Self {
+        self.element_validators = element_validators;
+        self
+    }
+
+    /// Shares the workspace base with the compiler and the export actor.
+    pub(crate) fn with_workspace_base(mut self, workspace_base: WorkspaceBase) -> Self {
+        self.workspace_base = workspace_base;
+        self
+    }
+
+    /// Shares the mirrors of untitled buffers with the compiler.
+    pub(crate) fn with_untitled_mirrors(mut self, untitled_mirrors: UntitledMirrors) -> Self {
+        self.untitled_mirrors = untitled_mirrors;
+        self
+    }
+
+    /// Restarts the compile server with the same configuration and entry.
+    ///
+    /// The current server is settled without waiting for it, since it may be
+    /// stuck. The subscriptions to documents and reports are kept.
+    pub fn restart_server(&mut self) {
+        info!("TypstActor({}): restarting server", self.diag_group);
+
+        let (tx, _) = oneshot::channel();
+        let _ = self.intr_tx.send(Interrupt::Settle(tx));
+
+        self.health.pending_interrupts.store(0, Ordering::Relaxed);
+        self.intr_tx = (self.spawner)(&self.config, self.entry.clone());
+
+        let memory_files = self.memory_files.lock().clone();
+        let snapshot = FileChangeSet::new_inserts(memory_files.into_iter().collect());
+        self.add_memory_changes(MemoryEvent::Update(snapshot));
+
+        if self.paused {
+            let _ = self.steal_inner(|c| c.set_paused(true));
+        }
+        if let Some(inputs) = self.input_overrides.clone() {
+            let _ = self.apply_inputs(inputs);
         }
     }
 
+    /// Marks whether the entry of the actor is focused by the editor. If
+    /// `compile_only_active` is enabled, an unfocused actor pauses compiling
+    /// until it is focused again.
+    pub fn set_active(&mut self, active: bool) {
+        let paused = self.config.compile_only_active && !active;
+        if paused == self.paused {
+            return;
+        }
+
+        info!("TypstActor({}): paused: {paused}", self.diag_group);
+        self.paused = paused;
+        let _ = self.steal_inner(move |c| c.set_paused(paused));
+    }
+
+    /// Whether the entry of the actor is focused by the editor.
+    pub fn is_focused_by(&self, path: Option<&Path>) -> bool {
+        let main = self
+            .entry
+            .main()
+            .and_then(|main| main.vpath().resolve(&self.entry.root()?));
+        path.is_some_and(|path| main.as_deref() == Some(path))
+    }
+
+    /// Gets the health of the actor without waiting for the compiler thread.
+    pub fn health(&self) -> CompileHealth {
+        CompileHealth {
+            interrupt_channel_open: !self.intr_tx.is_closed(),
+            export_channel_open: !self.export_tx.is_closed(),
+            pending_interrupts: self.health.pending_interrupts.load(Ordering::Relaxed),
+            since_last_success: self.health.last_success.lock().map(|t| t.elapsed()),
+            doc_version: self.current_doc_version(),
+        }
+    }
+
+    /// Gets the statuses of the exports run in background, sorted by the
+    /// kinds. A kind is listed after it is exported once.
+    pub fn export_status(&self) -> Vec<ExportStatus> {
+        let mut status = (self.exports.lock().iter())
+            .map(|(kind, target)| ExportStatus {
+                kind: kind.clone(),
+                state: target.state,
+                error: target.error.clone(),
+                since_last_success: target.last_success.map(|t| t.elapsed()),
+            })
+            .collect::<Vec<_>>();
+        status.sort_by(|a, b| a.kind.cmp(&b.kind));
+        status
+    }
+
+    /// Gets the version of the latest successfully compiled document, which the
+    /// latest diagnostics are published with.
+    pub fn current_doc_version(&self) -> usize {
+        self.doc_version.load(Ordering::SeqCst)
+    }
+
+    /// Gets the latest diagnostics sent to the editor.
+    pub fn diagnostics(&self) -> DiagnosticsMap {
+        self.diagnostics.lock().clone()
+    }
+
+    /// Summarizes the latest diagnostics and the given export events, e.g. the
+    /// ones received by [`Self::subscribe_exports`] during a batch export, to
+    /// a single status.
+    pub fn batch_status(&self, exports: &[ExportEvent]) -> BatchStatus {
+        BatchStatus::summarize(&self.diagnostics.lock(), exports)
+    }
+
+    /// Gets the locations of the latest diagnostics sent to the editor, sorted
+    /// by file and position. If `path` is given, only the diagnostics in the
+    /// file are returned.
+    pub fn sorted_diagnostics(&self, path: Option<&Path>) -> Vec<DiagnosticLocation> {
+        let uri = match path.map(path_to_url).transpose() {
+            Ok(uri) => uri,
+            Err(err) => {
+                error!("TypstActor: failed to convert path to uri: {err:#}");
+                return vec![];
+            }
+        };
+
+        let diagnostics = self.diagnostics.lock();
+        let mut locations = diagnostics
+            .iter()
+            .filter(|(file, _)| uri.as_ref().map_or(true, |uri| uri == *file))
+            .flat_map(|(file, diags)| {
+                diags.iter().map(|diag| DiagnosticLocation {
+                    uri: file.clone(),
+                    range: diag.range,
+                    severity: diag.severity,
+                    message: diag.message.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        locations.sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+        locations
+    }
+
+    /// Groups the latest diagnostics sent to the editor by their categories,
+    /// counting the diagnostics in each category across files.
+    ///
+    /// It only reads the cached diagnostics without waiting for the compiler
+    /// thread, so it is always in sync with the editor.
+    pub fn diagnostics_by_category(&self) -> HashMap<String, DiagnosticCategory> {
+        let mut categories = HashMap::<_, DiagnosticCategory>::new();
+        {
+            let diagnostics = self.diagnostics.lock();
+            for (file, diags) in diagnostics.iter() {
+                for diag in diags {
+                    let category = categories
+                        .entry(tinymist_query::diagnostic_category(diag))
+                        .or_insert_with(|| DiagnosticCategory {
+                            count: 0,
+                            samples: vec![],
+                        });
+                    category.count += 1;
+                    category.samples.push(DiagnosticLocation {
+                        uri: file.clone(),
+                        range: diag.range,
+                        severity: diag.severity,
+                        message: diag.message.clone(),
+                    });
+                }
+            }
+        }
+
+        for category in categories.values_mut() {
+            category
+                .samples
+                .sort_by(|a, b| a.sort_key().cmp(&b.sort_key()));
+            category.samples.truncate(MAX_CATEGORY_SAMPLES);
+        }
+        categories
+    }
+
+    /// Gets the warnings of the latest successful compilation without waiting
+    /// for the compiler thread. It is empty before the first successful
+    /// compilation.
+    pub fn last_warnings(&self) -> EcoVec<SourceDiagnostic> {
+        self.last_warnings.lock().clone()
+    }
+
+    /// Registers a validator checking the elements in the compiled documents,
+    /// whose issues are reported with the diagnostics since the next
+    /// compilation.
+    pub fn add_element_validator(&self, validator: Arc<dyn ElementValidator>) -> ZResult<()> {
+        self.element_validators.lock().push(validator.clone());
+        self.steal(move |c| {
+            c.compiler
+                .compiler
+                .analysis
+                .element_validators
+                .push(validator);
+        })?;
+        self.send_interrupt(Interrupt::Compile)
+            .map_err(|err| error_once!("failed to send interrupt", err: err))
+    }
+
+    fn send_interrupt(
+        &self,
+        intr: Interrupt<CompileService>,
+    ) -> Result<(), mpsc::error::SendError<Interrupt<CompileService>>> {
+        let pending = &self.health.pending_interrupts;
+        pending.fetch_add(1, Ordering::Relaxed);
+        self.intr_tx.send(intr).map_err(|err| {
+            pending.fetch_sub(1, Ordering::Relaxed);
+            err
+        })
+    }
+
+    /// Gets the pages changed by the latest successful compilation, along with
+    /// the version of the document. All pages are changed on the first
+    /// compilation.
+    pub fn changed_pages(&self) -> ChangedPages {
+        self.page_changes.lock().latest().clone()
+    }
+
+    /// Subscribes to the reports of compilations, which holds the report of
+    /// the latest compilation.
+    pub fn subscribe_reports(&self) -> watch::Receiver<Option<CompileReport>> {
+        self.report_rx.clone()
+    }
+
+    /// Subscribes the events of exports, including the ones triggered on
+    /// saving and typing.
+    pub fn subscribe_exports(&self) -> broadcast::Receiver<ExportEvent> {
+        self.export_event_tx.subscribe()
+    }
+
     fn steal_inner<Ret: Send + 'static>(
         &self,
         f: impl FnOnce(&mut CompileService) -> Ret + Send + 'static,
@@ -309,8 +1321,7 @@ impl CompileClientActor {
             }
         });
 
-        self.intr_tx
-            .send(Interrupt::Task(task))
+        self.send_interrupt(Interrupt::Task(task))
             .map_err(map_string_err("failed to send steal request"))?;
 
         Ok(rx)
@@ -334,6 +1345,50 @@ impl CompileClientActor {
             .map_err(map_string_err("failed to call steal_async"))
     }
 
+    /// Steals the compiler thread to run the given function without waiting
+    /// for it, returning a handle to the result.
+    ///
+    /// If the handle is dropped before the function is run, e.g. when the
+    /// request is cancelled, the function is skipped.
+    pub fn try_steal<Ret: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut CompileService) -> Ret + Send + 'static,
+    ) -> ZResult<StealHandle<Ret>> {
+        let (tx, rx) = oneshot::channel();
+
+        let task = Box::new(move |this: &mut CompileService| {
+            if tx.is_closed() {
+                return;
+            }
+            let _ = tx.send(f(this));
+        });
+
+        self.send_interrupt(Interrupt::Task(task))
+            .map_err(map_string_err("failed to send steal request"))?;
+
+        Ok(StealHandle(rx))
+    }
+
+    /// Like [`Self::try_steal`], but refuses to steal the compiler thread and
+    /// returns `None` if more than `max_pending` interrupts are waiting for the
+    /// compiler thread, i.e. the compiler is busy.
+    pub fn try_steal_if_idle<Ret: Send + 'static>(
+        &self,
+        max_pending: usize,
+        f: impl FnOnce(&mut CompileService) -> Ret + Send + 'static,
+    ) -> ZResult<Option<StealHandle<Ret>>> {
+        let pending = self.health.pending_interrupts.load(Ordering::Relaxed);
+        if pending > max_pending {
+            log::debug!(
+                "TypstActor({}): busy with {pending} pending interrupts, skip stealing",
+                self.diag_group
+            );
+            return Ok(None);
+        }
+
+        self.try_steal(f).map(Some)
+    }
+
     pub fn steal_state<T: Send + Sync + 'static>(
         &self,
         f: impl FnOnce(&mut AnalysisContext, Option<VersionedDocument>) -> T + Send + Sync + 'static,
@@ -356,7 +1411,7 @@ impl CompileClientActor {
         let _ = self.change_entry(None);
         info!("TypstActor({}): settle requested", self.diag_group);
         let (tx, rx) = oneshot::channel();
-        let _ = self.intr_tx.send(Interrupt::Settle(tx));
+        let _ = self.send_interrupt(Interrupt::Settle(tx));
         match utils::threaded_receive(rx) {
             Ok(()) => info!("TypstActor({}): settled", self.diag_group),
             Err(err) => error!("TypstActor({}): failed to settle: {err:#}", self.diag_group),
@@ -364,13 +1419,146 @@ impl CompileClientActor {
     }
 
     pub fn sync_config(&mut self, config: CompileConfig) {
+        if config.offline != self.config.offline {
+            let offline = config.offline;
+            info!("TypstActor({}): offline mode: {offline}", self.diag_group);
+            let _ = self.steal_inner(move |c| {
+                c.compiler.world_mut().registry.offline = offline;
+            });
+            let _ = self.send_interrupt(Interrupt::Compile);
+        }
+
+        if config.workspace_base != self.config.workspace_base {
+            let workspace_base = config.determine_workspace_base();
+            info!(
+                "TypstActor({}): workspace base: {workspace_base:?}",
+                self.diag_group
+            );
+            self.workspace_base.set_base(workspace_base);
+            let _ = self.steal_inner(move |c| {
+                // The resources are read again from the new base.
+                c.compiler.world_mut().reset();
+            });
+            // Syncs the watched files on the next compilation.
+            let _ = self.send_interrupt(Interrupt::Compile);
+        }
+
+        if config.watch_extensions != self.config.watch_extensions {
+            let watch_extensions = config.watch_extensions.clone();
+            info!(
+                "TypstActor({}): watch extensions: {watch_extensions:?}",
+                self.diag_group
+            );
+            let _ = self.steal_inner(move |c| {
+                c.set_watch_extensions(watch_extensions);
+            });
+            // Syncs the watched files on the next compilation.
+            let _ = self.send_interrupt(Interrupt::Compile);
+        }
+
+        if config.scan_ignored_files != self.config.scan_ignored_files {
+            let scan_ignored_files = config.scan_ignored_files;
+            let _ = self.steal_inner(move |c| {
+                let analysis = &mut c.compiler.compiler.analysis;
+                analysis.scan_ignored_files = scan_ignored_files;
+                // The scanned files are cached.
+                analysis.caches = Default::default();
+            });
+        }
+
+        if config.paper_override != self.config.paper_override {
+            let paper_override = config.determine_paper_override();
+            info!(
+                "TypstActor({}): paper override: {paper_override:?}",
+                self.diag_group
+            );
+            let _ = self.steal_inner(move |c| {
+                c.compiler.compiler.set_paper_override(paper_override);
+            });
+            let _ = self.send_interrupt(Interrupt::Compile);
+        }
+
+        if config.out_of_root_diagnostics != self.config.out_of_root_diagnostics {
+            let out_of_root = config.out_of_root_diagnostics;
+            let _ = self.steal_inner(move |c| {
+                c.compiler.compiler.analysis.out_of_root_diagnostics = out_of_root;
+            });
+            let _ = self.send_interrupt(Interrupt::Compile);
+        }
+
+        if config.diagnostic_verbosity != self.config.diagnostic_verbosity {
+            let verbosity = config.diagnostic_verbosity;
+            let _ = self.steal_inner(move |c| {
+                c.compiler.compiler.analysis.diagnostic_verbosity = verbosity;
+            });
+            let _ = self.send_interrupt(Interrupt::Compile);
+        }
+
+        if config.abort_on_panic != self.config.abort_on_panic {
+            let abort_on_panic = config.abort_on_panic;
+            let _ = self.steal_inner(move |c| {
+                c.compiler.compiler.abort_on_panic = abort_on_panic;
+            });
+        }
+
+        if config.document_history_size != self.config.document_history_size {
+            let size = config.document_history_size;
+            self.history.lock().set_capacity(size);
+        }
+
+        if config.analysis_threads != self.config.analysis_threads {
+            let analysis_threads = config.analysis_threads;
+            let _ = self.steal_inner(move |c| {
+                c.compiler.compiler.analysis.analysis_threads = analysis_threads;
+            });
+        }
+
+        if config.suppressed_diagnostics != self.config.suppressed_diagnostics {
+            let suppressed = config.suppressed_diagnostics.iter().cloned().collect();
+            let _ = self.steal_inner(move |c| {
+                c.compiler.compiler.analysis.suppressed_diagnostics = suppressed;
+            });
+            // Reports the diagnostics again without the suppressed ones.
+            let _ = self.send_interrupt(Interrupt::Compile);
+        }
+
+        if config.max_diagnostics_per_file != self.config.max_diagnostics_per_file {
+            let max_diagnostics_per_file = config.max_diagnostics_per_file;
+            let _ = self.steal_inner(move |c| {
+                c.compiler.compiler.analysis.max_diagnostics_per_file = max_diagnostics_per_file;
+            });
+            // Reports the diagnostics again with the new limit.
+            let _ = self.send_interrupt(Interrupt::Compile);
+        }
+
+        if config.lint_redefinitions != self.config.lint_redefinitions {
+            let lint_redefinitions = config.lint_redefinitions;
+            let _ = self.steal_inner(move |c| {
+                c.compiler.compiler.analysis.lint_redefinitions = lint_redefinitions;
+            });
+            // Reports the diagnostics again with or without the lint.
+            let _ = self.send_interrupt(Interrupt::Compile);
+        }
+
+        let remirror = config.untitled_root != self.config.untitled_root;
+        let resume = self.paused && !config.compile_only_active;
         self.config = config;
+        if remirror {
+            self.remirror_untitled_files();
+        }
+        if resume {
+            self.set_active(true);
+        }
     }
 
     pub fn change_entry(&mut self, path: Option<ImmutPath>) -> Result<bool, Error> {
+        let path = path.map(|p| ImmutPath::from(normalize_path(&p)));
+        // An untitled buffer is compiled from its mirror in the root for
+        // untitled buffers if it is configured, and detached otherwise.
+        let is_untitled = |p: &Path| p.starts_with("/untitled");
         if path
             .as_deref()
-            .is_some_and(|p| !p.is_absolute() && !p.starts_with("/untitled"))
+            .is_some_and(|p| !p.is_absolute() && !is_untitled(p))
         {
             return Err(error_once!("entry file must be absolute", path: path.unwrap().display()));
         }
@@ -381,7 +1569,10 @@ impl CompileClientActor {
         }
 
         let diag_group = &self.diag_group;
-        info!("the entry file of TypstActor({diag_group}) is changing to {next_entry:?}");
+        let prev_entry = &self.entry;
+        info!(
+            "the entry file of TypstActor({diag_group}) is changing from {prev_entry:?} to {next_entry:?}"
+        );
 
         // todo
         let next = next_entry.clone();
@@ -403,38 +1594,285 @@ impl CompileClientActor {
         let next = next_entry.clone();
         let _ = self.export_tx.send(ExportRequest::ChangeExportPath(next));
 
+        let old = self.entry_uris();
         self.entry = next_entry;
+        let new = self.entry_uris();
+        let _ = self.editor_tx.send(EditorRequest::EntryChanged(
+            self.diag_group.clone(),
+            old,
+            new,
+        ));
 
         Ok(true)
     }
 
+    /// Gets the root and the main file of the current entry as LSP URIs.
+    fn entry_uris(&self) -> TinymistEntry {
+        TinymistEntry {
+            root: self.root_uri(),
+            main: self.main_uri(),
+        }
+    }
+
+    /// Gets the root of the current entry as an LSP URI.
+    pub fn root_uri(&self) -> Option<Url> {
+        let root = self.entry.root()?;
+        path_to_url(&root).ok()
+    }
+
+    /// Gets the main file of the current entry as an LSP URI.
+    pub fn main_uri(&self) -> Option<Url> {
+        let root = self.entry.root()?;
+        let main = self.entry.main()?;
+        // todo: package??
+        if main.package().is_some() {
+            return None;
+        }
+
+        let main = main.vpath().resolve(&root)?;
+        match self.untitled_mirrors.lock().get(main.as_path()) {
+            Some(untitled) => path_to_url(untitled).ok(),
+            None => path_to_url(&main).ok(),
+        }
+    }
+
+    /// Gets the location of the start of the main file, which is absent if no
+    /// entry is set or the main file is an untitled buffer.
+    pub fn main_location(&self) -> Option<lsp_types::Location> {
+        let uri = self.main_uri()?;
+        if uri.scheme() != "file" {
+            return None;
+        }
+
+        Some(lsp_types::Location {
+            uri,
+            range: lsp_types::Range::default(),
+        })
+    }
+
+    /// Gets the inputs of `sys.inputs` of the document.
+    pub fn inputs(&self) -> anyhow::Result<TypstDict> {
+        self.steal(|c| c.compiler.world().inputs.as_ref().deref().clone())
+            .map_err(|e| e.into())
+    }
+
+    /// Lists the fonts available to the compiler in the order of precedence.
+    pub fn list_fonts(&self) -> anyhow::Result<Vec<FontEntry>> {
+        self.steal(|c| c.compiler.world().font_resolver.list_fonts())
+            .map_err(|e| e.into())
+    }
+
+    /// Sets an input of `sys.inputs`, or removes it if the value is None, and
+    /// recompiles the document. The inputs set at runtime are kept when the
+    /// server is restarted.
+    pub fn set_input(&mut self, key: String, value: Option<String>) -> anyhow::Result<()> {
+        info!(
+            "TypstActor({}): set input {key}: {value:?}",
+            self.diag_group
+        );
+        let mut inputs = self.inputs()?;
+        match value {
+            Some(value) => inputs.insert(key.into(), Value::Str(value.into())),
+            None => {
+                let _ = inputs.take(&key);
+            }
+        }
+
+        let inputs = Arc::new(Prehashed::new(inputs));
+        self.input_overrides = Some(inputs.clone());
+        self.apply_inputs(inputs)
+    }
+
+    fn apply_inputs(&self, inputs: ImmutDict) -> anyhow::Result<()> {
+        self.steal_inner(move |c| {
+            c.compiler.world_mut().inputs = inputs;
+
+            // The analyses may depend on the inputs.
+            let cc = &mut c.compiler.compiler;
+            cc.analysis.caches = Default::default();
+            cc.prepared_env = None;
+        })?;
+        self.send_interrupt(Interrupt::Compile)
+            .map_err(|err| anyhow!("failed to send compile request: {err}"))
+    }
+
+    /// Rebuilds the font resolver and recompiles the document.
+    pub fn reload_fonts(&mut self) {
+        info!("TypstActor({}): reload fonts requested", self.diag_group);
+        self.config.fonts = OnceCell::new();
+        let fonts = self.config.determine_fonts();
+        let _ = self.send_interrupt(Interrupt::ReloadFonts(Box::new(
+            move |c: &mut CompileService| {
+                c.compiler.world_mut().font_resolver = fonts.wait().clone();
+            },
+        )));
+    }
+
     pub fn add_memory_changes(&self, event: MemoryEvent) {
-        let _ = self.intr_tx.send(Interrupt::Memory(event));
+        {
+            let mut memory_files = self.memory_files.lock();
+            if matches!(event, MemoryEvent::Sync(..)) {
+                memory_files.clear();
+            }
+            let (MemoryEvent::Sync(e) | MemoryEvent::Update(e)) = &event;
+            for path in &e.removes {
+                memory_files.remove(path);
+            }
+            for (path, file) in &e.inserts {
+                memory_files.insert(path.clone(), file.clone());
+            }
+        }
+
+        let event = self.mirror_untitled_files(event);
+        let _ = self.send_interrupt(Interrupt::Memory(event));
     }
 
-    pub(crate) fn change_export_pdf(&mut self, config: ExportConfig) {
+    /// Mirrors the changes of untitled buffers into the root for untitled
+    /// buffers, so that they can be compiled as rooted entries.
+    fn mirror_untitled_files(&self, mut event: MemoryEvent) -> MemoryEvent {
+        let mut mirrors = self.untitled_mirrors.lock();
+        if matches!(event, MemoryEvent::Sync(..)) {
+            mirrors.clear();
+        }
+        let (MemoryEvent::Sync(e) | MemoryEvent::Update(e)) = &mut event;
+
+        let removes = (e.removes.iter())
+            .filter_map(|path| self.config.determine_untitled_path(path))
+            .collect::<Vec<_>>();
+        for mirror in &removes {
+            mirrors.remove(mirror);
+        }
+        let inserts = (e.inserts.iter())
+            .filter_map(|(path, file)| {
+                let mirror = self.config.determine_untitled_path(path)?;
+                mirrors.insert(mirror.clone(), path.clone());
+                Some((mirror, file.clone()))
+            })
+            .collect::<Vec<_>>();
+
+        e.removes.extend(removes);
+        e.inserts.extend(inserts);
+        event
+    }
+
+    /// Moves the mirrors of untitled buffers into the root for untitled
+    /// buffers after it is changed, along with an untitled entry.
+    fn remirror_untitled_files(&mut self) {
+        let untitled_entry = {
+            let mirrors = self.untitled_mirrors.lock();
+            let main = self.entry.root().zip(self.entry.main());
+            let main = main.and_then(|(root, main)| main.vpath().resolve(&root));
+            main.and_then(|main| match mirrors.get(main.as_path()) {
+                Some(untitled) => Some(untitled.clone()),
+                None => main.starts_with("/untitled").then(|| main.as_path().into()),
+            })
+        };
+
+        let changes = {
+            let mut mirrors = self.untitled_mirrors.lock();
+            let removes = mirrors.drain().map(|(mirror, _)| mirror).collect();
+            let mut changes = FileChangeSet::new_removes(removes);
+            for (path, file) in self.memory_files.lock().iter() {
+                if let Some(mirror) = self.config.determine_untitled_path(path) {
+                    mirrors.insert(mirror.clone(), path.clone());
+                    changes.inserts.push((mirror, file.clone()));
+                }
+            }
+            changes
+        };
+        let _ = self.send_interrupt(Interrupt::Memory(MemoryEvent::Update(changes)));
+
+        if let Some(untitled) = untitled_entry {
+            if let Err(err) = self.change_entry(Some(untitled)) {
+                error!("TypstActor: failed to move the untitled entry: {err}");
+            }
+        }
+    }
+
+    /// Takes a snapshot of the entry and the files in memory, to compile the
+    /// entry speculatively with a file changed temporarily, such as to preview
+    /// an edit before applying it. The text replaces the range of the file, or
+    /// the whole file if the range is None.
+    pub fn overlay_snapshot(
+        &self,
+        path: &Path,
+        range: Option<lsp_types::Range>,
+        text: String,
+    ) -> anyhow::Result<OverlaySnapshot> {
+        let Some(root) = self.entry.root().filter(|_| self.entry.main().is_some()) else {
+            bail!("no entry to compile");
+        };
+
+        let files = (self.memory_files.lock().iter())
+            .filter_map(|(path, file)| Some((path.clone(), file.content().ok()?.clone())))
+            .collect::<HashMap<_, _>>();
+
+        Ok(OverlaySnapshot {
+            entry: self.entry.clone(),
+            root,
+            config: self.config.clone(),
+            inputs: self.input_overrides.clone(),
+            files,
+            path: path.into(),
+            range,
+            text,
+        })
+    }
+
+    /// Changes the default export config, which also applies to the fields
+    /// that the entries don't override.
+    pub(crate) fn change_export_config(&mut self, config: ExportConfig) {
         let _ = self.export_tx.send(ExportRequest::ChangeConfig(config));
     }
 
+    /// Changes the export config of the current entry.
+    #[allow(unused)]
+    pub(crate) fn change_export_pdf(&mut self, config: ExportConfig) {
+        let _ = self
+            .export_tx
+            .send(ExportRequest::ChangeEntryConfig(config));
+    }
+
     pub fn clear_cache(&self) {
         let _ = self.steal(|c| {
             c.compiler.compiler.analysis.caches = Default::default();
         });
     }
 
+    /// Evicts the cached periscope images, keeping the analysis caches.
+    pub fn clear_periscope_cache(&self) {
+        let dg = self.diag_group.clone();
+        let _ = self.steal(move |c| {
+            let freed = c.compiler.compiler.periscope.clear_cache();
+            info!("TypstActor({dg}): freed {freed} bytes of periscope images");
+        });
+    }
+
     pub fn collect_server_info(&self) -> anyhow::Result<HashMap<String, ServerInfoResponse>> {
         let dg = self.diag_group.clone();
         self.steal(move |c| {
             let cc = &c.compiler.compiler;
 
+            let analysis_usage = cc.analysis.estimated_memory_by_cache();
+            let mut estimated_memory_usage = HashMap::from_iter([
+                ("vfs".to_owned(), cc.world().vfs.memory_usage()),
+                ("analysis".to_owned(), analysis_usage.values().sum()),
+                ("periscope".to_owned(), cc.periscope.estimated_memory()),
+            ]);
+            estimated_memory_usage.extend(
+                analysis_usage
+                    .into_iter()
+                    .map(|(kind, size)| (format!("analysis.{kind}"), size)),
+            );
+
             let info = ServerInfoResponse {
                 root: cc.world().entry.root().map(|e| e.as_ref().to_owned()),
                 font_paths: cc.world().font_resolver.font_paths().to_owned(),
                 inputs: cc.world().inputs.as_ref().deref().clone(),
-                estimated_memory_usage: HashMap::from_iter([
-                    ("vfs".to_owned(), cc.world().vfs.memory_usage()),
-                    ("analysis".to_owned(), cc.analysis.estimated_memory()),
-                ]),
+                estimated_memory_usage,
+                last_compile_incremental: cc.stats.last_incremental,
+                incremental_compiles: cc.stats.incremental,
+                full_compiles: cc.stats.full,
             };
 
             HashMap::from_iter([(dg, info)])
@@ -442,18 +1880,245 @@ impl CompileClientActor {
         .map_err(|e| e.into())
     }
 
-    pub fn on_export(&self, kind: ExportKind, path: PathBuf) -> anyhow::Result<Option<PathBuf>> {
+    /// Compiles a string buffer that is not backed by any file.
+    ///
+    /// The buffer is compiled as a detached in-memory entry in a separate
+    /// world, so the compilation of the workspace is not disturbed.
+    pub fn compile_string(
+        &self,
+        src: String,
+        position_encoding: PositionEncoding,
+    ) -> anyhow::Result<(Option<Arc<TypstDocument>>, DiagnosticsMap)> {
+        let entry_path: ImmutPath = Path::new("/._string_.typ").into();
+        let entry = EntryState::new_rootless(entry_path.clone())
+            .ok_or_else(|| anyhow!("failed to create entry for the string buffer"))?;
+        let root = entry
+            .root()
+            .ok_or_else(|| anyhow!("failed to create entry for the string buffer"))?;
+
+        let files = HashMap::from([(entry_path, Bytes::from(src.into_bytes()))]);
+        let inputs = self.input_overrides.clone();
+        compile_snapshot(entry, root, &self.config, inputs, files, position_encoding)
+    }
+
+    /// Gets the source text of a file as the compiler sees it, which is the
+    /// in-memory content if the file is being edited. It is None unless the
+    /// file is shadowed in memory or loaded by the world.
+    pub fn source_text(&self, path: PathBuf) -> Option<String> {
+        let shadowed = self.memory_files.lock().contains_key(path.as_path());
+        let res = self.steal(move |c| {
+            if !shadowed {
+                let path = normalize_path(&path);
+                let mut loaded = false;
+                c.compiler
+                    .iter_dependencies(&mut |dep, _| loaded |= normalize_path(dep) == path);
+                if !loaded {
+                    return Ok(None);
+                }
+            }
+
+            c.compiler.compiler.run_analysis(|ctx| {
+                let source = ctx.source_by_path(&path).ok()?;
+                Some(source.text().to_owned())
+            })
+        });
+
+        res.map_err(anyhow::Error::from)
+            .and_then(|res| res)
+            .unwrap_or_else(|err| {
+                error!("TypstActor: failed to get source text: {err:#}");
+                None
+            })
+    }
+
+    /// Lists the files in the root which are likely to be the entries of the
+    /// project, which can be switched to by [`Self::change_entry`].
+    ///
+    /// The targets configured by `targets` are returned if any. Otherwise, the
+    /// entrypoints declared by `typst.toml` in the root and the source files
+    /// not imported by other files are detected.
+    pub fn list_targets(&self) -> anyhow::Result<Vec<PathBuf>> {
+        if !self.config.targets.is_empty() {
+            return Ok(self.config.targets.clone());
+        }
+
+        let root = self
+            .entry
+            .root()
+            .ok_or_else(|| anyhow!("no root to detect targets"))?;
+        self.steal_world(move |ctx| {
+            let mut targets = BTreeSet::new();
+
+            let manifest = std::fs::read_to_string(root.join("typst.toml")).ok();
+            if let Some(manifest) =
+                manifest.and_then(|m| toml::from_str::<PackageManifest>(&m).ok())
+            {
+                targets.insert(root.join(manifest.package.entrypoint.as_str()));
+                if let Some(template) = &manifest.template {
+                    let path = root.join(template.path.as_str());
+                    targets.insert(path.join(template.entrypoint.as_str()));
+                }
+            }
+
+            let files = ctx.source_files().clone();
+            let deps = ctx.module_dependencies();
+            let roots = files
+                .into_iter()
+                .filter(|id| deps.get(id).map_or(true, |dep| dep.dependents.is_empty()))
+                .collect::<Vec<_>>();
+            targets.extend(roots.into_iter().filter_map(|id| ctx.path_for_id(id).ok()));
+
+            targets.into_iter().collect()
+        })
+    }
+
+    /// Gets the packages imported by the source files in the workspace.
+    pub fn imported_packages(&self) -> anyhow::Result<Vec<PackageSpec>> {
+        self.steal_world(|ctx| {
+            let mut specs = HashSet::new();
+            for id in ctx.source_files().clone() {
+                if let Ok(source) = ctx.source_by_id(id) {
+                    specs.extend(find_package_imports(&source));
+                }
+            }
+
+            let mut specs = specs.into_iter().collect::<Vec<_>>();
+            specs.sort_by_cached_key(|spec| spec.to_string());
+            specs
+        })
+    }
+
+    /// Gets the package registry of the world, which shares the downloaded
+    /// packages with the world.
+    pub fn registry(&self) -> anyhow::Result<LspRegistry> {
+        self.steal(|c| c.compiler.world().registry.clone())
+    }
+
+    /// Resolves a package specification to the directory of the package,
+    /// telling the namespace and the version actually selected.
+    pub fn resolve_package(&self, spec: String) -> anyhow::Result<ResolvedPackage> {
+        let res = self.steal(move |c| resolve_package(c.compiler.world(), &spec))?;
+        res.map_err(|err| anyhow!("failed to resolve package: {err}"))
+    }
+
+    /// Downloads the packages imported by the workspace which are not cached
+    /// yet on a separate thread, calling `progress` before downloading each
+    /// package, see [`prefetch_packages`], and `done` with the result for each
+    /// package.
+    ///
+    /// Only the imports are collected on the compiler thread.
+    pub fn prefetch_packages(
+        &self,
+        progress: impl FnMut(&PackageSpec, usize, usize) + Send + 'static,
+        done: impl FnOnce(Vec<PackagePrefetch>) + Send + 'static,
+    ) -> anyhow::Result<()> {
+        if self.config.offline {
+            bail!("cannot prefetch packages in offline mode");
+        }
+
+        let specs = self.imported_packages()?;
+        let registry = self.registry()?;
+        info!(
+            "TypstActor({}): prefetching {} packages",
+            self.diag_group,
+            specs.len()
+        );
+        std::thread::spawn(move || done(prefetch_packages(&registry, specs, progress)));
+        Ok(())
+    }
+
+    pub fn on_export(
+        &self,
+        kind: ExportKind,
+        path: PathBuf,
+        force: bool,
+    ) -> anyhow::Result<Option<PathBuf>> {
         // todo: we currently doesn't respect the path argument...
         info!("CompileActor: on export: {}", path.display());
 
+        self.sync_export_dependencies(&kind)?;
+
         let (tx, rx) = oneshot::channel();
-        let _ = self.export_tx.send(ExportRequest::Oneshot(Some(kind), tx));
+        let _ = self
+            .export_tx
+            .send(ExportRequest::Oneshot(Some(kind), force, tx));
         let res: Option<PathBuf> = utils::threaded_receive(rx)?;
 
         info!("CompileActor: on export end: {path:?} as {res:?}");
         Ok(res)
     }
 
+    /// Exports the latest successfully compiled document as PDF in memory,
+    /// honoring the export config of the entry but writing nothing to the disk.
+    pub fn export_pdf_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        let mut files = self.on_export_bytes(ExportKind::Pdf)?;
+        if files.len() != 1 {
+            bail!("the PDF is split into {} files", files.len());
+        }
+        Ok(files.remove(0).1)
+    }
+
+    /// Exports the latest successfully compiled document in memory, returning
+    /// the names and the data of the exported files without writing to the
+    /// disk.
+    pub fn on_export_bytes(&self, kind: ExportKind) -> anyhow::Result<ExportedFiles> {
+        self.export_bytes_of(kind, None)
+    }
+
+    /// Gets the versions of the recent successfully compiled documents kept in
+    /// the history, the oldest first.
+    pub fn document_versions(&self) -> Vec<usize> {
+        self.history.lock().versions()
+    }
+
+    /// Exports a previous version of the document kept in the history in
+    /// memory, like [`Self::on_export_bytes`].
+    pub fn export_version_bytes(
+        &self,
+        version: usize,
+        kind: ExportKind,
+    ) -> anyhow::Result<ExportedFiles> {
+        let Some(doc) = self.history.lock().get(version) else {
+            bail!("the version {version} of the document is not kept in the history");
+        };
+
+        self.export_bytes_of(kind, Some(doc.document))
+    }
+
+    fn export_bytes_of(
+        &self,
+        kind: ExportKind,
+        doc: Option<Arc<TypstDocument>>,
+    ) -> anyhow::Result<ExportedFiles> {
+        self.sync_export_dependencies(&kind)?;
+
+        let (tx, rx) = oneshot::channel();
+        self.export_tx
+            .send(ExportRequest::OneshotBytes(kind, doc, tx))
+            .map_err(|_| anyhow!("the export actor is stopped"))?;
+
+        // The request is dropped if there is no document.
+        let res = utils::threaded_receive(rx)
+            .map_err(|_| anyhow!("no successfully compiled document to export"))?;
+        res
+    }
+
+    /// Sends the dependencies of the document to the export actor, which are
+    /// required to bundle the sources.
+    fn sync_export_dependencies(&self, kind: &ExportKind) -> anyhow::Result<()> {
+        if matches!(kind, ExportKind::Bundle { sources: true, .. }) {
+            let deps = self.steal(|c| {
+                let mut deps = vec![];
+                c.compiler
+                    .iter_dependencies(&mut |dep, _| deps.push(dep.clone()));
+                deps
+            })?;
+            let _ = self.export_tx.send(ExportRequest::ChangeDependencies(deps));
+        }
+
+        Ok(())
+    }
+
     pub fn on_save_export(&self, path: PathBuf) -> anyhow::Result<()> {
         info!("CompileActor: on save export: {}", path.display());
         let _ = self.export_tx.send(ExportRequest::OnSaved);
@@ -461,3 +2126,104 @@ impl CompileClientActor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tinymist_query::LspDiagnostic;
+
+    use super::*;
+
+    #[test]
+    fn test_page_changes() {
+        let page = |width| typst::layout::Page {
+            frame: typst::layout::Frame::soft(typst::layout::Size::new(
+                typst::layout::Abs::pt(width),
+                typst::layout::Abs::pt(100.),
+            )),
+            numbering: None,
+            number: 1,
+        };
+        let doc = |widths: &[f64]| TypstDocument {
+            pages: widths.iter().map(|width| page(*width)).collect(),
+            ..TypstDocument::default()
+        };
+
+        let mut changes = PageChanges::default();
+        let first = Arc::new(doc(&[100., 100.]));
+        assert!(changes.update(1, &first));
+        assert_eq!(changes.latest().changed, [0, 1]);
+        assert_eq!(changes.page_hashes(&first).map(<[_]>::len), Some(2));
+
+        let second = Arc::new(doc(&[100., 200., 100.]));
+        assert!(changes.update(2, &second));
+        assert_eq!(changes.latest().version, 2);
+        assert_eq!(changes.latest().changed, [1, 2]);
+        assert!(changes.page_hashes(&first).is_none());
+
+        assert!(!changes.update(3, &Arc::new(doc(&[100., 200., 100.]))));
+        assert!(changes.latest().changed.is_empty());
+        // Removing pages is a change, even if no page is changed.
+        assert!(changes.update(4, &Arc::new(doc(&[100.]))));
+        assert!(changes.latest().changed.is_empty());
+    }
+
+    #[test]
+    fn test_document_history() {
+        let doc = |version| VersionedDocument {
+            version,
+            document: Arc::new(TypstDocument::default()),
+        };
+
+        let mut history = DocumentHistory::default();
+        history.push(doc(1));
+        assert!(history.versions().is_empty());
+
+        history.set_capacity(2);
+        for version in 2..=4 {
+            history.push(doc(version));
+        }
+        assert_eq!(history.versions(), [3, 4]);
+        assert!(history.get(2).is_none());
+        assert_eq!(history.get(3).map(|doc| doc.version), Some(3));
+
+        history.set_capacity(1);
+        assert_eq!(history.versions(), [4]);
+    }
+
+    #[test]
+    fn test_batch_status() {
+        let uri = Url::parse("file:///main.typ").unwrap();
+        let diag = |severity| LspDiagnostic {
+            severity: Some(severity),
+            ..Default::default()
+        };
+        let export = |error: Option<&str>| ExportEvent {
+            kind: "pdf".to_owned(),
+            path: None,
+            thumbnail: None,
+            error: error.map(ToOwned::to_owned),
+            duration: Duration::ZERO,
+        };
+
+        let warnings =
+            DiagnosticsMap::from([(uri.clone(), vec![diag(DiagnosticSeverity::WARNING)])]);
+        let errors = DiagnosticsMap::from([(uri, vec![diag(DiagnosticSeverity::ERROR); 2])]);
+        let exports = [export(None), export(Some("failed"))];
+
+        assert_eq!(
+            BatchStatus::summarize(&warnings, &exports[..1]),
+            BatchStatus::Ok
+        );
+        assert_eq!(
+            BatchStatus::summarize(&warnings, &exports),
+            BatchStatus::ExportErrors(1)
+        );
+        assert_eq!(
+            BatchStatus::summarize(&errors, &exports),
+            BatchStatus::CompileErrors(2)
+        );
+        assert_eq!(BatchStatus::CompileErrors(2).exit_code(), 1);
+    }
+}
@@ -2,7 +2,15 @@
 //!
 //! Please check `tinymist::actor::typ_client` for architecture details.
 
-use std::{collections::HashSet, path::Path, sync::Arc, thread::JoinHandle};
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::JoinHandle,
+};
 
 use tinymist_query::VersionedDocument;
 use tokio::sync::{mpsc, oneshot};
@@ -13,7 +21,9 @@ use typst_ts_compiler::service::{
 };
 use typst_ts_compiler::vfs::notify::{FilesystemEvent, MemoryEvent, NotifyMessage};
 use typst_ts_compiler::ShadowApi;
-use typst_ts_core::{config::compiler::EntryState, TypstDocument, TypstFileId};
+use typst_ts_core::{config::compiler::EntryState, ImmutPath, TypstDocument, TypstFileId};
+
+use crate::world::WorkspaceBase;
 
 pub enum Interrupt<Ctx> {
     /// Compile anyway.
@@ -22,6 +32,11 @@ pub enum Interrupt<Ctx> {
     ///
     /// See [`CompileClient<Ctx>::steal`] for more information.
     Task(BorrowTask<Ctx>),
+    /// Reload fonts and compile anyway.
+    ///
+    /// The task swaps the font resolver of the world on the compiler thread, so
+    /// that the resolver is never changed during a compilation.
+    ReloadFonts(BorrowTask<Ctx>),
     /// Memory file changes.
     Memory(MemoryEvent),
     /// File system event.
@@ -51,6 +66,8 @@ struct TaggedMemoryEvent {
 
 struct SuspendState {
     suspended: bool,
+    /// Whether the compilation is paused since the entry is in background.
+    paused: bool,
     dirty: bool,
 }
 
@@ -60,6 +77,17 @@ pub struct CompileServerActor<C: Compiler> {
     pub compiler: CompileReporter<C>,
     /// Whether to enable file system watching.
     pub enable_watch: bool,
+    /// The extensions of the files under the root which are watched to trigger
+    /// compilations, even if they are not dependencies of the document.
+    watch_extensions: Vec<String>,
+    /// The root of the entry, under which the files are watched.
+    watch_root: Option<ImmutPath>,
+    /// The cached files under the root with the watched extensions, which is
+    /// collected again once the root or the extensions change.
+    watched_files: Option<HashSet<ImmutPath>>,
+    /// The workspace base of the world, whose files are watched in place of
+    /// the resources under the root.
+    workspace_base: WorkspaceBase,
 
     /// The current logical tick.
     logical_tick: usize,
@@ -81,6 +109,12 @@ pub struct CompileServerActor<C: Compiler> {
     intr_tx: mpsc::UnboundedSender<Interrupt<Self>>,
     /// Channel for receiving interrupts from the compiler thread.
     intr_rx: mpsc::UnboundedReceiver<Interrupt<Self>>,
+    /// The approximate number of interrupts sent by the client but not yet
+    /// received.
+    pending_interrupts: Arc<AtomicUsize>,
+    /// The number of memory and file system changes applied to the compiler,
+    /// which invalidate the states derived from the world.
+    world_changes: Arc<AtomicUsize>,
 
     suspend_state: SuspendState,
 }
@@ -102,6 +136,10 @@ where
 
             logical_tick: 1,
             enable_watch: false,
+            watch_extensions: vec![],
+            watch_root: entry.root(),
+            watched_files: None,
+            workspace_base: Default::default(),
             dirty_shadow_logical_tick: 0,
 
             estimated_shadow_files: Default::default(),
@@ -114,9 +152,12 @@ where
 
             intr_tx,
             intr_rx,
+            pending_interrupts: Default::default(),
+            world_changes: Default::default(),
 
             suspend_state: SuspendState {
                 suspended: is_inactive(&entry),
+                paused: false,
                 dirty: false,
             },
         }
@@ -137,6 +178,33 @@ where
         self
     }
 
+    /// Watches the files with the given extensions under the root.
+    pub fn with_watch_extensions(mut self, watch_extensions: Vec<String>) -> Self {
+        self.watch_extensions = watch_extensions;
+        self
+    }
+
+    /// Shares the counter of interrupts sent by the client, which is counted
+    /// down when the interrupts are received.
+    pub fn with_pending_interrupts(mut self, pending_interrupts: Arc<AtomicUsize>) -> Self {
+        self.pending_interrupts = pending_interrupts;
+        self
+    }
+
+    /// Shares the counter of memory and file system changes, which is counted
+    /// up when the changes are applied to the compiler.
+    pub fn with_world_changes(mut self, world_changes: Arc<AtomicUsize>) -> Self {
+        self.world_changes = world_changes;
+        self
+    }
+
+    /// Shares the workspace base of the world, whose root is changed along
+    /// with the entry.
+    pub fn with_workspace_base(mut self, workspace_base: WorkspaceBase) -> Self {
+        self.workspace_base = workspace_base;
+        self
+    }
+
     pub fn success_doc(&self) -> Option<VersionedDocument> {
         self.latest_success_doc
             .clone()
@@ -233,6 +301,15 @@ where
                     // Warp the logical clock by one.
                     self.logical_tick += 1;
 
+                    // File system events are not sent by the client.
+                    if !matches!(event, Interrupt::Fs(..)) {
+                        let _ = self.pending_interrupts.fetch_update(
+                            Ordering::Relaxed,
+                            Ordering::Relaxed,
+                            |n| n.checked_sub(1),
+                        );
+                    }
+
                     // If settle, stop the actor.
                     if let Interrupt::Settle(e) = event {
                         log::info!("CompileServerActor: requested stop");
@@ -270,23 +347,45 @@ where
 
     pub(crate) fn change_entry(&mut self, entry: EntryState) {
         self.suspend_state.suspended = is_inactive(&entry);
-        if !self.suspend_state.suspended && self.suspend_state.dirty {
-            self.intr_tx.send(Interrupt::Compile).ok();
-        }
+        self.watch_root = entry.root();
+        self.watched_files = None;
+        self.workspace_base.set_root(entry.root());
+        self.resume_if_dirty();
 
         // Reset the document state.
         self.latest_doc = None;
         self.latest_success_doc = None;
     }
 
+    /// Changes the extensions of the files watched under the root.
+    pub(crate) fn set_watch_extensions(&mut self, watch_extensions: Vec<String>) {
+        self.watch_extensions = watch_extensions;
+        self.watched_files = None;
+    }
+
+    /// Pauses or resumes the compilation. The documents and caches are kept
+    /// while paused, and the changes are compiled once resumed.
+    pub(crate) fn set_paused(&mut self, paused: bool) {
+        self.suspend_state.paused = paused;
+        self.resume_if_dirty();
+    }
+
+    fn resume_if_dirty(&mut self) {
+        let state = &self.suspend_state;
+        if !state.suspended && !state.paused && state.dirty {
+            self.intr_tx.send(Interrupt::Compile).ok();
+        }
+    }
+
     /// Compile the document.
     fn compile(&mut self, send: impl Fn(CompilerResponse)) {
         use CompilerResponse::*;
 
-        if self.suspend_state.suspended {
+        if self.suspend_state.suspended || self.suspend_state.paused {
             self.suspend_state.dirty = true;
             return;
         }
+        self.suspend_state.dirty = false;
 
         // Compile the document.
         let mut env = self.make_env(self.watch_feature_set.clone());
@@ -303,11 +402,81 @@ where
 
         // Notify the new file dependencies.
         let mut deps = vec![];
-        self.compiler
-            .iter_dependencies(&mut |dep, _| deps.push(dep.clone()));
+        let base = &self.workspace_base;
+        self.compiler.iter_dependencies(&mut |dep, _| {
+            let resolved = base.resolve(dep).map(ImmutPath::from);
+            deps.push(resolved.unwrap_or_else(|| dep.clone()));
+        });
+        deps.extend(self.watched_files().iter().cloned());
         send(Notify(NotifyMessage::SyncDependency(deps)));
     }
 
+    /// Gets the files under the root with the watched extensions, which are
+    /// collected on the first call and then kept up to date by the file system
+    /// events.
+    fn watched_files(&mut self) -> &HashSet<ImmutPath> {
+        let root = self.watch_root.clone();
+        let watched = self.watched_files.take().unwrap_or_else(|| {
+            let Some(root) = root.filter(|_| !self.watch_extensions.is_empty()) else {
+                return HashSet::new();
+            };
+
+            let is_hidden = |e: &walkdir::DirEntry| {
+                e.depth() > 0 && e.file_name().to_string_lossy().starts_with('.')
+            };
+            walkdir::WalkDir::new(root)
+                .follow_links(false)
+                .into_iter()
+                .filter_entry(|e| !is_hidden(e))
+                .filter_map(Result::ok)
+                .filter(|e| e.file_type().is_file() && self.is_watched(e.path()))
+                .map(|e| ImmutPath::from(e.into_path()))
+                .collect()
+        });
+        self.watched_files.insert(watched)
+    }
+
+    /// Checks whether a file is under the root, not hidden, and has one of the
+    /// watched extensions.
+    fn is_watched(&self, path: &Path) -> bool {
+        let Some(relative) =
+            (self.watch_root.as_ref()).and_then(|root| path.strip_prefix(root).ok())
+        else {
+            return false;
+        };
+        let hidden = relative
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+        let ext = path.extension().and_then(|ext| ext.to_str());
+        !hidden
+            && ext.is_some_and(|ext| {
+                self.watch_extensions
+                    .iter()
+                    .any(|watched| watched.eq_ignore_ascii_case(ext))
+            })
+    }
+
+    /// Updates the cached watched files by the changed files.
+    fn update_watched_files(&mut self, event: &FilesystemEvent) {
+        let changeset = match event {
+            FilesystemEvent::Update(changeset) => changeset,
+            FilesystemEvent::UpstreamUpdate { changeset, .. } => changeset,
+        };
+
+        let inserts = (changeset.inserts.iter())
+            .map(|(path, _)| path)
+            .filter(|path| self.is_watched(path))
+            .cloned()
+            .collect::<Vec<_>>();
+        let Some(watched) = self.watched_files.as_mut() else {
+            return;
+        };
+        for path in &changeset.removes {
+            watched.remove(path);
+        }
+        watched.extend(inserts);
+    }
+
     /// Process some interrupt. Return whether it needs compilation.
     fn process(&mut self, event: Interrupt<Self>, send: impl Fn(CompilerResponse)) -> bool {
         use CompilerResponse::*;
@@ -319,6 +488,11 @@ where
                 task(self);
                 false
             }
+            Interrupt::ReloadFonts(task) => {
+                log::info!("CompileServerActor: reload fonts");
+                task(self);
+                true
+            }
             Interrupt::Memory(event) => {
                 log::debug!("CompileServerActor: memory event incoming");
 
@@ -368,7 +542,10 @@ where
                 }
 
                 // Apply file system changes.
+                self.update_watched_files(&event);
+                self.unresolve_fs_event(&mut event);
                 self.compiler.notify_fs_event(event);
+                self.world_changes.fetch_add(1, Ordering::SeqCst);
 
                 true
             }
@@ -376,6 +553,22 @@ where
         }
     }
 
+    /// Maps the changed files under the workspace base back to the resources
+    /// under the root, since the files are watched in place of the resources.
+    fn unresolve_fs_event(&self, event: &mut FilesystemEvent) {
+        let changeset = match event {
+            FilesystemEvent::Update(changeset) => changeset,
+            FilesystemEvent::UpstreamUpdate { changeset, .. } => changeset,
+        };
+
+        let inserts = changeset.inserts.iter_mut().map(|(path, _)| path);
+        for path in changeset.removes.iter_mut().chain(inserts) {
+            if let Some(unresolved) = self.workspace_base.unresolve(path) {
+                *path = unresolved.into();
+            }
+        }
+    }
+
     /// Apply delayed memory changes to underlying compiler.
     fn apply_delayed_memory_changes(&mut self, event: &mut FilesystemEvent) -> Option<()> {
         // Handle delayed upstream update event before applying file system changes
@@ -399,6 +592,7 @@ where
 
     /// Apply memory changes to underlying compiler.
     fn apply_memory_changes(&mut self, event: MemoryEvent) {
+        self.world_changes.fetch_add(1, Ordering::SeqCst);
         if matches!(event, MemoryEvent::Sync(..)) {
             self.compiler.reset_shadow();
         }
@@ -88,6 +88,23 @@ impl<S> LspHost<S> {
         warn!("failed to send notification: {res:?}");
     }
 
+    /// Sends a batch of partial results of a request by the `$/progress`
+    /// notification, with the partial result token given by the request.
+    pub fn send_partial_result<T: Serialize>(&self, token: ProgressToken, value: T) {
+        let params = serde_json::json!({ "token": token, "value": value });
+        let not = lsp_server::Notification::new("$/progress".to_owned(), params);
+
+        let sender = self.sender.read();
+        let Some(sender) = sender.as_ref() else {
+            warn!("failed to send partial result: connection closed");
+            return;
+        };
+        let Err(res) = sender.send(not.into()) else {
+            return;
+        };
+        warn!("failed to send partial result: {res:?}");
+    }
+
     pub fn register_request(&self, request: &lsp_server::Request, request_received: Instant) {
         let mut req_queue = self.req_queue.lock();
         info!(
@@ -37,6 +37,9 @@ pub mod transport;
 mod utils;
 mod world;
 pub use crate::harness::LspHost;
+pub use actor::oneshot;
+pub use actor::testing;
+pub use actor::typ_client::BatchStatus;
 pub use server::compiler;
 pub use server::compiler_init;
 pub use server::lsp::*;
@@ -20,7 +20,7 @@ use tinymist::{
     compiler_init::{CompileInit, CompileInitializeParams},
     harness::{lsp_harness, InitializedLspDriver, LspDriver, LspHost},
     transport::with_stdio_transport,
-    CompileFontOpts, Init, LspWorld, TypstLanguageServer,
+    BatchStatus, CompileFontOpts, Init, LspWorld, TypstLanguageServer,
 };
 
 #[cfg(feature = "dhat-heap")]
@@ -211,6 +211,7 @@ pub fn compiler_main(args: CompileArgs) -> anyhow::Result<()> {
                         tinymist_query::convert_diagnostics(
                             ctx,
                             warnings.iter().flatten().chain(errors.iter()),
+                            None,
                         )
                     });
 
@@ -234,10 +235,13 @@ pub fn compiler_main(args: CompileArgs) -> anyhow::Result<()> {
             // tokio::fs::write(output, p).await.unwrap();
             // }
 
+            let status = BatchStatus::summarize(&diagnostics, &[]);
             lsp_server::Message::Response(lsp_server::Response {
                 id: 0.into(),
                 result: Some(serde_json::json!({
                     "tracingData": timings,
+                    "status": status,
+                    "exitCode": status.exit_code(),
                 })),
                 error: None,
             })
@@ -1,6 +1,7 @@
 use core::fmt;
 use std::{collections::HashMap, path::Path, sync::Arc, time::Instant};
 
+use base64::Engine;
 use crossbeam_channel::{select, Receiver};
 use log::{error, info, warn};
 use lsp_server::{ErrorCode, Message, Notification, Request, RequestId, Response, ResponseError};
@@ -15,12 +16,12 @@ use typst_ts_compiler::vfs::notify::FileChangeSet;
 use typst_ts_core::{config::compiler::DETACHED_ENTRY, ImmutPath};
 
 use crate::{
-    actor::{editor::EditorRequest, export::ExportConfig, typ_client::CompileClientActor},
+    actor::{editor::EditorRequest, typ_client::CompileClientActor},
     compiler_init::{CompileConfig, CompilerConstConfig},
     harness::InitializedLspDriver,
     internal_error, invalid_params, method_not_found, run_query,
     state::MemoryFileMeta,
-    LspHost, LspResult,
+    utils, LspHost, LspResult,
 };
 
 type LspMethod<Res> = fn(srv: &mut CompileServer, args: JsonValue) -> LspResult<Res>;
@@ -332,18 +333,9 @@ impl CompileServer {
             e.sync_config(self.config.clone());
         }
 
-        if config.output_path != self.config.output_path
-            || config.export_pdf != self.config.export_pdf
-        {
-            let config = ExportConfig {
-                substitute_pattern: self.config.output_path.clone(),
-                mode: self.config.export_pdf,
-            };
-
-            self.compiler
-                .as_mut()
-                .unwrap()
-                .change_export_pdf(config.clone());
+        if config.export_config() != self.config.export_config() {
+            let config = self.config.export_config();
+            self.compiler.as_mut().unwrap().change_export_config(config);
         }
 
         if config.primary_opts() != self.config.primary_opts() {
@@ -373,8 +365,19 @@ impl CompileServer {
             redirected_command!("tinymist.exportPdf", Self::export_pdf),
             redirected_command!("tinymist.exportSvg", Self::export_svg),
             redirected_command!("tinymist.exportPng", Self::export_png),
+            redirected_command!("tinymist.exportBundle", Self::export_bundle),
             redirected_command!("tinymist.doClearCache", Self::clear_cache),
+            redirected_command!("tinymist.reloadFonts", Self::reload_fonts),
+            redirected_command!("tinymist.restartServer", Self::restart_server),
             redirected_command!("tinymist.changeEntry", Self::change_entry),
+            redirected_command!("tinymist.setInput", Self::set_input),
+            redirected_command!("tinymist.getInputs", Self::get_inputs),
+            redirected_command!("tinymist.listFonts", Self::list_fonts),
+            redirected_command!("tinymist.getDocumentVersions", Self::get_document_versions),
+            redirected_command!(
+                "tinymist.exportDocumentVersion",
+                Self::export_document_version
+            ),
         ])
     }
 
@@ -409,12 +412,21 @@ impl CompileServer {
         self.export(ExportKind::Png { page: opts.page }, arguments)
     }
 
+    /// Export the current document and its sources as a zip archive.
+    pub fn export_bundle(&self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let opts = parse_bundle_opts(arguments.get(1))?;
+        let sources = !opts.no_sources;
+        let kinds = opts.kinds();
+        self.export(ExportKind::Bundle { sources, kinds }, arguments)
+    }
+
     /// Export the current document as some format. The client is responsible
     /// for passing the correct absolute path of typst document.
     pub fn export(&self, kind: ExportKind, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
         let path = parse_path(arguments.first())?.as_ref().to_owned();
+        let force = parse_force(arguments.get(1));
 
-        let res = run_query!(self.OnExport(path, kind))?;
+        let res = run_query!(self.OnExport(path, kind, force))?;
         let res = serde_json::to_value(res).map_err(|_| internal_error("Cannot serialize path"))?;
 
         Ok(res)
@@ -425,10 +437,107 @@ impl CompileServer {
     /// # Errors
     /// Errors if the cache could not be cleared.
     pub fn clear_cache(&self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
-        comemo::evict(0);
+        utils::evict_all_caches();
+        Ok(JsonValue::Null)
+    }
+
+    /// Reload fonts and recompile the document.
+    pub fn reload_fonts(&mut self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        self.config.fonts = OnceCell::new();
+        if let Some(compiler) = self.compiler.as_mut() {
+            compiler.reload_fonts();
+        }
+        Ok(JsonValue::Null)
+    }
+
+    /// Restart the compile server, keeping the subscriptions of the client.
+    pub fn restart_server(&mut self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        if let Some(compiler) = self.compiler.as_mut() {
+            compiler.restart_server();
+        }
+        Ok(JsonValue::Null)
+    }
+
+    /// Set an input of `sys.inputs`, or remove it if the value is null.
+    pub fn set_input(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let (key, value) = parse_input(&arguments)?;
+        if let Some(compiler) = self.compiler.as_mut() {
+            compiler
+                .set_input(key, value)
+                .map_err(|err| internal_error(format!("could not set input: {err}")))?;
+        }
         Ok(JsonValue::Null)
     }
 
+    /// Get the inputs of `sys.inputs`.
+    pub fn get_inputs(&mut self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let inputs = self
+            .compiler()
+            .inputs()
+            .map_err(|err| internal_error(format!("could not get inputs: {err}")))?;
+        serde_json::to_value(inputs)
+            .map_err(|err| internal_error(format!("could not serialize inputs: {err}")))
+    }
+
+    /// List the fonts available to the compiler in the order of precedence,
+    /// telling which ones are selected for their families and variants.
+    pub fn list_fonts(&mut self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let fonts = self
+            .compiler()
+            .list_fonts()
+            .map_err(|err| internal_error(format!("could not list fonts: {err}")))?;
+        serde_json::to_value(fonts)
+            .map_err(|err| internal_error(format!("could not serialize fonts: {err}")))
+    }
+
+    /// Get the versions of the recent documents kept in the history, the oldest
+    /// first, which is empty unless `documentHistorySize` is set.
+    pub fn get_document_versions(&mut self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let versions = self.compiler().document_versions();
+        serde_json::to_value(versions)
+            .map_err(|err| internal_error(format!("could not serialize versions: {err}")))
+    }
+
+    /// Export a previous version of the document kept in the history as PDF,
+    /// SVG or PNG, returning the names and the base64-encoded data of the
+    /// exported files instead of writing them.
+    pub fn export_document_version(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let version = arguments
+            .first()
+            .and_then(JsonValue::as_u64)
+            .ok_or_else(|| invalid_params("The first parameter is not a valid version"))?
+            as usize;
+        // The sources of previous versions are not kept, so they can't be bundled.
+        let kind = match arguments.get(1).and_then(JsonValue::as_str) {
+            Some("pdf") => ExportKind::Pdf,
+            Some("svg") => ExportKind::Svg {
+                page: parse_opts(arguments.get(2))?.page,
+            },
+            Some("png") => ExportKind::Png {
+                page: parse_opts(arguments.get(2))?.page,
+            },
+            _ => {
+                return Err(invalid_params(
+                    "The second parameter is not a valid format, expected pdf, svg or png",
+                ))
+            }
+        };
+
+        let files = self
+            .compiler()
+            .export_version_bytes(version, kind)
+            .map_err(|err| internal_error(format!("could not export version: {err:#}")))?;
+        let files = files
+            .into_iter()
+            .map(|(name, data)| ExportedFile {
+                name,
+                data: base64::engine::general_purpose::STANDARD.encode(data),
+            })
+            .collect::<Vec<_>>();
+        serde_json::to_value(files)
+            .map_err(|err| internal_error(format!("could not serialize files: {err}")))
+    }
+
     /// Focus main file to some path.
     pub fn change_entry(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
         let new_entry = parse_path_or_null(arguments.first())?;
@@ -441,6 +550,14 @@ impl CompileServer {
     }
 }
 
+/// An exported file sent to the client.
+#[derive(Debug, Clone, Serialize)]
+struct ExportedFile {
+    name: String,
+    /// The base64-encoded data of the file.
+    data: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct ExportOpts {
     page: PageSelection,
@@ -456,6 +573,63 @@ fn parse_opts(v: Option<&JsonValue>) -> LspResult<ExportOpts> {
     })
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleOpts {
+    #[serde(default)]
+    no_sources: bool,
+    /// Whether to also bundle the first page as an SVG image.
+    #[serde(default)]
+    svg: bool,
+    /// Whether to also bundle the first page as a PNG image.
+    #[serde(default)]
+    png: bool,
+}
+
+impl BundleOpts {
+    /// The artifacts to bundle besides the PDF.
+    fn kinds(&self) -> Vec<ExportKind> {
+        let page = PageSelection::First;
+        let svg = self.svg.then_some(ExportKind::Svg { page });
+        let png = self.png.then_some(ExportKind::Png { page });
+        svg.into_iter().chain(png).collect()
+    }
+}
+
+fn parse_bundle_opts(v: Option<&JsonValue>) -> LspResult<BundleOpts> {
+    Ok(match v {
+        Some(opts) => serde_json::from_value::<BundleOpts>(opts.clone())
+            .map_err(|_| invalid_params("The third argument is not a valid object"))?,
+        _ => BundleOpts::default(),
+    })
+}
+
+/// Whether the export options ask to write the file even if it is unchanged.
+fn parse_force(v: Option<&JsonValue>) -> bool {
+    v.and_then(|opts| opts.get("force"))
+        .and_then(JsonValue::as_bool)
+        .unwrap_or(false)
+}
+
+fn parse_input(arguments: &[JsonValue]) -> LspResult<(String, Option<String>)> {
+    let Some(JsonValue::String(key)) = arguments.first() else {
+        return Err(invalid_params(
+            "The first parameter is not a valid input key",
+        ));
+    };
+    let value = match arguments.get(1) {
+        Some(JsonValue::String(value)) => Some(value.clone()),
+        Some(JsonValue::Null) | None => None,
+        _ => {
+            return Err(invalid_params(
+                "The second parameter is not a string or null",
+            ))
+        }
+    };
+
+    Ok((key.clone(), value))
+}
+
 fn parse_path(v: Option<&JsonValue>) -> LspResult<ImmutPath> {
     let new_entry = match v {
         Some(JsonValue::String(s)) => Path::new(s).into(),
@@ -1,5 +1,7 @@
 use core::fmt;
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::Arc;
 
 use anyhow::bail;
@@ -9,22 +11,28 @@ use comemo::Prehashed;
 use once_cell::sync::{Lazy, OnceCell};
 use serde::Deserialize;
 use serde_json::{Map, Value as JsonValue};
-use tinymist_query::PositionEncoding;
+use tinymist_query::{DiagnosticVerbosity, OutOfRootDiagnostics, PositionEncoding};
 use tinymist_render::PeriscopeArgs;
 use tokio::sync::mpsc;
 use typst::foundations::IntoValue;
+use typst::layout::Paper;
 use typst::syntax::{FileId, VirtualPath};
 use typst::util::Deferred;
 use typst_ts_core::config::compiler::EntryState;
 use typst_ts_core::{ImmutPath, TypstDict};
 
 use crate::actor::editor::EditorRequest;
+use crate::actor::export::{ExportConfig, ThumbnailConfig};
 use crate::compiler::CompileServer;
 use crate::harness::LspDriver;
 use crate::utils::{try_, try_or_default};
 use crate::world::{ImmutDict, SharedFontResolver};
 use crate::{CompileExtraOpts, CompileFontOpts, ExportMode, LspHost};
 
+/// The default maximum number of pixels of an exported PNG, which takes about
+/// 400 MB of memory to render.
+const DEFAULT_PNG_MAX_PIXELS: u64 = 100_000_000;
+
 #[cfg(feature = "clap")]
 const ENV_PATH_SEP: char = if cfg!(windows) { ';' } else { ':' };
 
@@ -103,18 +111,96 @@ pub struct CompileConfig {
     pub output_path: String,
     /// The mode of PDF export.
     pub export_pdf: ExportMode,
+    /// Whether to export PNG with a transparent page background.
+    pub png_transparent: bool,
+    /// The maximum number of pixels of an exported PNG, zero means unlimited.
+    /// It defaults to [`DEFAULT_PNG_MAX_PIXELS`] if unset.
+    pub png_max_pixels: Option<u64>,
+    /// Whether to export each page as a separate PDF.
+    pub pdf_split_pages: bool,
+    /// The unix permissions of the exported files.
+    pub output_mode: Option<u32>,
+    /// The creator of exported PDFs, where an empty string removes it.
+    pub pdf_creator: Option<String>,
+    /// The producer of exported PDFs, where an empty string removes it.
+    pub pdf_producer: Option<String>,
+    /// The command run after exporting, with the path of the exported file
+    /// appended to its arguments.
+    pub post_export_command: Option<Vec<String>>,
+    /// Whether to allow running the post-export command, which is disabled by
+    /// default.
+    pub enable_post_export_command: bool,
+    /// The thumbnail exported alongside the document.
+    pub export_thumbnail: Option<ThumbnailConfig>,
     /// Specifies the root path of the project manually.
     pub root_path: Option<PathBuf>,
+    /// The paths of the files relative to the root which are tried in order
+    /// as the entry if no entry is specified.
+    pub entry_candidates: Vec<String>,
+    /// Specifies the base directory to resolve resources under the root, such
+    /// as images and data files.
+    pub workspace_base: Option<PathBuf>,
+    /// Specifies the root for untitled buffers, which are detached otherwise.
+    pub untitled_root: Option<PathBuf>,
+    /// Specifies the entry files of the project manually, which overrides the
+    /// detected targets.
+    pub targets: Vec<PathBuf>,
+    /// Specifies an additional entry which is compiled only for diagnostics,
+    /// such as a file importing everything for testing.
+    pub check_entry: Option<PathBuf>,
+    /// The name of the paper overriding the default page size, which changes
+    /// the output for quick checks of the layout on other papers.
+    pub paper_override: Option<String>,
     /// Specifies the cli font options
     pub font_opts: CompileFontOpts,
     /// Whether to ignore system fonts
     pub system_fonts: Option<bool>,
     /// Specifies the font paths
     pub font_paths: Vec<PathBuf>,
+    /// Whether to prefer the fonts in the font paths over the system and
+    /// embedded fonts.
+    pub prefer_user_fonts: bool,
     /// Computed fonts based on configuration.
     pub fonts: OnceCell<Derived<Deferred<SharedFontResolver>>>,
     /// Notify the compile status to the editor.
     pub notify_compile_status: bool,
+    /// Whether to scan the files ignored by the ignore files, such as
+    /// `.gitignore`, when scanning the workspace.
+    pub scan_ignored_files: bool,
+    /// The extensions of the files under the root which trigger compilations
+    /// when changed, such as generated data files.
+    pub watch_extensions: Vec<String>,
+    /// Whether to pause compiling the entries which are not focused.
+    pub compile_only_active: bool,
+    /// The maximum number of diagnostics publications per second, zero means
+    /// unlimited.
+    pub diagnostics_max_rate: u32,
+    /// The maximum number of diagnostics reported for a file, `None` means
+    /// unlimited.
+    pub max_diagnostics_per_file: Option<usize>,
+    /// Whether to warn about `let` bindings rebinding names in the same scope.
+    pub lint_redefinitions: bool,
+    /// The stable identities of the diagnostics which are not reported.
+    pub suppressed_diagnostics: Vec<String>,
+    /// How to report the diagnostics in files outside of the root.
+    pub out_of_root_diagnostics: OutOfRootDiagnostics,
+    /// How verbose the messages of the diagnostics are.
+    pub diagnostic_verbosity: DiagnosticVerbosity,
+    /// The number of threads analyzing the files in parallel, which are
+    /// analyzed serially if it is zero or one.
+    pub analysis_threads: usize,
+    /// The number of recent successfully compiled documents kept for the
+    /// primary compiler, which can be exported again by their versions. Zero
+    /// disables the history.
+    pub document_history_size: usize,
+    /// Whether to let the server crash on panics of the compiler, instead of
+    /// reporting them as errors.
+    pub abort_on_panic: bool,
+    /// The user-defined completion snippets, mapping labels to snippet
+    /// bodies.
+    pub completion_snippets: Arc<BTreeMap<String, String>>,
+    /// Whether to forbid downloading packages and package metadata.
+    pub offline: bool,
     /// Enable periscope document in hover.
     pub periscope_args: Option<PeriscopeArgs>,
     /// Typst extra arguments.
@@ -144,12 +230,102 @@ impl CompileConfig {
     pub fn update_by_map(&mut self, update: &Map<String, JsonValue>) -> anyhow::Result<()> {
         self.output_path = try_or_default(|| Some(update.get("outputPath")?.as_str()?.to_owned()));
         self.export_pdf = try_or_default(|| ExportMode::deserialize(update.get("exportPdf")?).ok());
+        self.png_transparent = try_or_default(|| update.get("exportPngTransparent")?.as_bool());
+        self.pdf_split_pages = try_or_default(|| update.get("exportPdfSplitPages")?.as_bool());
+        self.output_mode = match try_(|| update.get("exportFileMode")?.as_str()) {
+            Some(mode) => match u32::from_str_radix(mode.trim_start_matches("0o"), 8) {
+                Ok(mode) if mode <= 0o777 => Some(mode),
+                _ => bail!("exportFileMode must be octal permissions like \"640\": {mode:?}"),
+            },
+            None => None,
+        };
+        self.pdf_creator = try_(|| Some(update.get("exportPdfCreator")?.as_str()?.to_owned()));
+        self.pdf_producer = try_(|| Some(update.get("exportPdfProducer")?.as_str()?.to_owned()));
+        self.post_export_command = match update.get("postExportCommand") {
+            Some(JsonValue::Null) | None => None,
+            Some(command) => match Vec::<String>::deserialize(command) {
+                Ok(command) if !command.is_empty() => Some(command),
+                _ => bail!("postExportCommand must be a non-empty array of strings"),
+            },
+        };
+        self.enable_post_export_command =
+            try_or_default(|| update.get("enablePostExportCommand")?.as_bool());
+        self.export_thumbnail = match update.get("exportThumbnail") {
+            Some(JsonValue::Null) | None => None,
+            Some(thumbnail) => match ThumbnailConfig::deserialize(thumbnail) {
+                Ok(thumbnail) if thumbnail.max_size > 0 => Some(thumbnail),
+                _ => bail!(
+                    "exportThumbnail must be an object like {{ \"maxSize\": 256, \"page\": 1 }}"
+                ),
+            },
+        };
+        self.png_max_pixels = try_(|| update.get("exportPngMaxPixels")?.as_u64());
         self.root_path = try_(|| Some(update.get("rootPath")?.as_str()?.into()));
+        self.entry_candidates =
+            try_or_default(|| Vec::<String>::deserialize(update.get("entryCandidates")?).ok());
+        self.workspace_base = try_(|| Some(update.get("workspaceBase")?.as_str()?.into()));
+        self.untitled_root = try_(|| Some(update.get("untitledRoot")?.as_str()?.into()));
+        self.targets = try_or_default(|| Vec::<_>::deserialize(update.get("targets")?).ok());
+        self.check_entry = try_(|| Some(update.get("checkEntry")?.as_str()?.into()));
+        self.paper_override = match try_(|| update.get("paperOverride")?.as_str()) {
+            Some(paper) => match Paper::from_str(paper) {
+                Ok(_) => Some(paper.to_owned()),
+                Err(_) => bail!("paperOverride must be a paper name like \"a4\": {paper:?}"),
+            },
+            None => None,
+        };
         self.notify_compile_status = match try_(|| update.get("compileStatus")?.as_str()) {
             Some("enable") => true,
             Some("disable") | None => false,
             _ => bail!("compileStatus must be either 'enable' or 'disable'"),
         };
+        self.scan_ignored_files = try_or_default(|| update.get("scanIgnoredFiles")?.as_bool());
+        self.watch_extensions = try_or_default(|| {
+            let extensions = Vec::<String>::deserialize(update.get("watchExtensions")?).ok()?;
+            let extensions = extensions.iter().map(|ext| ext.trim_start_matches('.'));
+            Some(
+                extensions
+                    .filter(|ext| !ext.is_empty())
+                    .map(str::to_owned)
+                    .collect(),
+            )
+        });
+        self.compile_only_active = try_or_default(|| update.get("compileOnlyActive")?.as_bool());
+        self.diagnostics_max_rate =
+            try_or_default(|| u32::try_from(update.get("diagnosticsMaxRate")?.as_u64()?).ok());
+        self.offline = try_or_default(|| update.get("offline")?.as_bool());
+        self.max_diagnostics_per_file =
+            try_(|| usize::try_from(update.get("maxDiagnosticsPerFile")?.as_u64()?).ok());
+        self.lint_redefinitions = try_or_default(|| update.get("lintRedefinitions")?.as_bool());
+        self.suppressed_diagnostics = try_or_default(|| {
+            Vec::<String>::deserialize(update.get("suppressedDiagnostics")?).ok()
+        });
+        self.out_of_root_diagnostics = match update.get("outOfRootDiagnostics") {
+            Some(JsonValue::Null) | None => OutOfRootDiagnostics::default(),
+            Some(value) => match OutOfRootDiagnostics::deserialize(value) {
+                Ok(value) => value,
+                Err(_) => bail!("outOfRootDiagnostics must be either 'report' or 'suppress'"),
+            },
+        };
+        self.diagnostic_verbosity = match update.get("diagnosticVerbosity") {
+            Some(JsonValue::Null) | None => DiagnosticVerbosity::default(),
+            Some(value) => match DiagnosticVerbosity::deserialize(value) {
+                Ok(value) => value,
+                Err(_) => bail!("diagnosticVerbosity must be either 'terse' or 'explained'"),
+            },
+        };
+        self.analysis_threads =
+            try_or_default(|| usize::try_from(update.get("analysisThreads")?.as_u64()?).ok());
+        self.document_history_size =
+            try_or_default(|| usize::try_from(update.get("documentHistorySize")?.as_u64()?).ok());
+        self.abort_on_panic = try_or_default(|| update.get("abortOnPanic")?.as_bool());
+        self.completion_snippets = match update.get("completionSnippets") {
+            Some(JsonValue::Null) | None => Arc::default(),
+            Some(snippets) => match serde_json::from_value(snippets.clone()) {
+                Ok(e) => Arc::new(e),
+                Err(e) => bail!("failed to parse completionSnippets: {e}"),
+            },
+        };
         self.preferred_theme = try_(|| Some(update.get("preferredTheme")?.as_str()?.to_owned()));
 
         // periscope_args
@@ -161,7 +337,9 @@ impl CompileConfig {
                 Err(e) => bail!("failed to parse hoverPeriscope: {e}"),
             },
         };
+        let max_pixels = self.determine_png_max_pixels();
         if let Some(args) = self.periscope_args.as_mut() {
+            args.max_pixels = max_pixels;
             if args.invert_color == "auto" && self.preferred_theme.as_deref() == Some("dark") {
                 "always".clone_into(&mut args.invert_color);
             }
@@ -206,6 +384,7 @@ impl CompileConfig {
 
         self.font_paths = try_or_default(|| Vec::<_>::deserialize(update.get("fontPaths")?).ok());
         self.system_fonts = try_(|| update.get("systemFonts")?.as_bool());
+        self.prefer_user_fonts = try_or_default(|| update.get("preferUserFonts")?.as_bool());
 
         self.has_default_entry_path = self.determine_default_entry_path().is_some();
         self.validate()
@@ -243,30 +422,116 @@ impl CompileConfig {
         None
     }
 
+    /// Determines the entry used regardless of the focused file, which is
+    /// specified by the extra arguments, or the first existing candidate entry
+    /// in the root.
     pub fn determine_default_entry_path(&self) -> Option<ImmutPath> {
-        let extras = self.typst_extra_args.as_ref()?;
+        let entry = self.typst_extra_args.as_ref().and_then(|e| e.entry.clone());
         // todo: pre-compute this when updating config
-        if let Some(entry) = &extras.entry {
+        if let Some(entry) = entry {
             if entry.is_relative() {
                 let root = self.determine_root(None)?;
                 return Some(root.join(entry).as_path().into());
             }
+            return Some(entry);
+        }
+
+        self.determine_candidate_entry_path()
+    }
+
+    /// Determines the first candidate entry existing in the root.
+    pub fn determine_candidate_entry_path(&self) -> Option<ImmutPath> {
+        if self.entry_candidates.is_empty() {
+            return None;
+        }
+
+        let root = self.determine_root(None)?;
+        let candidates = self.entry_candidates.iter().map(|c| root.join(c));
+        let entry = candidates.into_iter().find(|c| c.is_file())?;
+        Some(entry.as_path().into())
+    }
+
+    /// Determines the command run after exporting, which is None unless running
+    /// it is enabled.
+    pub fn determine_post_export_command(&self) -> Option<Vec<String>> {
+        if !self.enable_post_export_command {
+            return None;
+        }
+        self.post_export_command.clone()
+    }
+
+    /// Determines the maximum number of pixels of a rendered image, zero means
+    /// unlimited.
+    pub fn determine_png_max_pixels(&self) -> u64 {
+        self.png_max_pixels.unwrap_or(DEFAULT_PNG_MAX_PIXELS)
+    }
+
+    /// Determines the config of the export actors.
+    pub fn export_config(&self) -> ExportConfig {
+        ExportConfig {
+            substitute_pattern: self.output_path.clone(),
+            mode: self.export_pdf,
+            png_transparent: self.png_transparent,
+            png_max_pixels: self.determine_png_max_pixels(),
+            pdf_split_pages: self.pdf_split_pages,
+            output_mode: self.output_mode,
+            pdf_creator: self.pdf_creator.clone(),
+            pdf_producer: self.pdf_producer.clone(),
+            post_export_command: self.determine_post_export_command(),
+            thumbnail: self.export_thumbnail,
         }
-        extras.entry.clone()
+    }
+
+    /// Determines the workspace base to resolve resources from, if it is set.
+    pub fn determine_workspace_base(&self) -> Option<ImmutPath> {
+        self.workspace_base.as_deref().map(ImmutPath::from)
+    }
+
+    /// Determines the paper overriding the default page size.
+    pub fn determine_paper_override(&self) -> Option<Paper> {
+        Paper::from_str(self.paper_override.as_deref()?).ok()
+    }
+
+    /// Determines the path of the check entry, which is relative to the root
+    /// unless it is absolute.
+    pub fn determine_check_entry_path(&self) -> Option<ImmutPath> {
+        let entry = self.check_entry.as_ref()?;
+        if entry.is_relative() {
+            let root = self.determine_root(None)?;
+            return Some(root.join(entry).as_path().into());
+        }
+        Some(entry.as_path().into())
+    }
+
+    /// Determines the path of an untitled buffer in the root for untitled
+    /// buffers, to which the buffer is mirrored.
+    ///
+    /// Returns `None` if the path is not an untitled buffer or the root for
+    /// untitled buffers is not configured.
+    pub fn determine_untitled_path(&self, path: &Path) -> Option<ImmutPath> {
+        let root = self.untitled_root.as_ref()?;
+        let rel = path.strip_prefix("/untitled").ok()?;
+        Some(root.join(rel).as_path().into())
     }
 
     pub fn determine_entry(&self, entry: Option<ImmutPath>) -> EntryState {
-        // todo: formalize untitled path
-        // let is_untitled = entry.as_ref().is_some_and(|p| p.starts_with("/untitled"));
-        // let root_dir = self.determine_root(if is_untitled { None } else {
-        // entry.as_ref() });
+        // An untitled buffer is compiled in the root for untitled buffers if it is
+        // configured, so that it can import files from there.
+        if let Some(untitled_root) = &self.untitled_root {
+            let rel = entry
+                .as_ref()
+                .and_then(|e| e.strip_prefix("/untitled").ok());
+            if let Some(rel) = rel {
+                return EntryState::new_rooted(
+                    untitled_root.as_path().into(),
+                    Some(FileId::new(None, VirtualPath::new(rel))),
+                );
+            }
+        }
+
         let root_dir = self.determine_root(entry.as_ref());
 
         let entry = match (entry, root_dir) {
-            // (Some(entry), Some(root)) if is_untitled => Some(EntryState::new_rooted(
-            //     root,
-            //     Some(FileId::new(None, VirtualPath::new(entry))),
-            // )),
             (Some(entry), Some(root)) => match entry.strip_prefix(&root) {
                 Ok(stripped) => Some(EntryState::new_rooted(
                     root,
@@ -296,6 +561,7 @@ impl CompileConfig {
             if let Some(system_fonts) = self.system_fonts {
                 opts.no_system_fonts = !system_fonts;
             }
+            opts.prefer_user_fonts = self.prefer_user_fonts;
 
             let font_paths = (!self.font_paths.is_empty()).then_some(&self.font_paths);
             let font_paths =
@@ -356,6 +622,24 @@ impl CompileConfig {
             }
         }
 
+        if let Some(base) = &self.workspace_base {
+            if !base.is_absolute() {
+                bail!("workspaceBase must be an absolute path: {base:?}");
+            }
+        }
+
+        if let Some(root) = &self.untitled_root {
+            if !root.is_absolute() {
+                bail!("untitledRoot must be an absolute path: {root:?}");
+            }
+        }
+
+        for target in &self.targets {
+            if !target.is_absolute() {
+                bail!("targets must be absolute paths: {target:?}");
+            }
+        }
+
         if let Some(extra_args) = &self.typst_extra_args {
             if let Some(root) = &extra_args.root_dir {
                 if !root.is_absolute() {
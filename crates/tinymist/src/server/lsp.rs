@@ -13,12 +13,14 @@ use lsp_server::{ErrorCode, Message, Notification, Request, RequestId, Response,
 use lsp_types::notification::Notification as NotificationTrait;
 use lsp_types::request::{GotoDeclarationParams, GotoDeclarationResponse, WorkspaceConfiguration};
 use lsp_types::*;
+use once_cell::sync::OnceCell;
 use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use serde_json::{Map, Value as JsonValue};
 use tinymist_query::{
     get_semantic_tokens_options, get_semantic_tokens_registration,
-    get_semantic_tokens_unregistration, ExportKind, PageSelection, SemanticTokenContext,
+    get_semantic_tokens_unregistration, CompletionDocs, CompletionResolveData, ExportKind,
+    PageSelection, SemanticTokenContext,
 };
 use tokio::sync::mpsc;
 use typst::diag::StrResult;
@@ -35,8 +37,8 @@ use crate::actor::user_action::{TraceParams, UserActionRequest};
 use crate::compiler::CompileServer;
 use crate::compiler_init::CompilerConstConfig;
 use crate::harness::{InitializedLspDriver, LspHost};
-use crate::tools::package::InitTask;
-use crate::{run_query, LspResult};
+use crate::tools::package::{InitTask, PackagePrefetch};
+use crate::{run_query, utils, LspResult};
 
 pub type MaySyncResult<'a> = Result<JsonValue, BoxFuture<'a, JsonValue>>;
 
@@ -138,6 +140,19 @@ fn as_path_pos(inp: TextDocumentPositionParams) -> (PathBuf, Position) {
     (as_path(inp.text_document), inp.position)
 }
 
+/// Gets the final result of a request streaming partial results, which is
+/// empty if any batch is already sent, as the results shall not be reported
+/// twice.
+fn finish_partial_result<T, U>(
+    sink: Option<tinymist_query::PartialResultSink<T>>,
+    resp: Option<Vec<U>>,
+) -> Option<Vec<U>> {
+    match sink {
+        Some(sink) if sink.is_sent() => Some(vec![]),
+        _ => resp,
+    }
+}
+
 /// The object providing the language server functionality.
 pub struct TypstLanguageServer {
     /// The language server client.
@@ -150,6 +165,8 @@ pub struct TypstLanguageServer {
     pub sema_tokens_registered: bool,
     /// Whether the server has registered document formatter capabilities.
     pub formatter_registered: bool,
+    /// Whether the server has registered to watch the candidate entry files.
+    pub entry_watcher_registered: bool,
     /// Whether client is pinning a file.
     pub pinning: bool,
     /// The client focusing file.
@@ -222,6 +239,7 @@ impl TypstLanguageServer {
             ever_manual_focusing: false,
             sema_tokens_registered: false,
             formatter_registered: false,
+            entry_watcher_registered: false,
             config: Default::default(),
             const_config,
 
@@ -255,6 +273,7 @@ impl TypstLanguageServer {
             request_fn!(Shutdown, Self::shutdown),
             // lantency sensitive
             request_fn!(Completion, Self::completion),
+            request_fn!(ResolveCompletionItem, Self::resolve_completion_item),
             request_fn!(SemanticTokensFullRequest, Self::semantic_tokens_full),
             request_fn!(SemanticTokensFullDeltaRequest, Self::semantic_tokens_full_delta),
             request_fn!(DocumentHighlightRequest, Self::document_highlight),
@@ -290,6 +309,7 @@ impl TypstLanguageServer {
             notify_fn!(DidChangeTextDocument, Self::did_change),
             notify_fn!(DidSaveTextDocument, Self::did_save),
             notify_fn!(DidChangeConfiguration, Self::did_change_configuration),
+            notify_fn!(DidChangeWatchedFiles, Self::did_change_watched_files),
         ])
     }
 }
@@ -322,6 +342,13 @@ impl InitializedLspDriver for TypstLanguageServer {
             }
         }
 
+        if !self.config.compile.entry_candidates.is_empty() {
+            let err = self.watch_entry_candidates();
+            if let Err(err) = err {
+                error!("could not watch candidate entries for initialization: {err}");
+            }
+        }
+
         if self.const_config().cfg_change_registration {
             trace!("setting up to request config change notifications");
 
@@ -481,6 +508,62 @@ impl TypstLanguageServer {
         }
     }
 
+    /// Registers to watch the creation and deletion of the candidate entry
+    /// files, replacing the former registration. The candidates are watched
+    /// relative to the root, so the watcher is registered again once the root
+    /// changes.
+    fn watch_entry_candidates(&mut self) -> anyhow::Result<()> {
+        if !self.const_config().watched_files_dynamic_registration {
+            trace!("skip dynamic register watched files by config");
+            return Ok(());
+        }
+
+        const ENTRY_WATCHER_REGISTRATION_ID: &str = "entryCandidates";
+        const WATCHED_FILES_METHOD_ID: &str = "workspace/didChangeWatchedFiles";
+
+        if self.entry_watcher_registered {
+            trace!("unregistering candidate entry watcher");
+            self.client
+                .unregister_capability(vec![Unregistration {
+                    id: ENTRY_WATCHER_REGISTRATION_ID.to_owned(),
+                    method: WATCHED_FILES_METHOD_ID.to_owned(),
+                }])
+                .context("could not unregister candidate entry watcher")?;
+            self.entry_watcher_registered = false;
+        }
+
+        let candidates = &self.config.compile.entry_candidates;
+        let root = self.config.compile.determine_root(None);
+        let (false, Some(root)) = (candidates.is_empty(), root) else {
+            return Ok(());
+        };
+        let base_uri = Url::from_directory_path(&root)
+            .ok()
+            .context("could not convert the root to a URI")?;
+
+        let watchers = candidates.iter().map(|candidate| FileSystemWatcher {
+            glob_pattern: GlobPattern::Relative(RelativePattern {
+                base_uri: OneOf::Right(base_uri.clone()),
+                pattern: candidate.clone(),
+            }),
+            kind: Some(WatchKind::Create | WatchKind::Delete),
+        });
+        let options = DidChangeWatchedFilesRegistrationOptions {
+            watchers: watchers.collect(),
+        };
+
+        trace!("registering candidate entry watcher");
+        self.client
+            .register_capability(vec![Registration {
+                id: ENTRY_WATCHER_REGISTRATION_ID.to_owned(),
+                method: WATCHED_FILES_METHOD_ID.to_owned(),
+                register_options: Some(serde_json::to_value(options)?),
+            }])
+            .context("could not register candidate entry watcher")?;
+        self.entry_watcher_registered = true;
+        Ok(())
+    }
+
     /// Registers or unregisters document formatter.
     fn enable_formatter_caps(&mut self, enable: bool) -> anyhow::Result<()> {
         if !self.const_config().doc_fmt_dynamic_registration {
@@ -560,15 +643,65 @@ impl TypstLanguageServer {
             exec_fn!("tinymist.exportPdf", Self::export_pdf),
             exec_fn!("tinymist.exportSvg", Self::export_svg),
             exec_fn!("tinymist.exportPng", Self::export_png),
+            exec_fn!("tinymist.exportBundle", Self::export_bundle),
             exec_fn!("tinymist.doClearCache", Self::clear_cache),
+            exec_fn!(
+                "tinymist.doClearPeriscopeCache",
+                Self::clear_periscope_cache
+            ),
+            exec_fn!("tinymist.reloadFonts", Self::reload_fonts),
+            exec_fn!("tinymist.restartServer", Self::restart_server),
+            exec_fn!("tinymist.revertFile", Self::revert_file),
+            exec_fn_!("tinymist.compileWithOverlay", Self::compile_with_overlay),
+            exec_fn!("tinymist.setInput", Self::set_input),
+            exec_fn!("tinymist.getInputs", Self::get_inputs),
+            exec_fn!("tinymist.listFonts", Self::list_fonts),
+            exec_fn!("tinymist.getDocumentVersions", Self::get_document_versions),
+            exec_fn!(
+                "tinymist.exportDocumentVersion",
+                Self::export_document_version
+            ),
             exec_fn!("tinymist.pinMain", Self::pin_document),
             exec_fn!("tinymist.focusMain", Self::focus_document),
+            exec_fn!("tinymist.getMainLocation", Self::get_main_location),
             exec_fn!("tinymist.doInitTemplate", Self::init_template),
+            exec_fn_!("tinymist.prefetchPackages", Self::prefetch_packages),
+            exec_fn!("tinymist.resolvePackage", Self::resolve_package),
             exec_fn!("tinymist.doGetTemplateEntry", Self::do_get_template_entry),
             exec_fn!("tinymist.interactCodeContext", Self::interact_code_context),
             exec_fn_!("tinymist.getDocumentTrace", Self::get_document_trace),
             exec_fn!("tinymist.getDocumentMetrics", Self::get_document_metrics),
+            exec_fn!("tinymist.getDocumentFonts", Self::get_document_fonts),
+            exec_fn!("tinymist.getDocumentMetadata", Self::get_document_metadata),
+            exec_fn!("tinymist.getPageText", Self::get_page_text),
+            exec_fn!("tinymist.getDocumentHeadings", Self::get_document_headings),
+            exec_fn!("tinymist.getDocumentOutline", Self::get_document_outline),
+            exec_fn!("tinymist.forwardSearch", Self::forward_search),
+            exec_fn!("tinymist.getChangedPages", Self::get_changed_pages),
+            exec_fn!("tinymist.getExportStatus", Self::get_export_status),
+            exec_fn!("tinymist.checkReferences", Self::check_references),
+            exec_fn!("tinymist.whyDepends", Self::why_depends),
+            exec_fn!("tinymist.getSyntaxTree", Self::get_syntax_tree),
+            exec_fn!(
+                "tinymist.getDocumentResources",
+                Self::get_document_resources
+            ),
+            exec_fn!("tinymist.getStyleRules", Self::get_style_rules),
+            exec_fn!("tinymist.getBreadcrumbs", Self::get_breadcrumbs),
             exec_fn!("tinymist.getServerInfo", Self::get_server_info),
+            exec_fn!("tinymist.listTargets", Self::list_targets),
+            exec_fn!(
+                "tinymist.getSortedDiagnostics",
+                Self::get_sorted_diagnostics
+            ),
+            exec_fn!(
+                "tinymist.getDiagnosticsByCategory",
+                Self::get_diagnostics_by_category
+            ),
+            exec_fn!(
+                "tinymist.exportDiagnosticsSarif",
+                Self::export_diagnostics_sarif
+            ),
             // For Documentations
             exec_fn!("tinymist.getResources", Self::get_resources),
         ])
@@ -591,12 +724,21 @@ impl TypstLanguageServer {
         self.export(ExportKind::Png { page: opts.page }, arguments)
     }
 
+    /// Export the current document and its sources as a zip archive.
+    pub fn export_bundle(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let opts = parse_bundle_opts(arguments.get(1))?;
+        let sources = !opts.no_sources;
+        let kinds = opts.kinds();
+        self.export(ExportKind::Bundle { sources, kinds }, arguments)
+    }
+
     /// Export the current document as some format. The client is responsible
     /// for passing the correct absolute path of typst document.
     pub fn export(&mut self, kind: ExportKind, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
         let path = parse_path(arguments.first())?.as_ref().to_owned();
+        let force = parse_force(arguments.get(1));
 
-        let res = run_query!(self.OnExport(path, kind))?;
+        let res = run_query!(self.OnExport(path, kind, force))?;
         let res = serde_json::to_value(res).map_err(|_| internal_error("Cannot serialize path"))?;
 
         Ok(res)
@@ -696,6 +838,145 @@ impl TypstLanguageServer {
         Ok(res)
     }
 
+    /// Get the fonts used by the compiled document, with their sources.
+    pub fn get_document_fonts(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+
+        let res = run_query!(self.DocumentFonts(path))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Get the metadata of the compiled document, such as its title and
+    /// authors.
+    pub fn get_document_metadata(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+
+        let res = run_query!(self.DocumentMetadata(path))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Get the plain text of a page in the compiled document, in reading order.
+    pub fn get_page_text(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+        let page = arguments
+            .get(1)
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| invalid_params("The second parameter is not a valid page"))?
+            as usize;
+
+        let res = run_query!(self.PageText(path, page))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Get the headings of the document, with their levels and pages.
+    pub fn get_document_headings(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+
+        let res = run_query!(self.DocumentHeadings(path))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Get the outline of the compiled document as it will be embedded in the
+    /// exported PDF.
+    pub fn get_document_outline(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+
+        let res = run_query!(self.DocumentOutline(path))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Find the page and the position in the compiled document showing the
+    /// text at a cursor, which tells the slide to step to for slide decks.
+    pub fn forward_search(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+        let position = arguments
+            .get(1)
+            .and_then(|v| serde_json::from_value::<Position>(v.clone()).ok())
+            .ok_or_else(|| invalid_params("The second parameter is not a valid position"))?;
+
+        let res = run_query!(self.ForwardSearch(path, position))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// List the `set` and `show` rules in scope at a position, in the order
+    /// they are applied.
+    pub fn get_style_rules(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+        let position = arguments
+            .get(1)
+            .and_then(|v| serde_json::from_value::<Position>(v.clone()).ok())
+            .ok_or_else(|| invalid_params("The second parameter is not a valid position"))?;
+
+        let res = run_query!(self.StyleRules(path, position))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Get the chain of headings, functions and labeled elements enclosing a
+    /// position, the outermost first.
+    pub fn get_breadcrumbs(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+        let position = arguments
+            .get(1)
+            .and_then(|v| serde_json::from_value::<Position>(v.clone()).ok())
+            .ok_or_else(|| invalid_params("The second parameter is not a valid position"))?;
+
+        let res = run_query!(self.Breadcrumbs(path, position))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Get the indices of the pages changed by the latest successful
+    /// compilation, starting from 0, along with the version of the document.
+    pub fn get_changed_pages(&self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let res = serde_json::to_value(self.primary().changed_pages())
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Get the current statuses of the exports run in background, such as to
+    /// show a stuck or failed export in the status bar.
+    pub fn get_export_status(&self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let res = serde_json::to_value(self.primary().export_status())
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+        Ok(res)
+    }
+
+    /// Check that all references in the compiled document are resolved,
+    /// returning the unresolved ones as diagnostics.
+    pub fn check_references(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+
+        let res = run_query!(self.CheckReferences(path))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
     /// Get the server info.
     pub fn get_server_info(&mut self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
         let res = run_query!(self.ServerInfo())?;
@@ -706,12 +987,104 @@ impl TypstLanguageServer {
         Ok(res)
     }
 
+    /// List the entry files of the project, which can be pinned by
+    /// `tinymist.pinMain`.
+    pub fn list_targets(&mut self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let res = self
+            .primary()
+            .list_targets()
+            .map_err(|e| internal_error(format!("could not list targets: {e}")))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Explain why a file is a dependency of the main file, by the shortest
+    /// chains of imports from the main file to the file.
+    pub fn why_depends(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+
+        let res = run_query!(self.WhyDepends(path))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// List the resources read by a document, such as images, with their
+    /// resolved paths and whether they exist.
+    pub fn get_document_resources(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+
+        let res = run_query!(self.DocumentResources(path))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Get the syntax tree of a source file, with the ranges of the nodes.
+    pub fn get_syntax_tree(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+
+        let res = run_query!(self.SyntaxTree(path))?;
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Get the locations of the diagnostics sorted by position, in the given
+    /// file or in all files if the path is null.
+    pub fn get_sorted_diagnostics(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path_or_null(arguments.first())?;
+
+        let res = self.primary().sorted_diagnostics(path.as_deref());
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Get the diagnostics grouped by their categories across files, with the
+    /// number of diagnostics and a few sample locations in each category.
+    pub fn get_diagnostics_by_category(&self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let res = self.primary().diagnostics_by_category();
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Export the latest diagnostics as a SARIF log. The log is written to the
+    /// given path and the path is returned, or the log is returned if the path
+    /// is null.
+    pub fn export_diagnostics_sarif(&self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path_or_null(arguments.first())?;
+
+        let root = self.config.compile.determine_root(None);
+        let position_encoding = self.const_config().position_encoding;
+        let diagnostics = self.primary().diagnostics();
+        let log = crate::tools::sarif::sarif_log(&diagnostics, root.as_deref(), position_encoding);
+        let Some(path) = path else {
+            return Ok(log);
+        };
+
+        let data = serde_json::to_vec_pretty(&log)
+            .map_err(|e| internal_error(format!("Cannot serialize SARIF log {e}")))?;
+        std::fs::write(&path, data)
+            .map_err(|e| internal_error(format!("Cannot write SARIF log {e}")))?;
+
+        Ok(JsonValue::String(path.to_string_lossy().into_owned()))
+    }
+
     /// Clear all cached resources.
     ///
     /// # Errors
     /// Errors if the cache could not be cleared.
     pub fn clear_cache(&self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
-        comemo::evict(0);
+        utils::evict_all_caches();
         for v in Some(self.primary())
             .into_iter()
             .chain(self.dedicates.iter().map(|v| v.compiler()))
@@ -721,6 +1094,134 @@ impl TypstLanguageServer {
         Ok(JsonValue::Null)
     }
 
+    /// Clear the periscope images of all compilers, keeping the analysis
+    /// caches warm.
+    pub fn clear_periscope_cache(&self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        for v in Some(self.primary())
+            .into_iter()
+            .chain(self.dedicates.iter().map(|v| v.compiler()))
+        {
+            v.clear_periscope_cache();
+        }
+        Ok(JsonValue::Null)
+    }
+
+    /// Reload fonts of all compilers and recompile the documents.
+    pub fn reload_fonts(&mut self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        self.config.compile.fonts = OnceCell::new();
+        for v in Some(&mut self.primary)
+            .into_iter()
+            .chain(self.dedicates.iter_mut())
+        {
+            v.reload_fonts(vec![])?;
+        }
+        Ok(JsonValue::Null)
+    }
+
+    /// Restart the compile servers of all compilers.
+    pub fn restart_server(&mut self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        for v in Some(&mut self.primary)
+            .into_iter()
+            .chain(self.dedicates.iter_mut())
+        {
+            v.restart_server(vec![])?;
+        }
+        Ok(JsonValue::Null)
+    }
+
+    /// Discard the unsaved changes of a file kept by the server, reading it
+    /// from the disk again. Returns whether the file had unsaved changes.
+    pub fn revert_file(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+
+        let reverted = self
+            .revert_source(path)
+            .map_err(|err| internal_error(format!("could not revert file: {err}")))?;
+        Ok(JsonValue::Bool(reverted))
+    }
+
+    /// Compile the main document with the text replacing a range of a file, or
+    /// the whole file if the range is null, without applying the change, to
+    /// preview an edit speculatively. The document is compiled on a separate
+    /// thread, so that the server keeps responding meanwhile.
+    pub fn compile_with_overlay(
+        &mut self,
+        req_id: RequestId,
+        arguments: Vec<JsonValue>,
+    ) -> LspResult<Option<()>> {
+        let path = parse_path(arguments.first())?.as_ref().to_owned();
+        let text = arguments
+            .get(1)
+            .and_then(JsonValue::as_str)
+            .ok_or_else(|| invalid_params("The second parameter is not a valid string"))?
+            .to_owned();
+        let range = match arguments.get(2) {
+            Some(JsonValue::Null) | None => None,
+            Some(v) => Some(
+                serde_json::from_value::<Range>(v.clone())
+                    .map_err(|_| invalid_params("The third parameter is not a valid range"))?,
+            ),
+        };
+
+        let snapshot = self
+            .primary()
+            .overlay_snapshot(&path, range, text)
+            .map_err(|err| internal_error(format!("could not compile with overlay: {err}")))?;
+
+        let position_encoding = self.const_config().position_encoding;
+        let client = self.client.clone();
+        std::thread::spawn(move || {
+            let res = snapshot
+                .compile(position_encoding)
+                .map_err(|err| internal_error(format!("could not compile with overlay: {err}")))
+                .and_then(|res| {
+                    let diagnostics = serde_json::to_value(&res.diagnostics)
+                        .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+                    let mut result = Map::new();
+                    result.insert("success".into(), res.document.is_some().into());
+                    let page_count = res.document.map(|doc| doc.pages.len());
+                    result.insert("pageCount".into(), page_count.into());
+                    result.insert("diagnostics".into(), diagnostics);
+                    Ok(JsonValue::Object(result))
+                });
+            client.respond(result_to_response(req_id, res));
+        });
+
+        Ok(Some(()))
+    }
+
+    /// Set an input of `sys.inputs` for all compilers, or remove it if the
+    /// value is null.
+    pub fn set_input(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        for v in Some(&mut self.primary)
+            .into_iter()
+            .chain(self.dedicates.iter_mut())
+        {
+            v.set_input(arguments.clone())?;
+        }
+        Ok(JsonValue::Null)
+    }
+
+    /// Get the inputs of `sys.inputs` of the primary compiler.
+    pub fn get_inputs(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        self.primary.get_inputs(arguments)
+    }
+
+    /// List the fonts available to the primary compiler.
+    pub fn list_fonts(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        self.primary.list_fonts(arguments)
+    }
+
+    /// Get the versions of the recent documents kept in the history.
+    pub fn get_document_versions(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        self.primary.get_document_versions(arguments)
+    }
+
+    /// Export a previous version of the document kept in the history.
+    pub fn export_document_version(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        self.primary.export_document_version(arguments)
+    }
+
     /// Pin main file to some path.
     pub fn pin_document(&mut self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
         let new_entry = parse_path_or_null(arguments.first())?;
@@ -750,6 +1251,101 @@ impl TypstLanguageServer {
         Ok(JsonValue::Null)
     }
 
+    /// Get the location of the main file, or null if there is no main file
+    /// to reveal.
+    pub fn get_main_location(&self, _arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let res = self.primary().main_location();
+        let res = serde_json::to_value(res)
+            .map_err(|e| internal_error(format!("Cannot serialize response {e}")))?;
+
+        Ok(res)
+    }
+
+    /// Download the packages imported by the workspace which are not cached
+    /// yet, reporting the progress if the client supports it.
+    pub fn prefetch_packages(
+        &mut self,
+        req_id: RequestId,
+        _arguments: Vec<JsonValue>,
+    ) -> LspResult<Option<()>> {
+        let token = self.const_config().work_done_progress.then(|| {
+            let token = ProgressToken::String(format!("tinymist/prefetchPackages/{req_id}"));
+            self.client.send_request::<request::WorkDoneProgressCreate>(
+                WorkDoneProgressCreateParams {
+                    token: token.clone(),
+                },
+                |_, resp| {
+                    if let Some(err) = resp.error {
+                        log::error!("failed to create work done progress: {err:?}");
+                    }
+                },
+            );
+            token
+        });
+
+        let client = self.client.clone();
+        let report = move |value: WorkDoneProgress| {
+            if let Some(token) = token.clone() {
+                client.send_notification::<notification::Progress>(ProgressParams {
+                    token,
+                    value: ProgressParamsValue::WorkDone(value),
+                });
+            }
+        };
+
+        let report_ = report.clone();
+        let progress = move |spec: &PackageSpec, idx: usize, total: usize| {
+            let message = Some(format!("{spec} ({}/{total})", idx + 1));
+            let percentage = Some((idx * 100 / total) as u32);
+            report_(match idx {
+                0 => WorkDoneProgress::Begin(WorkDoneProgressBegin {
+                    title: "Prefetching packages".to_owned(),
+                    message,
+                    percentage,
+                    ..Default::default()
+                }),
+                _ => WorkDoneProgress::Report(WorkDoneProgressReport {
+                    message,
+                    percentage,
+                    ..Default::default()
+                }),
+            });
+        };
+
+        let client = self.client.clone();
+        let done = move |res: Vec<PackagePrefetch>| {
+            if !res.is_empty() {
+                let failed = res.iter().filter(|p| p.error.is_some()).count();
+                report(WorkDoneProgress::End(WorkDoneProgressEnd {
+                    message: Some(format!("{} packages, {failed} failed", res.len())),
+                }));
+            }
+            client.respond(result_to_response(req_id, Ok(res)));
+        };
+
+        self.primary()
+            .prefetch_packages(progress, done)
+            .map_err(|err| internal_error(format!("cannot prefetch packages: {err}")))?;
+
+        Ok(Some(()))
+    }
+
+    /// Resolve a package specification to the directory of the package.
+    pub fn resolve_package(&self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
+        let spec = arguments
+            .first()
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_owned())
+            .ok_or_else(|| invalid_params("The first parameter is not a valid package spec"))?;
+
+        let res = self
+            .primary()
+            .resolve_package(spec)
+            .map_err(|err| invalid_params(format!("{err:#}")))?;
+
+        serde_json::to_value(res).map_err(|_| internal_error("Cannot serialize package"))
+    }
+
     /// Initialize a new template.
     pub fn init_template(&self, arguments: Vec<JsonValue>) -> LspResult<JsonValue> {
         use crate::tools::package::{self, determine_latest_version, TemplateSource};
@@ -941,6 +1537,13 @@ impl TypstLanguageServer {
         }
         self.primary.on_changed_configuration(values)?;
 
+        let check_entry = |c: &Config| c.compile.determine_check_entry_path();
+        if check_entry(&config) != check_entry(&self.config)
+            || config.compile.primary_opts() != self.config.compile.primary_opts()
+        {
+            self.restart_check_server();
+        }
+
         info!("new settings applied");
 
         if config.semantic_tokens != self.config.semantic_tokens {
@@ -951,6 +1554,19 @@ impl TypstLanguageServer {
             }
         }
 
+        let root = |c: &Config| c.compile.determine_root(None);
+        if config.compile.entry_candidates != self.config.compile.entry_candidates
+            || root(&config) != root(&self.config)
+        {
+            let err = self.watch_entry_candidates();
+            if let Err(err) = err {
+                error!("could not change candidate entry watcher: {err}");
+            }
+            if let Err(err) = self.refresh_default_entry() {
+                error!("could not change the entry to candidate entries: {err}");
+            }
+        }
+
         if config.formatter != self.config.formatter {
             let err = self.enable_formatter_caps(self.config.formatter != FormatterMode::Disable);
             if let Err(err) = err {
@@ -970,6 +1586,16 @@ impl TypstLanguageServer {
         Ok(())
     }
 
+    fn did_change_watched_files(&mut self, params: DidChangeWatchedFilesParams) -> LspResult<()> {
+        log::info!("watched files changed: {:?}", params.changes);
+
+        // Only the candidate entry files are watched.
+        if let Err(err) = self.refresh_default_entry() {
+            error!("could not change the entry to candidate entries: {err}");
+        }
+        Ok(())
+    }
+
     fn did_change_configuration(&mut self, params: DidChangeConfigurationParams) -> LspResult<()> {
         // For some clients, we don't get the actual changed configuration and need to
         // poll for it https://github.com/microsoft/language-server-protocol/issues/676
@@ -1022,8 +1648,11 @@ impl TypstLanguageServer {
     }
 
     fn references(&mut self, params: ReferenceParams) -> LspResult<Option<Vec<Location>>> {
+        let partial = self.partial_result_sink(params.partial_result_params.partial_result_token);
         let (path, position) = as_path_pos(params.text_document_position);
-        run_query!(self.References(path, position))
+        let sink = partial.clone();
+        let resp = run_query!(self.References(path, position, partial))?;
+        Ok(finish_partial_result(sink, resp))
     }
 
     fn hover(&mut self, params: HoverParams) -> LspResult<Option<Hover>> {
@@ -1152,8 +1781,43 @@ impl TypstLanguageServer {
             .context
             .map(|context| context.trigger_kind == CompletionTriggerKind::INVOKED)
             .unwrap_or(false);
+        let snippets = self.config.compile.completion_snippets.clone();
+        let docs = if self.const_config().completion_resolve_support {
+            CompletionDocs::Lazy
+        } else {
+            CompletionDocs::All
+        };
+
+        run_query!(self.Completion(path, position, explicit, snippets, docs))
+    }
+
+    fn resolve_completion_item(&mut self, mut item: CompletionItem) -> LspResult<CompletionItem> {
+        let data = item.data.clone();
+        let data = data.and_then(|data| serde_json::from_value::<CompletionResolveData>(data).ok());
+        let Some(data) = data.filter(|data| data.label == item.label) else {
+            return Ok(item);
+        };
 
-        run_query!(self.Completion(path, position, explicit))
+        let CompletionResolveData {
+            path,
+            position,
+            label,
+        } = data;
+        let explicit = false;
+        let snippets = self.config.compile.completion_snippets.clone();
+        let docs = CompletionDocs::Resolve(label.into());
+        let resolved = run_query!(self.Completion(path, position, explicit, snippets, docs))?;
+
+        let resolved = match resolved {
+            Some(CompletionResponse::List(list)) => list.items,
+            Some(CompletionResponse::Array(items)) => items,
+            None => vec![],
+        };
+        if let Some(resolved) = resolved.into_iter().find(|r| r.kind == item.kind) {
+            item.detail = resolved.detail;
+            item.documentation = resolved.documentation;
+        }
+        Ok(item)
     }
 
     fn signature_help(&mut self, params: SignatureHelpParams) -> LspResult<Option<SignatureHelp>> {
@@ -1179,8 +1843,24 @@ impl TypstLanguageServer {
         &mut self,
         params: WorkspaceSymbolParams,
     ) -> LspResult<Option<Vec<SymbolInformation>>> {
+        let partial = self.partial_result_sink(params.partial_result_params.partial_result_token);
         let pattern = (!params.query.is_empty()).then_some(params.query);
-        run_query!(self.Symbol(pattern))
+        let sink = partial.clone();
+        let resp = run_query!(self.Symbol(pattern, partial))?;
+        Ok(finish_partial_result(sink, resp))
+    }
+
+    /// Creates a sink reporting partial results to the client, if the client
+    /// requests them by a partial result token.
+    fn partial_result_sink<T: Serialize + 'static>(
+        &self,
+        token: Option<ProgressToken>,
+    ) -> Option<tinymist_query::PartialResultSink<T>> {
+        let token = token?;
+        let client = self.client.clone();
+        Some(tinymist_query::PartialResultSink::new(move |batch: T| {
+            client.send_partial_result(token.clone(), batch)
+        }))
     }
 }
 
@@ -1199,6 +1879,44 @@ fn parse_opts(v: Option<&JsonValue>) -> LspResult<ExportOpts> {
     })
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BundleOpts {
+    #[serde(default)]
+    no_sources: bool,
+    /// Whether to also bundle the first page as an SVG image.
+    #[serde(default)]
+    svg: bool,
+    /// Whether to also bundle the first page as a PNG image.
+    #[serde(default)]
+    png: bool,
+}
+
+impl BundleOpts {
+    /// The artifacts to bundle besides the PDF.
+    fn kinds(&self) -> Vec<ExportKind> {
+        let page = PageSelection::First;
+        let svg = self.svg.then_some(ExportKind::Svg { page });
+        let png = self.png.then_some(ExportKind::Png { page });
+        svg.into_iter().chain(png).collect()
+    }
+}
+
+fn parse_bundle_opts(v: Option<&JsonValue>) -> LspResult<BundleOpts> {
+    Ok(match v {
+        Some(opts) => serde_json::from_value::<BundleOpts>(opts.clone())
+            .map_err(|_| invalid_params("The third argument is not a valid object"))?,
+        _ => BundleOpts::default(),
+    })
+}
+
+/// Whether the export options ask to write the file even if it is unchanged.
+fn parse_force(v: Option<&JsonValue>) -> bool {
+    v.and_then(|opts| opts.get("force"))
+        .and_then(JsonValue::as_bool)
+        .unwrap_or(false)
+}
+
 fn parse_path(v: Option<&JsonValue>) -> LspResult<ImmutPath> {
     let new_entry = match v {
         Some(JsonValue::String(s)) => Path::new(s).clean().as_path().into(),
@@ -77,14 +77,44 @@ pub struct CompileExtraOpts {
 const CONFIG_ITEMS: &[&str] = &[
     "outputPath",
     "exportPdf",
+    "exportPdfSplitPages",
+    "exportPngTransparent",
+    "exportPngMaxPixels",
+    "exportThumbnail",
+    "exportFileMode",
+    "exportPdfCreator",
+    "exportPdfProducer",
+    "postExportCommand",
+    "enablePostExportCommand",
     "rootPath",
+    "entryCandidates",
+    "workspaceBase",
+    "untitledRoot",
+    "targets",
+    "checkEntry",
+    "paperOverride",
     "semanticTokens",
     "formatterMode",
     "formatterPrintWidth",
     "fontPaths",
     "systemFonts",
+    "preferUserFonts",
     "typstExtraArgs",
     "compileStatus",
+    "scanIgnoredFiles",
+    "watchExtensions",
+    "compileOnlyActive",
+    "diagnosticsMaxRate",
+    "maxDiagnosticsPerFile",
+    "lintRedefinitions",
+    "suppressedDiagnostics",
+    "outOfRootDiagnostics",
+    "diagnosticVerbosity",
+    "analysisThreads",
+    "documentHistorySize",
+    "abortOnPanic",
+    "offline",
+    "completionSnippets",
     "preferredTheme",
     "hoverPeriscope",
 ];
@@ -180,6 +210,12 @@ pub struct ConstConfig {
     pub doc_line_folding_only: bool,
     /// Allow dynamic registration of document formatting.
     pub doc_fmt_dynamic_registration: bool,
+    /// Allow dynamic registration of watched files.
+    pub watched_files_dynamic_registration: bool,
+    /// Allow resolving the details of completion items lazily.
+    pub completion_resolve_support: bool,
+    /// Allow reporting the progress of work initiated by the server.
+    pub work_done_progress: bool,
 }
 
 impl From<&InitializeParams> for ConstConfig {
@@ -199,10 +235,13 @@ impl From<&InitializeParams> for ConstConfig {
         };
 
         let workspace = params.capabilities.workspace.as_ref();
+        let window = params.capabilities.window.as_ref();
         let doc = params.capabilities.text_document.as_ref();
         let sema = try_(|| doc?.semantic_tokens.as_ref());
         let fold = try_(|| doc?.folding_range.as_ref());
         let format = try_(|| doc?.formatting.as_ref());
+        let completion_item = try_(|| doc?.completion.as_ref()?.completion_item.as_ref());
+        let resolve_support = try_(|| completion_item?.resolve_support.as_ref());
 
         Self {
             position_encoding,
@@ -212,6 +251,15 @@ impl From<&InitializeParams> for ConstConfig {
             tokens_multiline_token_support: try_or(|| sema?.multiline_token_support, false),
             doc_line_folding_only: try_or(|| fold?.line_folding_only, true),
             doc_fmt_dynamic_registration: try_or(|| format?.dynamic_registration, false),
+            watched_files_dynamic_registration: try_or(
+                || workspace?.did_change_watched_files?.dynamic_registration,
+                false,
+            ),
+            work_done_progress: try_or(|| window?.work_done_progress, false),
+            completion_resolve_support: try_or(
+                || Some(resolve_support?.properties.iter().any(|p| p == "detail")),
+                false,
+            ),
         }
     }
 }
@@ -301,9 +349,11 @@ impl Init {
             self.host.clone(),
             editor_rx,
             service.config.compile.notify_compile_status,
+            service.config.compile.diagnostics_max_rate,
         );
 
         service.primary.restart_server("primary");
+        service.restart_check_server();
 
         // Run the cluster in the background after we referencing it
         self.handle.spawn(editor_actor.run());
@@ -355,6 +405,7 @@ impl Init {
                         String::from("\""),
                         String::from("@"),
                     ]),
+                    resolve_provider: Some(true),
                     ..Default::default()
                 }),
                 text_document_sync: Some(TextDocumentSyncCapability::Options(
@@ -410,6 +461,8 @@ impl Init {
 mod tests {
     use super::*;
     use serde_json::json;
+    use std::path::Path;
+    use typst::syntax::{FileId, VirtualPath};
 
     #[test]
     fn test_config_update() {
@@ -452,6 +505,37 @@ mod tests {
         config.update(&update).unwrap();
     }
 
+    #[test]
+    fn test_untitled_root() {
+        let mut config = Config::default();
+
+        let untitled_root = if cfg!(windows) {
+            "C:\\scratch"
+        } else {
+            "/scratch"
+        };
+        let update = json!({
+            "untitledRoot": untitled_root,
+        });
+
+        config.update(&update).unwrap();
+
+        let entry = config
+            .compile
+            .determine_entry(Some(Path::new("/untitled/Untitled-1").into()));
+        assert_eq!(entry.root(), Some(Path::new(untitled_root).into()));
+        assert_eq!(
+            entry.main(),
+            Some(FileId::new(None, VirtualPath::new("Untitled-1")))
+        );
+        assert_eq!(
+            config
+                .compile
+                .determine_untitled_path(Path::new("/untitled/Untitled-1")),
+            Some(Path::new(untitled_root).join("Untitled-1").as_path().into())
+        );
+    }
+
     #[test]
     fn test_reject_abnormal_root() {
         let mut config = Config::default();
@@ -473,4 +557,33 @@ mod tests {
         let err = format!("{}", config.update(&update).unwrap_err());
         assert!(err.contains("absolute path"), "unexpected error: {}", err);
     }
+
+    #[test]
+    fn test_entry_candidates() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        std::fs::write(root.join("paper.typ"), "").unwrap();
+
+        let mut config = Config::default();
+        let update = json!({
+            "rootPath": root,
+            "entryCandidates": ["main.typ", "paper.typ"],
+        });
+        config.update(&update).unwrap();
+        let paper = config.compile.determine_default_entry_path();
+        let has_paper = config.compile.has_default_entry_path;
+
+        // A candidate with a higher priority is preferred once it exists.
+        std::fs::write(root.join("main.typ"), "").unwrap();
+        let main = config.compile.determine_default_entry_path();
+
+        std::fs::remove_file(root.join("main.typ")).unwrap();
+        std::fs::remove_file(root.join("paper.typ")).unwrap();
+        let none = config.compile.determine_default_entry_path();
+
+        assert_eq!(paper, Some(root.join("paper.typ").as_path().into()));
+        assert!(has_paper);
+        assert_eq!(main, Some(root.join("main.typ").as_path().into()));
+        assert_eq!(none, None);
+    }
 }
@@ -1,12 +1,13 @@
 //! Bootstrap actors for Tinymist.
 
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 use anyhow::anyhow;
 use lsp_types::TextDocumentContentChangeEvent;
 use tinymist_query::{
     lsp_to_typst, CompilerQueryRequest, CompilerQueryResponse, FoldRequestFeature, OnExportRequest,
-    OnSaveExportRequest, PositionEncoding, SemanticRequest, StatefulRequest, SyntaxRequest,
+    OnSaveExportRequest, PositionEncoding, SemanticRequest, ServerInfoResponse, StatefulRequest,
+    SyntaxRequest,
 };
 use typst::{diag::FileResult, syntax::Source};
 use typst_ts_compiler::{
@@ -15,7 +16,11 @@ use typst_ts_compiler::{
 };
 use typst_ts_core::{error::prelude::*, Bytes, Error, ImmutPath};
 
-use crate::{actor::typ_client::CompileClientActor, compiler::CompileServer, TypstLanguageServer};
+use crate::{
+    actor::{typ_client::CompileClientActor, CHECK_DIAG_GROUP},
+    compiler::CompileServer,
+    utils, TypstLanguageServer,
+};
 
 impl CompileServer {
     /// Focus main file to some path.
@@ -39,14 +44,42 @@ impl TypstLanguageServer {
 
     /// Updates the primary (focusing) entry
     pub fn focus_entry(&mut self, new_entry: Option<ImmutPath>) -> Result<bool, Error> {
+        // The primary one always follows or pins the entry, so only the dedicated
+        // ones are paused in background.
+        for dedicate in self.dedicates.iter_mut() {
+            let client = dedicate.compiler.as_mut().unwrap();
+            // The check entry is always compiled to keep it validated.
+            if client.diag_group == CHECK_DIAG_GROUP {
+                continue;
+            }
+            let active = client.is_focused_by(new_entry.as_deref());
+            client.set_active(active);
+        }
+
+        // The focusing file is tracked to fall back to it when the default entry
+        // is gone.
+        self.focusing.clone_from(&new_entry);
         if self.pinning || self.config.compile.has_default_entry_path {
-            self.focusing = new_entry;
             return Ok(false);
         }
 
         self.primary.do_change_entry(new_entry.clone())
     }
 
+    /// Determines the default entry again, which changes when a candidate entry
+    /// file is created or deleted, and follows it unless the entry is pinned.
+    /// The focusing file becomes the entry if no default entry exists.
+    pub fn refresh_default_entry(&mut self) -> Result<bool, Error> {
+        let default_entry = self.config.compile.determine_default_entry_path();
+        self.config.compile.has_default_entry_path = default_entry.is_some();
+        if self.pinning {
+            return Ok(false);
+        }
+
+        let entry = default_entry.or_else(|| self.focusing.clone());
+        self.primary.do_change_entry(entry)
+    }
+
     /// This is used for tracking activating document status if a client is not
     /// performing any focus command request.
     ///
@@ -144,43 +177,85 @@ impl TypstLanguageServer {
         self.update_source(files)
     }
 
+    /// Discards the unsaved changes of a file on the server side, replacing
+    /// them with the content on the disk, e.g. after it is reverted in the
+    /// editor. Returns false without recompiling if the file isn't shadowed in
+    /// memory.
+    ///
+    /// The file is still synchronized with the editor, whose later changes are
+    /// applied to the content read from the disk.
+    pub fn revert_source(&mut self, path: PathBuf) -> Result<bool, Error> {
+        let path: ImmutPath = path.into();
+        let Some(meta) = self.primary.memory_changes.get_mut(&path) else {
+            return Ok(false);
+        };
+
+        let content = std::fs::read_to_string(&path).map_err(
+            |err| error_once!("failed to read file", path: path.display(), err: err.to_string()),
+        )?;
+        log::info!("revert source: {:?}", path);
+
+        let now = Time::now();
+        meta.mt = now;
+        meta.content.replace(&content);
+
+        let content: Bytes = content.as_bytes().into();
+        let files = FileChangeSet::new_inserts(vec![(path, FileResult::Ok((now, content)).into())]);
+
+        self.update_source(files)?;
+        Ok(true)
+    }
+
     pub fn edit_source(
         &mut self,
         path: PathBuf,
         content: Vec<TextDocumentContentChangeEvent>,
         position_encoding: PositionEncoding,
     ) -> Result<(), Error> {
-        let now = Time::now();
-        let path: ImmutPath = path.into();
+        let files = edit_memory_file(
+            &mut self.primary.memory_changes,
+            path.into(),
+            content,
+            position_encoding,
+            Time::now(),
+        )?;
 
-        let meta = self
-            .primary
-            .memory_changes
-            .get_mut(&path)
-            .ok_or_else(|| error_once!("file missing", path: path.display()))?;
-
-        for change in content {
-            let replacement = change.text;
-            match change.range {
-                Some(lsp_range) => {
-                    let range = lsp_to_typst::range(lsp_range, position_encoding, &meta.content)
-                        .expect("invalid range");
-                    meta.content.edit(range, &replacement);
-                }
-                None => {
-                    meta.content.replace(&replacement);
-                }
+        self.update_source(files)
+    }
+}
+
+/// Applies the changes made in the editor to a file in memory, returning the
+/// changed file. It fails if the file isn't opened in the editor.
+fn edit_memory_file(
+    memory_changes: &mut HashMap<ImmutPath, MemoryFileMeta>,
+    path: ImmutPath,
+    content: Vec<TextDocumentContentChangeEvent>,
+    position_encoding: PositionEncoding,
+    now: Time,
+) -> Result<FileChangeSet, Error> {
+    let meta = memory_changes
+        .get_mut(&path)
+        .ok_or_else(|| error_once!("file missing", path: path.display()))?;
+
+    for change in content {
+        let replacement = change.text;
+        match change.range {
+            Some(lsp_range) => {
+                let range = lsp_to_typst::range(lsp_range, position_encoding, &meta.content)
+                    .expect("invalid range");
+                meta.content.edit(range, &replacement);
+            }
+            None => {
+                meta.content.replace(&replacement);
             }
         }
+    }
 
-        meta.mt = now;
-
-        let snapshot = FileResult::Ok((now, meta.content.text().as_bytes().into())).into();
+    meta.mt = now;
 
-        let files = FileChangeSet::new_inserts(vec![(path.clone(), snapshot)]);
+    let snapshot = FileResult::Ok((now, meta.content.text().as_bytes().into())).into();
 
-        self.update_source(files)
-    }
+    Ok(FileChangeSet::new_inserts(vec![(path, snapshot)]))
 }
 
 #[macro_export]
@@ -191,8 +266,8 @@ macro_rules! run_query {
         $self
             .query(CompilerQueryRequest::$query(req.clone()))
             .map_err(|err| {
-                error!("error getting $query: {err} with request {req:?}");
-                internal_error("Internal error")
+                error!("error getting $query: {err:#} with request {req:?}");
+                internal_error(format!("Internal error: {err:#}"))
             })
             .map(|resp| {
                 let CompilerQueryResponse::$query(resp) = resp else {
@@ -249,7 +324,37 @@ impl TypstLanguageServer {
         let snapshot = self.primary.memory_changes.get(&path);
         let snapshot = snapshot.ok_or_else(|| anyhow!("file missing {path:?}"))?;
         let source = snapshot.content.clone();
-        f(source)
+        if self.config.compile.abort_on_panic {
+            return f(source);
+        }
+
+        utils::catch_panic(|| f(source))
+            .map_err(|msg| anyhow!("the query panicked: {msg}"))
+            .and_then(|res| res)
+    }
+
+    /// Collects the information of all compile servers, keyed by their
+    /// diagnostics groups.
+    ///
+    /// The information of each server is collected separately, and a server
+    /// failing to respond is skipped.
+    pub fn collect_server_info(&self) -> HashMap<String, ServerInfoResponse> {
+        let mut res = HashMap::new();
+        for client in Some(self.primary())
+            .into_iter()
+            .chain(self.dedicates.iter().map(|v| v.compiler()))
+        {
+            match client.collect_server_info() {
+                Ok(info) => res.extend(info),
+                Err(err) => {
+                    log::error!(
+                        "failed to collect server info of {}: {err}",
+                        client.diag_group
+                    )
+                }
+            }
+        }
+        res
     }
 
     pub fn query(&mut self, query: CompilerQueryRequest) -> anyhow::Result<CompilerQueryResponse> {
@@ -262,7 +367,13 @@ impl TypstLanguageServer {
             FoldingRange(req) => query_source!(self, FoldingRange, req),
             SelectionRange(req) => query_source!(self, SelectionRange, req),
             DocumentSymbol(req) => query_source!(self, DocumentSymbol, req),
+            StyleRules(req) => query_source!(self, StyleRules, req),
+            Breadcrumbs(req) => query_source!(self, Breadcrumbs, req),
             ColorPresentation(req) => Ok(CompilerQueryResponse::ColorPresentation(req.request())),
+            ServerInfo(_) => {
+                let res = self.collect_server_info();
+                Ok(CompilerQueryResponse::ServerInfo(Some(res)))
+            }
             _ => {
                 let client = &mut self.primary;
                 if !self.pinning && !self.config.compile.has_default_entry_path {
@@ -284,8 +395,8 @@ impl TypstLanguageServer {
         assert!(query.fold_feature() != FoldRequestFeature::ContextFreeUnique);
 
         match query {
-            OnExport(OnExportRequest { kind, path }) => Ok(CompilerQueryResponse::OnExport(
-                client.on_export(kind, path)?,
+            OnExport(OnExportRequest { kind, path, force }) => Ok(CompilerQueryResponse::OnExport(
+                client.on_export(kind, path, force)?,
             )),
             OnSaveExport(OnSaveExportRequest { path }) => {
                 client.on_save_export(path)?;
@@ -306,6 +417,16 @@ impl TypstLanguageServer {
             PrepareRename(req) => query_state!(client, PrepareRename, req),
             Symbol(req) => query_world!(client, Symbol, req),
             DocumentMetrics(req) => query_state!(client, DocumentMetrics, req),
+            DocumentFonts(req) => query_state!(client, DocumentFonts, req),
+            DocumentMetadata(req) => query_state!(client, DocumentMetadata, req),
+            PageText(req) => query_state!(client, PageText, req),
+            DocumentHeadings(req) => query_state!(client, DocumentHeadings, req),
+            DocumentOutline(req) => query_state!(client, DocumentOutline, req),
+            ForwardSearch(req) => query_state!(client, ForwardSearch, req),
+            CheckReferences(req) => query_state!(client, CheckReferences, req),
+            WhyDepends(req) => query_world!(client, WhyDepends, req),
+            SyntaxTree(req) => query_world!(client, SyntaxTree, req),
+            DocumentResources(req) => query_world!(client, DocumentResources, req),
             ServerInfo(_) => {
                 let res = client.collect_server_info()?;
                 Ok(CompilerQueryResponse::ServerInfo(Some(res)))
@@ -321,3 +442,53 @@ impl CompileServer {
         TypstLanguageServer::query_on(client, query)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::*;
+
+    #[test]
+    fn test_edit_memory_file() {
+        let path: ImmutPath = Path::new("/main.typ").into();
+        let change = |range: Option<(u32, u32)>, text: &str| TextDocumentContentChangeEvent {
+            range: range.map(|(start, end)| lsp_types::Range {
+                start: lsp_types::Position::new(0, start),
+                end: lsp_types::Position::new(0, end),
+            }),
+            range_length: None,
+            text: text.to_owned(),
+        };
+        let edit = |memory_changes: &mut HashMap<_, _>, changes| {
+            let now = Time::now();
+            edit_memory_file(
+                memory_changes,
+                path.clone(),
+                changes,
+                PositionEncoding::Utf16,
+                now,
+            )
+        };
+
+        let mut memory_changes = HashMap::new();
+        memory_changes.insert(
+            path.clone(),
+            MemoryFileMeta {
+                mt: Time::now(),
+                content: Source::detached("Hello"),
+            },
+        );
+        let files = edit(&mut memory_changes, vec![change(Some((5, 5)), " World")]).unwrap();
+        assert_eq!(files.inserts.len(), 1);
+        assert_eq!(memory_changes[&path].content.text(), "Hello World");
+        edit(&mut memory_changes, vec![change(None, "Bye")]).unwrap();
+        assert_eq!(memory_changes[&path].content.text(), "Bye");
+
+        // A reverted file is no longer in memory, whose edits are not applied
+        // to the content on the disk.
+        memory_changes.remove(&path);
+        assert!(edit(&mut memory_changes, vec![change(None, "Hello")]).is_err());
+        assert!(memory_changes.is_empty());
+    }
+}
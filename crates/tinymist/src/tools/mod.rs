@@ -1,4 +1,6 @@
 pub mod package;
+pub mod pdf_info;
+pub mod sarif;
 pub mod word_count;
 
 #[cfg(feature = "preview")]
@@ -1,8 +1,12 @@
-use typst::diag::{eco_format, StrResult};
-use typst::syntax::package::{PackageVersion, VersionlessPackageSpec};
+use std::fmt::Write;
+use std::path::PathBuf;
+
+use serde::Serialize;
+use typst::diag::{eco_format, PackageError, StrResult};
+use typst::syntax::package::{PackageSpec, PackageVersion, VersionlessPackageSpec};
 use typst_ts_compiler::package::Registry;
 
-use crate::world::LspWorld;
+use crate::world::{package_subdir, package_version_subdir, LspRegistry, LspWorld};
 
 mod init;
 pub use init::*;
@@ -24,7 +28,7 @@ pub fn determine_latest_version(
         // For other namespaces, search locally. We only search in the data
         // directory and not the cache directory, because the latter is not
         // intended for storage of local packages.
-        let subdir = format!("typst/packages/{}/{}", spec.namespace, spec.name);
+        let subdir = package_subdir(&spec.namespace, &spec.name);
         world
             .registry
             .local_path()
@@ -38,3 +42,120 @@ pub fn determine_latest_version(
             .ok_or_else(|| eco_format!("please specify the desired version"))
     }
 }
+
+/// Adds details of the resolution to a package error, i.e. the namespace and
+/// the local path that were searched, so that the user knows how to fix it.
+pub fn describe_package_error(
+    world: &LspWorld,
+    spec: &PackageSpec,
+    err: PackageError,
+) -> PackageError {
+    let PackageError::NotFound(..) = err else {
+        return err;
+    };
+
+    let mut msg = eco_format!(
+        "package not found (searched for {spec} in namespace `{}`",
+        spec.namespace
+    );
+    if let Some(dir) = world.registry.local_path() {
+        let subdir = package_version_subdir(spec);
+        let _ = write!(msg, ", checked {}", dir.join(subdir).display());
+    }
+    msg.push(')');
+
+    if spec.namespace == "preview" {
+        msg.push_str(
+            "\n\nHint: the package is downloaded on compilation, \
+             check your network connection and compile again to download it",
+        );
+    } else {
+        msg.push_str("\n\nHint: put the package at the checked local path to use it");
+    }
+
+    PackageError::Other(Some(msg))
+}
+
+/// A package resolved to a directory on the disk.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ResolvedPackage {
+    /// The namespace of the package.
+    pub namespace: String,
+    /// The name of the package.
+    pub name: String,
+    /// The version of the package, which is the latest one if it is not
+    /// specified.
+    pub version: String,
+    /// The directory of the package.
+    pub path: PathBuf,
+}
+
+/// Resolves a package specification to the directory of the package, which
+/// downloads the package if it is not cached yet.
+///
+/// The version may be omitted, e.g. `@preview/example`, in which case the
+/// latest version is selected.
+pub fn resolve_package(world: &LspWorld, spec: &str) -> StrResult<ResolvedPackage> {
+    let spec: PackageSpec = spec.parse().or_else(|err| {
+        // Try to parse without version, but prefer the error message of the
+        // normal package spec parsing if it fails.
+        let spec: VersionlessPackageSpec = spec.parse().map_err(|_| err)?;
+        let version = determine_latest_version(world, &spec)?;
+        StrResult::Ok(spec.at(version))
+    })?;
+
+    let path = world
+        .registry
+        .resolve(&spec)
+        .map_err(|err| describe_package_error(world, &spec, err))?;
+
+    Ok(ResolvedPackage {
+        namespace: spec.namespace.to_string(),
+        name: spec.name.to_string(),
+        version: spec.version.to_string(),
+        path: path.to_path_buf(),
+    })
+}
+
+/// The result of prefetching a package.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PackagePrefetch {
+    /// The specification of the package.
+    pub spec: String,
+    /// The error message if the package could not be fetched.
+    pub error: Option<String>,
+}
+
+/// Downloads the packages which are not cached yet, calling `progress` with
+/// the package, its index and the number of packages before downloading each
+/// package.
+///
+/// The registry is cloned from the world, so it can be run off the compiler
+/// thread.
+pub fn prefetch_packages(
+    registry: &LspRegistry,
+    specs: Vec<PackageSpec>,
+    mut progress: impl FnMut(&PackageSpec, usize, usize),
+) -> Vec<PackagePrefetch> {
+    let total = specs.len();
+
+    specs
+        .into_iter()
+        .enumerate()
+        .map(|(idx, spec)| {
+            log::info!("prefetching package {spec} ({}/{total})", idx + 1);
+            progress(&spec, idx, total);
+            let error = registry.resolve_with_retries(&spec).err().map(|err| {
+                log::warn!("failed to prefetch package {spec}: {err}");
+                err.to_string()
+            });
+
+            PackagePrefetch {
+                spec: spec.to_string(),
+                error,
+            }
+        })
+        .collect()
+}
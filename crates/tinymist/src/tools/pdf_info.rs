@@ -0,0 +1,208 @@
+//! Overrides the document information of the PDFs written by typst.
+//!
+//! The writer of typst doesn't take the creator or the producer of a PDF, so
+//! the PDF written by typst is parsed and written again, with the entries
+//! replaced in both the document information dictionary and the XMP metadata.
+
+use log::warn;
+use lopdf::{Dictionary, Document, Object, StringFormat};
+
+/// The creator tool property in XMP metadata and its namespace.
+const XMP_CREATOR_TOOL: (&str, &str) = ("xmp:CreatorTool", "http://ns.adobe.com/xap/1.0/");
+/// The producer property in XMP metadata and its namespace.
+const XMP_PRODUCER: (&str, &str) = ("pdf:Producer", "http://ns.adobe.com/pdf/1.3/");
+
+/// Overrides the creator and the producer of a PDF written by typst.
+///
+/// A `None` keeps the value written by typst, while an empty string removes
+/// the entry. The PDF is returned unchanged if it cannot be parsed.
+pub fn override_pdf_info(pdf: Vec<u8>, creator: Option<&str>, producer: Option<&str>) -> Vec<u8> {
+    if creator.is_none() && producer.is_none() {
+        return pdf;
+    }
+
+    match rewrite_pdf_info(&pdf, creator, producer) {
+        Ok(pdf) => pdf,
+        Err(err) => {
+            warn!("failed to override the creator or the producer of the PDF: {err}");
+            pdf
+        }
+    }
+}
+
+/// Rewrites a PDF with the document information overridden.
+fn rewrite_pdf_info(
+    pdf: &[u8],
+    creator: Option<&str>,
+    producer: Option<&str>,
+) -> lopdf::Result<Vec<u8>> {
+    let mut doc = Document::load_mem(pdf)?;
+
+    let info = match doc.trailer.get(b"Info").and_then(Object::as_reference) {
+        Ok(info) => info,
+        Err(_) => {
+            let info = doc.add_object(Dictionary::new());
+            doc.trailer.set("Info", info);
+            info
+        }
+    };
+    let info = doc.get_dictionary_mut(info)?;
+    for (key, value) in [(&b"Creator"[..], creator), (b"Producer", producer)] {
+        match value {
+            None => {}
+            Some("") => {
+                info.remove(key);
+            }
+            Some(value) => info.set(key, text_string(value)),
+        }
+    }
+
+    // The creator and the producer are also written to the XMP metadata.
+    let metadata = doc
+        .catalog()?
+        .get(b"Metadata")
+        .and_then(Object::as_reference);
+    if let Ok(metadata) = metadata {
+        let stream = doc.get_object_mut(metadata)?.as_stream_mut()?;
+        let content = stream
+            .decompressed_content()
+            .unwrap_or_else(|_| stream.content.clone());
+        match String::from_utf8(content) {
+            Ok(mut xmp) => {
+                for (property, value) in [(XMP_CREATOR_TOOL, creator), (XMP_PRODUCER, producer)] {
+                    if let Some(value) = value {
+                        xmp = override_xmp_property(&xmp, property, value);
+                    }
+                }
+                stream.set_plain_content(xmp.into_bytes());
+            }
+            Err(_) => warn!("failed to decode the XMP metadata of the PDF"),
+        }
+    }
+
+    let mut pdf = vec![];
+    doc.save_to(&mut pdf)?;
+    Ok(pdf)
+}
+
+/// Encodes a text string, which is a literal string if it is ASCII, otherwise
+/// a string in UTF-16BE with a byte order mark.
+fn text_string(text: &str) -> Object {
+    if text.is_ascii() {
+        return Object::string_literal(text);
+    }
+
+    let mut bytes = vec![0xFE, 0xFF];
+    bytes.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+    Object::String(bytes, StringFormat::Hexadecimal)
+}
+
+/// Replaces, removes or adds a simple property written as an element in XMP
+/// metadata. An added property declares its own namespace, since the
+/// description may not declare it.
+fn override_xmp_property(xmp: &str, (name, namespace): (&str, &str), value: &str) -> String {
+    let end_tag = format!("</{name}>");
+    let escaped = value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+
+    // The start tag may carry attributes, e.g. the namespace added by us.
+    let open = format!("<{name}");
+    let start = xmp
+        .match_indices(&open)
+        .map(|(start, _)| start)
+        .find(|start| {
+            let after = xmp[start + open.len()..].chars().next();
+            after.is_some_and(|c| c == '>' || c.is_whitespace())
+        });
+    if let Some(start) = start {
+        let (Some(content), Some(end)) = (xmp[start..].find('>'), xmp[start..].find(&end_tag))
+        else {
+            return xmp.to_owned();
+        };
+        let (content, end) = (start + content + 1, start + end);
+        return if value.is_empty() {
+            format!("{}{}", &xmp[..start], &xmp[end + end_tag.len()..])
+        } else {
+            format!("{}{escaped}{}", &xmp[..content], &xmp[end..])
+        };
+    }
+
+    let description_end = xmp.find("</rdf:Description>");
+    match description_end.filter(|_| !value.is_empty()) {
+        Some(at) => {
+            let prefix = name.split(':').next().unwrap_or_default();
+            let added = format!("<{name} xmlns:{prefix}=\"{namespace}\">{escaped}{end_tag}");
+            format!("{}{added}{}", &xmp[..at], &xmp[at..])
+        }
+        None => xmp.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use typst::foundations::Smart;
+    use typst_ts_core::TypstDocument;
+
+    use super::*;
+
+    /// Gets an entry of the document information and the XMP metadata.
+    fn info_of(pdf: &[u8], key: &[u8]) -> (Option<Vec<u8>>, String) {
+        let doc = Document::load_mem(pdf).unwrap();
+        let info = doc.trailer.get(b"Info").unwrap().as_reference().unwrap();
+        let info = doc.get_dictionary(info).unwrap();
+        let value = info.get(key).ok().map(|v| v.as_str().unwrap().to_vec());
+
+        let metadata = doc.catalog().unwrap().get(b"Metadata").unwrap();
+        let metadata = metadata.as_reference().unwrap();
+        let stream = doc.get_object(metadata).unwrap().as_stream().unwrap();
+        (value, String::from_utf8(stream.content.clone()).unwrap())
+    }
+
+    #[test]
+    fn test_override_pdf_info() {
+        let doc = TypstDocument::default();
+        let pdf = typst_pdf::pdf(&doc, Smart::Auto, None);
+        assert!(info_of(&pdf, b"Creator").0.is_some());
+
+        let pdf = override_pdf_info(pdf, Some(""), Some("Acme (Ltd)"));
+        let (creator, xmp) = info_of(&pdf, b"Creator");
+        assert_eq!(creator, None);
+        assert!(!xmp.contains("xmp:CreatorTool"));
+        let (producer, xmp) = info_of(&pdf, b"Producer");
+        assert_eq!(producer.as_deref(), Some(&b"Acme (Ltd)"[..]));
+        assert!(xmp.contains("Acme (Ltd)</pdf:Producer>"));
+
+        let pdf = override_pdf_info(pdf, Some("Ünï"), None);
+        let (creator, xmp) = info_of(&pdf, b"Creator");
+        let utf16 = [0xFE, 0xFF, 0x00, 0xDC, 0x00, 0x6E, 0x00, 0xEF];
+        assert_eq!(creator.as_deref(), Some(&utf16[..]));
+        assert!(xmp.contains("<xmp:CreatorTool xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\">Ünï"));
+        assert!(info_of(&pdf, b"Producer").0.is_some());
+
+        // An empty string removes the value overridden before.
+        let pdf = override_pdf_info(pdf, None, Some(""));
+        let (producer, xmp) = info_of(&pdf, b"Producer");
+        assert_eq!(producer, None);
+        assert!(!xmp.contains("pdf:Producer"));
+    }
+
+    #[test]
+    fn test_override_xmp_property() {
+        let xmp =
+            "<rdf:Description><xmp:CreatorTool>Typst 0.11.1</xmp:CreatorTool></rdf:Description>";
+        assert_eq!(
+            override_xmp_property(xmp, XMP_CREATOR_TOOL, "A & B"),
+            "<rdf:Description><xmp:CreatorTool>A &amp; B</xmp:CreatorTool></rdf:Description>"
+        );
+        assert_eq!(
+            override_xmp_property(xmp, XMP_CREATOR_TOOL, ""),
+            "<rdf:Description></rdf:Description>"
+        );
+        assert_eq!(
+            override_xmp_property("<rdf:Description></rdf:Description>", XMP_PRODUCER, "A"),
+            "<rdf:Description><pdf:Producer xmlns:pdf=\"http://ns.adobe.com/pdf/1.3/\">A</pdf:Producer></rdf:Description>"
+        );
+    }
+}
@@ -0,0 +1,165 @@
+//! Serializes diagnostics to the SARIF format, which is read by code scanning
+//! tools such as GitHub code scanning.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use lsp_types::{DiagnosticSeverity, Url};
+use serde_json::{json, Value as JsonValue};
+use tinymist_query::{
+    diagnostic_category, diagnostic_id, DiagnosticsMap, LspDiagnostic, LspRange, PositionEncoding,
+};
+
+/// The version of SARIF.
+const SARIF_VERSION: &str = "2.1.0";
+/// The schema of SARIF.
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Serializes the diagnostics to a SARIF log with a single run.
+///
+/// Each diagnostic is reported under a rule, which is its code if it has one,
+/// otherwise its category. The files under the root are located relatively to
+/// `%SRCROOT%`, which is required by GitHub code scanning. The columns are
+/// counted in the position encoding of the diagnostics.
+pub fn sarif_log(
+    diagnostics: &DiagnosticsMap,
+    root: Option<&Path>,
+    position_encoding: PositionEncoding,
+) -> JsonValue {
+    let mut files = diagnostics.iter().collect::<Vec<_>>();
+    files.sort_by(|a, b| a.0.cmp(b.0));
+
+    let mut rules = BTreeMap::new();
+    let mut results = vec![];
+    for (uri, diags) in files {
+        for diag in diags {
+            let rule_id = diagnostic_category(diag);
+            rules.entry(rule_id.clone()).or_insert_with(|| {
+                json!({
+                    "id": rule_id,
+                    "shortDescription": { "text": rule_id },
+                })
+            });
+
+            let related = diag.related_information.iter().flatten();
+            let related = related.enumerate().map(|(idx, info)| {
+                let mut location = location(&info.location.uri, &info.location.range, root);
+                location["id"] = json!(idx);
+                location["message"] = json!({ "text": info.message });
+                location
+            });
+
+            let mut result = json!({
+                "ruleId": rule_id,
+                "level": level(diag),
+                "message": { "text": diag.message },
+                "locations": [location(uri, &diag.range, root)],
+                "relatedLocations": related.collect::<Vec<_>>(),
+            });
+            if let Some(id) = diagnostic_id(diag) {
+                result["partialFingerprints"] = json!({ "tinymistId/v1": id });
+            }
+            results.push(result);
+        }
+    }
+
+    json!({
+        "version": SARIF_VERSION,
+        "$schema": SARIF_SCHEMA,
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "tinymist",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/Myriad-Dreamin/tinymist",
+                    "rules": rules.into_values().collect::<Vec<_>>(),
+                },
+            },
+            "columnKind": column_kind(position_encoding),
+            "results": results,
+        }],
+    })
+}
+
+/// Gets the kind of the columns counted in a position encoding. SARIF has no
+/// kind for UTF-8 bytes, which are the same as the code points in ASCII text.
+fn column_kind(position_encoding: PositionEncoding) -> &'static str {
+    match position_encoding {
+        PositionEncoding::Utf16 => "utf16CodeUnits",
+        PositionEncoding::Utf8 => "unicodeCodePoints",
+    }
+}
+
+/// Converts the severity of a diagnostic to the level of a result.
+fn level(diag: &LspDiagnostic) -> &'static str {
+    match diag.severity {
+        Some(DiagnosticSeverity::ERROR) | None => "error",
+        Some(DiagnosticSeverity::WARNING) => "warning",
+        Some(_) => "note",
+    }
+}
+
+/// Converts a range in a file to a physical location, whose lines and columns
+/// start from 1.
+fn location(uri: &Url, range: &LspRange, root: Option<&Path>) -> JsonValue {
+    let path = uri.to_file_path().ok();
+    let relative = path
+        .as_deref()
+        .zip(root)
+        .and_then(|(path, root)| path.strip_prefix(root).ok());
+    let artifact = match relative {
+        Some(relative) => json!({
+            "uri": relative.to_string_lossy().replace('\\', "/"),
+            "uriBaseId": "%SRCROOT%",
+        }),
+        None => json!({ "uri": uri.as_str() }),
+    };
+
+    json!({
+        "physicalLocation": {
+            "artifactLocation": artifact,
+            "region": {
+                "startLine": range.start.line + 1,
+                "startColumn": range.start.character + 1,
+                "endLine": range.end.line + 1,
+                "endColumn": range.end.character + 1,
+            },
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use lsp_types::Position;
+
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_sarif_log() {
+        let uri = Url::parse("file:///root/main.typ").unwrap();
+        let diag = LspDiagnostic {
+            range: LspRange::new(Position::new(1, 2), Position::new(1, 5)),
+            severity: Some(DiagnosticSeverity::WARNING),
+            message: "unknown variable: `x`".to_owned(),
+            ..Default::default()
+        };
+        let diagnostics = DiagnosticsMap::from([(uri, vec![diag.clone(), diag])]);
+
+        let log = sarif_log(
+            &diagnostics,
+            Some(Path::new("/root")),
+            PositionEncoding::Utf16,
+        );
+        let run = &log["runs"][0];
+        assert_eq!(run["columnKind"], "utf16CodeUnits");
+        assert_eq!(run["tool"]["driver"]["rules"].as_array().unwrap().len(), 1);
+
+        let result = &run["results"][0];
+        assert_eq!(result["level"], "warning");
+        let location = &result["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "main.typ");
+        assert_eq!(location["region"]["startLine"], 2);
+        assert_eq!(location["region"]["startColumn"], 3);
+    }
+}
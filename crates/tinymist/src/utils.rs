@@ -1,3 +1,7 @@
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
 use tokio::sync::oneshot;
@@ -34,6 +38,107 @@ pub fn try_or_default<T: Default>(f: impl FnOnce() -> Option<T>) -> T {
     f().unwrap_or_default()
 }
 
+/// The number of times the memoized results are cleared by
+/// [`evict_all_caches`].
+static CACHE_CLEARS: AtomicUsize = AtomicUsize::new(0);
+
+/// Clears all the memoized results of comemo, so that the following
+/// compilations start from scratch.
+pub fn evict_all_caches() {
+    comemo::evict(0);
+    CACHE_CLEARS.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Gets the number of times the memoized results are cleared, telling whether
+/// a compilation can reuse the results of a previous one.
+pub fn cache_clears() -> usize {
+    CACHE_CLEARS.load(Ordering::SeqCst)
+}
+
+#[allow(deprecated)]
+type PanicHook = Box<dyn Fn(&panic::PanicInfo<'_>) + Sync + Send + 'static>;
+
+/// The number of running [`catch_panic`] calls, and the panic hook replaced
+/// by the one of [`catch_panic`] while they are running.
+static PANIC_HOOK: Mutex<(usize, Option<Arc<PanicHook>>)> = Mutex::new((0, None));
+
+thread_local! {
+    /// The number of running [`catch_panic`] calls on the thread.
+    static CATCHING: Cell<usize> = const { Cell::new(0) };
+    /// The backtrace of the latest caught panic on the thread.
+    static PANIC_BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Runs a function and catches its panic, returning the message of the panic
+/// as an error instead of unwinding further. The backtrace of the panic is
+/// logged, in addition to the message printed by the panic hook.
+///
+/// A panic hook capturing the backtrace is installed only while some
+/// `catch_panic` is running, and the previous hook is restored afterwards.
+///
+/// The state mutated by the function may be inconsistent after a panic, which
+/// should be reset by the caller.
+pub fn catch_panic<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    install_panic_hook();
+    CATCHING.with(|c| c.set(c.get() + 1));
+    let res = panic::catch_unwind(AssertUnwindSafe(f));
+    CATCHING.with(|c| c.set(c.get() - 1));
+    uninstall_panic_hook();
+
+    res.map_err(|payload| {
+        let msg = if let Some(msg) = payload.downcast_ref::<&str>() {
+            msg.to_string()
+        } else if let Some(msg) = payload.downcast_ref::<String>() {
+            msg.clone()
+        } else {
+            "unknown panic".to_owned()
+        };
+        let backtrace = PANIC_BACKTRACE.with(|b| b.borrow_mut().take());
+        log::error!(
+            "caught panic: {msg}\nbacktrace:\n{}",
+            backtrace.as_deref().unwrap_or("unavailable")
+        );
+        msg
+    })
+}
+
+fn install_panic_hook() {
+    let mut hook = PANIC_HOOK.lock().unwrap_or_else(|e| e.into_inner());
+    hook.0 += 1;
+    if hook.0 > 1 {
+        return;
+    }
+
+    let prev = Arc::new(panic::take_hook());
+    let inner = prev.clone();
+    panic::set_hook(Box::new(move |info| {
+        // Panics on other threads are not caught by us.
+        if CATCHING.with(Cell::get) > 0 {
+            let backtrace = std::backtrace::Backtrace::force_capture().to_string();
+            PANIC_BACKTRACE.with(|b| *b.borrow_mut() = Some(backtrace));
+        }
+        inner(info);
+    }));
+    hook.1 = Some(prev);
+}
+
+fn uninstall_panic_hook() {
+    let mut hook = PANIC_HOOK.lock().unwrap_or_else(|e| e.into_inner());
+    hook.0 -= 1;
+    if hook.0 > 0 {
+        return;
+    }
+
+    // Drops our hook, which shares the previous hook.
+    drop(panic::take_hook());
+    if let Some(prev) = hook.1.take() {
+        match Arc::try_unwrap(prev) {
+            Ok(prev) => panic::set_hook(prev),
+            Err(prev) => panic::set_hook(Box::new(move |info| prev(info))),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     fn do_receive() {
@@ -42,6 +147,19 @@ mod tests {
         let res = super::threaded_receive(rx).unwrap();
         assert_eq!(res, 1);
     }
+    #[test]
+    fn test_catch_panic() {
+        assert_eq!(super::catch_panic(|| 1), Ok(1));
+        assert_eq!(
+            super::catch_panic(|| -> i32 { panic!("oops {}", 1) }),
+            Err("oops 1".to_owned())
+        );
+        assert_eq!(
+            super::catch_panic(|| -> i32 { panic!("oops") }),
+            Err("oops".to_owned())
+        );
+    }
+
     #[test]
     fn test_sync() {
         do_receive();
@@ -1,19 +1,33 @@
-use std::{borrow::Cow, path::PathBuf, sync::Arc};
+use std::{
+    borrow::Cow,
+    collections::HashSet,
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
 use comemo::Prehashed;
+use parking_lot::{Mutex, RwLock};
 use serde::{Deserialize, Serialize};
+use typst::diag::{eco_format, EcoString, FileResult, PackageError};
+use typst::foundations::Bytes;
+use typst::syntax::package::PackageSpec;
+use typst::text::{FontBook, FontStretch, FontStyle, FontWeight};
 use typst_ts_core::{
     config::{compiler::EntryState, CompileFontOpts as FontOptsInner},
+    debug_loc::DataSource,
     error::prelude::*,
     font::FontResolverImpl,
-    FontResolver, TypstDict,
+    FontResolver, ImmutPath, TypstDict, TypstFont,
 };
 
 use typst_ts_compiler::{
     font::system::SystemFontSearcher,
-    package::http::HttpRegistry,
-    vfs::{system::SystemAccessModel, Vfs},
+    package::{http::HttpRegistry, Registry},
+    vfs::{system::SystemAccessModel, AccessModel, Vfs},
     world::CompilerWorld,
+    Time,
 };
 
 #[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
@@ -45,20 +59,39 @@ pub struct CompileFontOpts {
     pub font_paths: Vec<PathBuf>,
     /// Exclude system font paths
     pub no_system_fonts: bool,
+    /// Prefer the fonts in the font paths over the system and embedded fonts
+    /// of the same family and variant
+    #[serde(default)]
+    pub prefer_user_fonts: bool,
 }
 
+/// The fonts resolved from the font paths, the system, and the embedded fonts.
+///
+/// The fonts are resolved in layers, one for each font path and one for the
+/// system and embedded fonts, which are merged into a single font book in the
+/// order of precedence. Typst selects the first font in the book among the
+/// fonts matching equally well, so the fonts in earlier font paths win, and the
+/// fonts in the font paths win over the system ones if `prefer_user_fonts` is
+/// set, otherwise the system ones win.
 #[derive(Debug, Clone)]
 pub struct SharedFontResolver {
     font_paths: Vec<PathBuf>,
-    pub inner: Arc<FontResolverImpl>,
+    /// The layers of fonts in the order of precedence, with the font path they
+    /// are resolved from, which is None for the system and embedded fonts.
+    layers: Vec<(Option<PathBuf>, Arc<FontResolverImpl>)>,
+    /// The merged font book.
+    book: Arc<Prehashed<FontBook>>,
+    /// The layer and the index in the layer of each font in the merged book.
+    slots: Vec<(usize, usize)>,
 }
 
 impl FontResolver for SharedFontResolver {
     fn font(&self, idx: usize) -> Option<typst_ts_core::TypstFont> {
-        self.inner.font(idx)
+        let (layer, idx) = *self.slots.get(idx)?;
+        self.layers[layer].1.font(idx)
     }
-    fn font_book(&self) -> &Prehashed<typst::text::FontBook> {
-        self.inner.font_book()
+    fn font_book(&self) -> &Prehashed<FontBook> {
+        &self.book
     }
 }
 
@@ -83,16 +116,90 @@ impl SharedFontResolver {
         }
 
         let font_paths = opts.font_paths.clone();
-        let res = crate::world::LspWorldBuilder::resolve_fonts(opts)?;
+        let mut layers = Vec::with_capacity(font_paths.len() + 1);
+        for path in &font_paths {
+            let fonts = LspWorldBuilder::resolve_font_path(&opts, path)?;
+            layers.push((Some(path.clone()), Arc::new(fonts)));
+        }
+        let prefer_user_fonts = opts.prefer_user_fonts;
+        let system = LspWorldBuilder::resolve_fonts(CompileFontOpts {
+            font_paths: vec![],
+            ..opts
+        })?;
+        let system = (None, Arc::new(system));
+        if prefer_user_fonts {
+            layers.push(system);
+        } else {
+            layers.insert(0, system);
+        }
+
+        let mut book = FontBook::new();
+        let mut slots = vec![];
+        for (layer, (_, fonts)) in layers.iter().enumerate() {
+            let fonts = fonts.font_book();
+            for (idx, info) in (0..).map_while(|idx| Some((idx, fonts.info(idx)?))) {
+                book.push(info.clone());
+                slots.push((layer, idx));
+            }
+        }
+
         Ok(Self {
             font_paths,
-            inner: Arc::new(res),
+            layers,
+            book: Arc::new(Prehashed::new(book)),
+            slots,
         })
     }
 
     pub fn font_paths(&self) -> &[PathBuf] {
         &self.font_paths
     }
+
+    /// Describes the source of a loaded font.
+    pub fn describe_font(&self, font: &TypstFont) -> Option<Arc<DataSource>> {
+        let layers = self.layers.iter();
+        layers.find_map(|(_, fonts)| fonts.describe_font(font))
+    }
+
+    /// Lists the fonts in the order of precedence, telling which font path
+    /// each font is resolved from and whether it is selected for its family
+    /// and variant rather than shadowed by an earlier one.
+    pub fn list_fonts(&self) -> Vec<FontEntry> {
+        let mut seen = HashSet::new();
+        let infos = (0..).map_while(|idx| self.book.info(idx));
+        infos
+            .zip(&self.slots)
+            .map(|(info, &(layer, _))| FontEntry {
+                family: info.family.clone(),
+                style: info.variant.style,
+                weight: info.variant.weight,
+                stretch: info.variant.stretch,
+                font_path: self.layers[layer].0.clone(),
+                selected: seen.insert((info.family.to_lowercase(), info.variant)),
+            })
+            .collect()
+    }
+}
+
+/// A font available to the compiler.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FontEntry {
+    /// The family of the font.
+    pub family: String,
+    /// The style of the font.
+    pub style: FontStyle,
+    /// The weight of the font.
+    pub weight: FontWeight,
+    /// The stretch of the font.
+    pub stretch: FontStretch,
+    /// The font path the font is resolved from. It is None for the system and
+    /// embedded fonts.
+    pub font_path: Option<PathBuf>,
+    /// Whether the font is selected for its family and variant, rather than
+    /// shadowed by a font of the same family and variant with higher
+    /// precedence.
+    pub selected: bool,
 }
 
 /// type trait of [`LspWorld`].
@@ -103,9 +210,257 @@ impl typst_ts_compiler::world::CompilerFeat for SystemCompilerFeat {
     /// Uses [`SharedFontResolver`] directly.
     type FontResolver = SharedFontResolver;
     /// It accesses a physical file system.
-    type AccessModel = SystemAccessModel;
+    type AccessModel = LspAccessModel;
     /// It performs native HTTP requests for fetching package data.
-    type Registry = HttpRegistry;
+    type Registry = LspRegistry;
+}
+
+/// The access model of [`LspWorld`], which resolves resources under the root
+/// from a workspace base directory if it is set.
+///
+/// Typst source files are still resolved from the root, so imports are not
+/// affected. Files in packages are not under the root and are not affected
+/// either.
+pub struct LspAccessModel {
+    inner: SystemAccessModel,
+    /// The workspace base to resolve resources from.
+    base: WorkspaceBase,
+}
+
+impl LspAccessModel {
+    fn resolve<'a>(&self, src: &'a Path) -> Cow<'a, Path> {
+        self.base
+            .resolve(src)
+            .map_or(Cow::Borrowed(src), Cow::Owned)
+    }
+}
+
+impl AccessModel for LspAccessModel {
+    type RealPath = <SystemAccessModel as AccessModel>::RealPath;
+
+    fn mtime(&self, src: &Path) -> FileResult<Time> {
+        self.inner.mtime(&self.resolve(src))
+    }
+
+    fn is_file(&self, src: &Path) -> FileResult<bool> {
+        self.inner.is_file(&self.resolve(src))
+    }
+
+    fn real_path(&self, src: &Path) -> FileResult<Self::RealPath> {
+        self.inner.real_path(&self.resolve(src))
+    }
+
+    fn content(&self, src: &Path) -> FileResult<Bytes> {
+        self.inner.content(&self.resolve(src))
+    }
+}
+
+/// The workspace base of [`LspAccessModel`] along with the root of the entry,
+/// which is shared with the compiler actor watching the resolved files, and
+/// changed at runtime.
+#[derive(Debug, Clone, Default)]
+pub struct WorkspaceBase(Arc<RwLock<(Option<ImmutPath>, Option<ImmutPath>)>>);
+
+impl WorkspaceBase {
+    /// Creates a workspace base for the root of an entry.
+    pub fn new(root: Option<ImmutPath>, base: Option<ImmutPath>) -> Self {
+        Self(Arc::new(RwLock::new((root, base))))
+    }
+
+    /// Changes the root of the entry.
+    pub fn set_root(&self, root: Option<ImmutPath>) {
+        self.0.write().0 = root;
+    }
+
+    /// Changes the workspace base, or unsets it.
+    pub fn set_base(&self, base: Option<ImmutPath>) {
+        self.0.write().1 = base;
+    }
+
+    /// Resolves a resource under the root to the file under the workspace base,
+    /// returning None if it is not redirected. Typst source files are not
+    /// redirected.
+    pub fn resolve(&self, src: &Path) -> Option<PathBuf> {
+        let (Some(root), Some(base)) = &*self.0.read() else {
+            return None;
+        };
+        if is_source(src) {
+            return None;
+        }
+
+        Some(base.join(src.strip_prefix(root).ok()?))
+    }
+
+    /// Maps a file under the workspace base back to the resource under the
+    /// root, which is the reverse of [`Self::resolve`].
+    pub fn unresolve(&self, path: &Path) -> Option<PathBuf> {
+        let (Some(root), Some(base)) = &*self.0.read() else {
+            return None;
+        };
+        if is_source(path) {
+            return None;
+        }
+
+        Some(root.join(path.strip_prefix(base).ok()?))
+    }
+}
+
+/// Checks whether a file is a typst source file, which is always resolved from
+/// the root.
+fn is_source(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "typ")
+}
+
+/// The maximum number of retries on transient failures of downloading
+/// packages.
+const MAX_PACKAGE_RETRIES: u32 = 3;
+/// The delay before the first retry, which is doubled on each retry.
+const PACKAGE_RETRY_DELAY: Duration = Duration::from_millis(500);
+
+/// The package registry of [`LspWorld`], which refuses to download packages in
+/// offline mode, and retries on transient network failures otherwise.
+///
+/// The compiler thread never waits for the retries, which are run by a worker
+/// thread per package, so that the package is ready for the next compilation.
+///
+/// The registry is cheap to clone, so that packages can be downloaded off the
+/// compiler thread.
+#[derive(Default, Clone)]
+pub struct LspRegistry {
+    inner: Arc<HttpRegistry>,
+    /// Whether to use only the packages available locally.
+    pub offline: bool,
+    /// The packages being downloaded again by the workers.
+    retrying: Arc<Mutex<HashSet<PackageSpec>>>,
+}
+
+impl Deref for LspRegistry {
+    type Target = HttpRegistry;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl LspRegistry {
+    /// Resolves a package, retrying on transient failures on the calling
+    /// thread, which must not be the compiler thread.
+    pub fn resolve_with_retries(&self, spec: &PackageSpec) -> Result<Arc<Path>, PackageError> {
+        self.check_offline(spec)?;
+        retry_resolve(&self.inner, spec)
+    }
+
+    /// Refuses to resolve a package which is not available locally in
+    /// offline mode.
+    fn check_offline(&self, spec: &PackageSpec) -> Result<(), PackageError> {
+        if self.offline && !is_package_available(spec) {
+            return Err(PackageError::Other(Some(eco_format!(
+                "package {spec} is not available locally, and downloading is disabled in offline mode"
+            ))));
+        }
+
+        Ok(())
+    }
+
+    /// Downloads a package again on a worker thread, unless it is already
+    /// being downloaded.
+    fn retry_in_background(&self, spec: &PackageSpec) {
+        if !self.retrying.lock().insert(spec.clone()) {
+            return;
+        }
+
+        let inner = self.inner.clone();
+        let retrying = self.retrying.clone();
+        let spec = spec.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(PACKAGE_RETRY_DELAY);
+            if retry_resolve(&inner, &spec).is_ok() {
+                log::info!("resolved package {spec} on retry");
+            }
+            retrying.lock().remove(&spec);
+        });
+    }
+}
+
+impl Registry for LspRegistry {
+    fn reset(&mut self) {
+        match Arc::get_mut(&mut self.inner) {
+            Some(inner) => inner.reset(),
+            None => self.inner = Arc::default(),
+        }
+    }
+
+    fn resolve(&self, spec: &PackageSpec) -> Result<Arc<Path>, PackageError> {
+        self.check_offline(spec)?;
+
+        match self.inner.resolve(spec) {
+            Err(err) if !self.offline && is_transient(&err) => {
+                log::warn!("failed to resolve package {spec}: {err}, retrying in background");
+                self.retry_in_background(spec);
+                Err(err)
+            }
+            res => res,
+        }
+    }
+
+    fn packages(&self) -> &[(PackageSpec, Option<EcoString>)] {
+        // The package index is fetched from the network.
+        if self.offline {
+            return &[];
+        }
+
+        self.inner.packages()
+    }
+}
+
+/// Resolves a package, retrying on transient failures with a growing delay.
+fn retry_resolve(registry: &HttpRegistry, spec: &PackageSpec) -> Result<Arc<Path>, PackageError> {
+    let mut delay = PACKAGE_RETRY_DELAY;
+    let mut retries = 0;
+    loop {
+        match registry.resolve(spec) {
+            Err(err) if retries < MAX_PACKAGE_RETRIES && is_transient(&err) => {
+                retries += 1;
+                log::warn!(
+                    "failed to resolve package {spec}: {err}, retrying ({retries}/{MAX_PACKAGE_RETRIES}) in {delay:?}"
+                );
+                std::thread::sleep(delay);
+                delay *= 2;
+            }
+            res => return res,
+        }
+    }
+}
+
+/// Checks whether a failure of downloading a package may succeed on retry, such
+/// as timeouts and server errors. Missing packages, client errors and malformed
+/// archives are permanent.
+fn is_transient(err: &PackageError) -> bool {
+    match err {
+        PackageError::NetworkFailed(Some(msg)) => !msg.contains("client error"),
+        PackageError::NetworkFailed(None) => true,
+        _ => false,
+    }
+}
+
+/// Gets the directory of the versions of a package, relative to the data or
+/// cache directory.
+pub(crate) fn package_subdir(namespace: &str, name: &str) -> PathBuf {
+    Path::new("typst/packages").join(namespace).join(name)
+}
+
+/// Gets the directory of a package, relative to the data or cache directory.
+pub(crate) fn package_version_subdir(spec: &PackageSpec) -> PathBuf {
+    package_subdir(&spec.namespace, &spec.name).join(spec.version.to_string())
+}
+
+/// Checks whether a package is in the local data or cache directory.
+fn is_package_available(spec: &PackageSpec) -> bool {
+    let subdir = package_version_subdir(spec);
+    [dirs::data_dir(), dirs::cache_dir()]
+        .into_iter()
+        .flatten()
+        .any(|dir| dir.join(&subdir).exists())
 }
 
 /// The compiler world in system environment.
@@ -120,21 +475,44 @@ impl LspWorldBuilder {
     /// Create [`LspWorld`] with the given options.
     /// See SystemCompilerFeat for instantiation details.
     /// See [`CompileOpts`] for available options.
+    ///
+    /// If `workspace_base` is set, resources under the root of the entry are
+    /// resolved from it. See [`LspAccessModel`] for details.
     pub fn build(
         entry: EntryState,
         font_resolver: SharedFontResolver,
         inputs: ImmutDict,
+        workspace_base: WorkspaceBase,
     ) -> ZResult<LspWorld> {
+        let access_model = LspAccessModel {
+            inner: SystemAccessModel {},
+            base: workspace_base,
+        };
         let mut res = CompilerWorld::new_raw(
             entry,
-            Vfs::new(SystemAccessModel {}),
-            HttpRegistry::default(),
+            Vfs::new(access_model),
+            LspRegistry::default(),
             font_resolver,
         );
         res.inputs = inputs;
         Ok(res)
     }
 
+    /// Resolve the fonts in a font path only.
+    pub(crate) fn resolve_font_path(
+        opts: &CompileFontOpts,
+        path: &Path,
+    ) -> ZResult<FontResolverImpl> {
+        let mut searcher = SystemFontSearcher::new();
+        searcher.resolve_opts(FontOptsInner {
+            font_profile_cache_path: opts.font_profile_cache_path.clone(),
+            font_paths: vec![path.to_owned()],
+            no_system_fonts: true,
+            with_embedded_fonts: vec![],
+        })?;
+        Ok(searcher.into())
+    }
+
     /// Resolve fonts from given options.
     pub(crate) fn resolve_fonts(opts: CompileFontOpts) -> ZResult<FontResolverImpl> {
         let mut searcher = SystemFontSearcher::new();
@@ -147,3 +525,47 @@ impl LspWorldBuilder {
         Ok(searcher.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_base() {
+        let base = WorkspaceBase::default();
+        assert_eq!(base.resolve(Path::new("/root/a.png")), None);
+
+        base.set_root(Some(Path::new("/root").into()));
+        base.set_base(Some(Path::new("/root/assets").into()));
+        let resolved = base.resolve(Path::new("/root/img/a.png"));
+        assert_eq!(
+            resolved.as_deref(),
+            Some(Path::new("/root/assets/img/a.png"))
+        );
+        let unresolved = base.unresolve(Path::new("/root/assets/img/a.png"));
+        assert_eq!(unresolved.as_deref(), Some(Path::new("/root/img/a.png")));
+
+        assert_eq!(base.resolve(Path::new("/root/main.typ")), None);
+        assert_eq!(base.unresolve(Path::new("/root/assets/lib.typ")), None);
+        assert_eq!(base.resolve(Path::new("/other/a.png")), None);
+        assert_eq!(base.unresolve(Path::new("/root/a.png")), None);
+    }
+
+    #[test]
+    fn test_transient_package_errors() {
+        let spec: PackageSpec = "@preview/example:0.1.0".parse().unwrap();
+        let network = |msg: &str| PackageError::NetworkFailed(Some(msg.into()));
+
+        assert!(is_transient(&PackageError::NetworkFailed(None)));
+        assert!(is_transient(&network("operation timed out")));
+        assert!(is_transient(&network(
+            "HTTP status server error (503 Service Unavailable)"
+        )));
+
+        assert!(!is_transient(&network(
+            "HTTP status client error (403 Forbidden)"
+        )));
+        assert!(!is_transient(&PackageError::NotFound(spec)));
+        assert!(!is_transient(&PackageError::MalformedArchive(None)));
+    }
+}
@@ -632,6 +632,13 @@ const STR_VARIABLES = [
 ];
 const STR_ARR_VARIABLES = ["fontPaths", "tinymist.fontPaths"];
 const PREFERRED_THEME = ["preferredTheme", "tinymist.preferredTheme"];
+// The settings running programs, which are ignored in untrusted workspaces.
+const UNTRUSTED_RESTRICTED = [
+    "postExportCommand",
+    "tinymist.postExportCommand",
+    "enablePostExportCommand",
+    "tinymist.enablePostExportCommand",
+];
 
 // todo: documentation that, typstExtraArgs won't get variable extended
 function substVscodeVarsInConfig(keys: (string | undefined)[], values: unknown[]): unknown[] {
@@ -640,6 +647,9 @@ function substVscodeVarsInConfig(keys: (string | undefined)[], values: unknown[]
         if (!k) {
             return value;
         }
+        if (UNTRUSTED_RESTRICTED.includes(k) && !workspace.isTrusted) {
+            return null;
+        }
         if (PREFERRED_THEME.includes(k)) {
             return determineVscodeTheme();
         }